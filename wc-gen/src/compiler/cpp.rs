@@ -1,58 +1,129 @@
-use super::compile_identifier;
+use super::dependency_graph::DependencyGraph;
+use super::{compile_identifier, compile_snake_case, local_name, module_path};
 use crate::{
-    definition::{field::Field, function::Function, struct_::Struct, type_::Type, FromLisp},
+    definition::{
+        attribute::Attribute,
+        constant::Constant,
+        constraint::ConstraintKind,
+        enum_::Enum,
+        expression::{Expression, ExpressionKind, Operator, Pattern},
+        field::Field,
+        flags::Flags,
+        function::{Contract, ContractKind, Function, Parameter, ParameterMode},
+        literal::Literal,
+        newtype::Newtype,
+        output::TargetLanguage,
+        struct_::Struct,
+        trait_::Trait,
+        type_::Type,
+        FromLisp,
+    },
     env::Environment,
 };
-use std::path::PathBuf;
+use lisper::Error;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+};
+
+/// The attributes the C++ backend understands. Any others are reported as warnings.
+const KNOWN_ATTRIBUTES: &[&str] = &["cpp-name", "repr"];
+
+/// Words C++ reserves, which `cpp-name` can not use.
+const CPP_KEYWORDS: &str = "alignas alignof and asm auto bitand bitor bool break case catch char class \
+    compl const constexpr const_cast continue decltype default delete do double dynamic_cast else enum \
+    explicit export extern false float for friend goto if inline int long mutable namespace new noexcept \
+    not nullptr operator or private protected public register reinterpret_cast return short signed \
+    sizeof static static_assert static_cast struct switch template this throw true try typedef typeid \
+    typename union unsigned using virtual void volatile while xor";
+
+/// A definition declared in a C++ scope, with its name, attributes and location.
+type ScopeMember<'a> = (&'a str, &'a [Attribute], &'a lisper::Location);
+
+/// Checks the `requires` and `ensures` conditions of functions. Define `WC_NO_CONTRACTS` to compile them out.
+const CONTRACTS_HPP: &str = "#pragma once
+// This file was generated by wc-gen. Do not modify this file manually.
+#include <cstdio>
+#include <cstdlib>
+
+// Define WC_NO_CONTRACTS to compile out the requires and ensures checks
+#ifdef WC_NO_CONTRACTS
+#define WC_CONTRACT(condition, message) ((void)0)
+#else
+#define WC_CONTRACT(condition, message) \\
+\tdo \\
+\t{ \\
+\t\tif (!(condition)) \\
+\t\t{ \\
+\t\t\tstd::fprintf(stderr, \"%s\\n\", message); \\
+\t\t\tstd::abort(); \\
+\t\t} \\
+\t} while (0)
+#endif
+";
 
 #[derive(Debug, Clone, PartialEq)]
 enum TypeReference {
     Reference(Type),
     ConstReference(Type),
     Pointer(Type),
+    NullablePointer(Type),
     Value(Type),
 }
 
-pub fn compile(output_folder: PathBuf, env: &Environment) {
-    let mut structs = env
-        .structs
-        .iter()
-        .map(|(_, s)| s.clone())
-        .collect::<Vec<_>>();
+/// How a type is needed by a header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Usage {
+    /// Only pointers or references are used, so a forward declaration is enough.
+    Declaration,
+    /// The full definition is required.
+    Definition,
+}
 
-    // Sort structs by dependencies
-    structs.sort_by(|a, b| {
-        let a_referenced_types = a.get_referenced_structs();
-        if a_referenced_types.contains(&b.name) {
-            return std::cmp::Ordering::Greater;
-        } else {
-            return std::cmp::Ordering::Less;
-        }
-    });
-    let structs = structs;
+pub fn compile(output_folder: PathBuf, env: &Environment) -> Result<(), Error> {
+    // C++ classes are generated per instantiation rather than as templates
+    let env = &env.monomorphize()?;
+    validate_enums(env)?;
+    validate_cpp_names(env)?;
+    let structs = sort_structs(env)?;
+    warn_unknown_attributes(env);
 
     // Add classes
     for s in structs.iter() {
-        let class = compile_cpp_class(env, s);
+        let class = compile_cpp_class(env, s)?;
 
         // Create directory
-        let class_folder = output_folder.join(compile_identifier(&s.name));
+        let class_folder = output_folder.join(get_class_folder(&s.name));
         std::fs::create_dir_all(&class_folder).unwrap();
 
-        let hpp_name = format!("{}.hpp", compile_identifier(&s.name));
+        let local = compile_identifier(local_name(&s.name));
+        let hpp_name = format!("{}.hpp", local);
         let cpp_name = {
-            let name = format!("{}_generated_impl.cpp", compile_identifier(&s.name));
+            let name = format!("{}_generated_impl.cpp", local);
             class_folder.join(name)
         };
         let custom_cpp_file = {
-            let name = format!("{}_custom_impl.cpp", compile_identifier(&s.name));
+            let name = format!("{}_custom_impl.cpp", local);
             class_folder.join(name)
         };
 
-        let mut includes = vec![];
+        // Headers sit in the module folder and implementations in the class folder below it
+        let depth = module_path(&s.name).len();
+        let dependencies = get_struct_dependencies(env, s);
+
+        // Used by the validation methods
+        let mut includes = vec![
+            "#include <string>".to_string(),
+            "#include <vector>".to_string(),
+        ];
         for (_, ty) in s.get_related_types() {
             includes.append(&mut get_type_includes(&ty));
         }
+        includes.append(&mut get_header_includes(
+            &dependencies,
+            &get_relative_prefix(depth),
+        ));
+        includes.append(&mut get_forward_declaration_includes(env, &dependencies));
         includes.sort();
         includes.dedup();
 
@@ -62,19 +133,52 @@ pub fn compile(output_folder: PathBuf, env: &Environment) {
             .collect::<Vec<_>>()
             .join("\n");
 
+        let forward_declarations = get_forward_declarations(env, &dependencies);
+        let mut implementation_dependencies = dependencies.clone();
+        let bodies = s
+            .functions
+            .values()
+            .flat_map(|f| f.body.iter().chain(f.contracts().map(|c| &c.condition)));
+        let uses_constants =
+            add_body_dependencies(env, &s.name, bodies, &mut implementation_dependencies);
+        implementation_dependencies.remove(&s.name);
+        let mut implementation_includes = get_implementation_includes(
+            &implementation_dependencies,
+            &get_relative_prefix(depth + 1),
+        );
+        if uses_constants {
+            implementation_includes.push_str(&format!(
+                "#include \"{}constants.hpp\"\n",
+                get_relative_prefix(depth + 1)
+            ));
+        }
+        let bodies = s.functions.values().flat_map(|f| f.body.iter());
+        if uses_operator(bodies, Operator::Remainder) {
+            implementation_includes.push_str("#include <cmath>\n");
+        }
+        if s.functions.values().any(|f| f.contracts().next().is_some()) {
+            implementation_includes.push_str(&format!(
+                "#include \"{}contracts.hpp\"\n",
+                get_relative_prefix(depth + 1)
+            ));
+        }
+
         let autogen_disclaimer =
             format!("// This file was generated by wc-gen. Do not modify this file manually.\n");
         let hpp_code = format!(
-            "#pragma once\n{}{}\n\n{}",
-            autogen_disclaimer, includes, class.header_definition
+            "#pragma once\n{}{}\n\n{}{}",
+            autogen_disclaimer,
+            includes,
+            forward_declarations,
+            wrap_namespace(&s.name, &class.header_definition)
         );
         let cpp_code = format!(
-            "{}\n#include \"../{}\"\n\n{}",
-            autogen_disclaimer, hpp_name, class.implementation
+            "{}\n#include \"../{}\"\n{}\n{}",
+            autogen_disclaimer, hpp_name, implementation_includes, class.implementation
         );
 
         // Remove old files
-        let hpp_path = output_folder.join(&hpp_name);
+        let hpp_path = output_folder.join(get_header_path(&s.name));
         let cpp_path = cpp_name;
 
         if hpp_path.exists() {
@@ -92,145 +196,1652 @@ pub fn compile(output_folder: PathBuf, env: &Environment) {
         // Write custom methods and preserve existing definitions.
         let custom_cpp_path = custom_cpp_file;
         if !custom_cpp_path.exists() {
-            let cpp_code = format!("#include \"../{}\"\n\n", hpp_name);
+            let cpp_code = format!(
+                "#include \"../{}\"\n{}\n",
+                hpp_name, implementation_includes
+            );
             std::fs::write(&custom_cpp_path, &cpp_code).unwrap();
         }
 
-        // Reload code, then if the method doesn't exist, add it.
-        let mut custom_code = std::fs::read_to_string(&custom_cpp_path).unwrap();
-        for method in class.custom_methods.iter() {
-            if !custom_code.contains(&method.function_definition) {
-                custom_code.push_str(&method.implementation);
-            }
-        }
-        std::fs::write(&custom_cpp_path, &custom_code).unwrap();
+        // Reload code, then if the method doesn't exist, add it.
+        let custom_code = std::fs::read_to_string(&custom_cpp_path).unwrap();
+        let custom_code = merge_custom_methods(&custom_code, &class.custom_methods)?;
+        std::fs::write(&custom_cpp_path, &custom_code).unwrap();
+    }
+
+    // Add function forward declarations
+    let mut functions = env
+        .functions
+        .iter()
+        .map(|(_, f)| f.clone())
+        .collect::<Vec<_>>();
+    functions.sort_by(|a, b| a.name.cmp(&b.name));
+
+    for f in functions.iter() {
+        let function = compile_cpp_function(env, f)?;
+        let hpp_name = format!("{}.hpp", compile_identifier(local_name(&f.name)));
+        let cpp_name = format!("{}.cpp", compile_identifier(local_name(&f.name)));
+
+        let module_folder = output_folder.join(module_path(&f.name).join("/"));
+        std::fs::create_dir_all(&module_folder).unwrap();
+        let prefix = get_relative_prefix(module_path(&f.name).len());
+
+        let mut dependencies = BTreeMap::new();
+        let mut includes = vec![];
+        for (_, ty) in f.get_related_types() {
+            includes.append(&mut get_type_includes(&ty));
+            add_type_dependencies(&ty, Usage::Declaration, &mut dependencies);
+        }
+        includes.append(&mut get_forward_declaration_includes(env, &dependencies));
+        includes.sort();
+        includes.dedup();
+
+        let includes = includes
+            .iter()
+            .map(|i| i.clone())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let forward_declarations = get_forward_declarations(env, &dependencies);
+        let mut implementation_dependencies = dependencies.clone();
+        let bodies = f.body.iter().chain(f.contracts().map(|c| &c.condition));
+        let uses_constants =
+            add_body_dependencies(env, &f.name, bodies, &mut implementation_dependencies);
+        implementation_dependencies.remove(&f.name);
+        let mut implementation_includes =
+            get_implementation_includes(&implementation_dependencies, &prefix);
+        if uses_constants {
+            implementation_includes.push_str(&format!("#include \"{}constants.hpp\"\n", prefix));
+        }
+        if uses_operator(f.body.iter(), Operator::Remainder) {
+            implementation_includes.push_str("#include <cmath>\n");
+        }
+        if f.contracts().next().is_some() {
+            implementation_includes.push_str(&format!("#include \"{}contracts.hpp\"\n", prefix));
+        }
+
+        let hpp_code = format!(
+            "#pragma once\n{}\n\n{}{}",
+            includes,
+            forward_declarations,
+            wrap_namespace(&f.name, &function.header_declaration)
+        );
+        let cpp_code = format!(
+            "#include \"{}\"\n{}\n{}",
+            hpp_name, implementation_includes, function.implementation
+        );
+
+        // Remove old files
+        let hpp_path = module_folder.join(&hpp_name);
+        let cpp_path = module_folder.join(&cpp_name);
+
+        if hpp_path.exists() {
+            std::fs::remove_file(&hpp_path).unwrap();
+        }
+
+        if cpp_path.exists() {
+            std::fs::remove_file(&cpp_path).unwrap();
+        }
+
+        std::fs::write(hpp_path, hpp_code).unwrap();
+        std::fs::write(cpp_path, cpp_code).unwrap();
+    }
+
+    // Add aliases so engine code can keep using the alias names
+    let mut aliases = env.aliases.values().cloned().collect::<Vec<_>>();
+    aliases.sort_by(|a, b| a.name.cmp(&b.name));
+
+    for a in aliases.iter() {
+        let ty = env.resolve_aliases(&a.type_.1);
+        let prefix = get_relative_prefix(module_path(&a.name).len());
+
+        let mut dependencies = BTreeMap::new();
+        add_type_dependencies(&ty, Usage::Definition, &mut dependencies);
+
+        let mut includes = get_type_includes(&ty);
+        includes.append(&mut get_header_includes(&dependencies, &prefix));
+        includes.sort();
+        includes.dedup();
+
+        let hpp_code = format!(
+            "#pragma once\n// This file was generated by wc-gen. Do not modify this file manually.\n{}\n\n{}",
+            includes.join("\n"),
+            wrap_namespace(
+                &a.name,
+                &format!(
+                    "using {} = {};\n",
+                    compile_identifier(local_name(&a.name)),
+                    compile_cpp_type(&ty)
+                )
+            )
+        );
+
+        let hpp_path = output_folder.join(get_header_path(&a.name));
+        std::fs::create_dir_all(hpp_path.parent().unwrap()).unwrap();
+        if hpp_path.exists() {
+            std::fs::remove_file(&hpp_path).unwrap();
+        }
+        std::fs::write(hpp_path, hpp_code).unwrap();
+    }
+
+    // Add newtypes
+    let mut newtypes = env.newtypes.values().cloned().collect::<Vec<_>>();
+    newtypes.sort_by(|a, b| a.name.cmp(&b.name));
+
+    for n in newtypes.iter() {
+        let class = compile_cpp_newtype(n);
+
+        // Create directory
+        let class_folder = output_folder.join(get_class_folder(&n.name));
+        std::fs::create_dir_all(&class_folder).unwrap();
+
+        let local = compile_identifier(local_name(&n.name));
+        let hpp_name = format!("{}.hpp", local);
+        let cpp_name = {
+            let name = format!("{}_generated_impl.cpp", local);
+            class_folder.join(name)
+        };
+        let prefix = get_relative_prefix(module_path(&n.name).len());
+
+        // The wrapped value is stored directly
+        let mut dependencies = BTreeMap::new();
+        add_type_dependencies(&n.type_.1, Usage::Definition, &mut dependencies);
+        dependencies.remove(&n.name);
+
+        let mut includes = get_type_includes(&n.type_.1);
+        includes.append(&mut get_header_includes(&dependencies, &prefix));
+        includes.sort();
+        includes.dedup();
+        let includes = includes.join("\n");
+
+        let autogen_disclaimer =
+            "// This file was generated by wc-gen. Do not modify this file manually.\n";
+        let hpp_code = format!(
+            "#pragma once\n{}{}\n\n{}",
+            autogen_disclaimer,
+            includes,
+            wrap_namespace(&n.name, &class.header_definition)
+        );
+        let cpp_code = format!(
+            "{}\n#include \"../{}\"\n\n{}",
+            autogen_disclaimer, hpp_name, class.implementation
+        );
+
+        // Remove old files
+        let hpp_path = output_folder.join(get_header_path(&n.name));
+        let cpp_path = cpp_name;
+
+        if hpp_path.exists() {
+            std::fs::remove_file(&hpp_path).unwrap();
+        }
+
+        if cpp_path.exists() {
+            std::fs::remove_file(&cpp_path).unwrap();
+        }
+
+        std::fs::write(hpp_path, hpp_code).unwrap();
+        std::fs::write(cpp_path, cpp_code).unwrap();
+    }
+
+    // Add simple enums
+    let mut enums = env
+        .enums
+        .values()
+        .filter(|e| e.is_simple())
+        .cloned()
+        .collect::<Vec<_>>();
+    enums.sort_by(|a, b| a.name.cmp(&b.name));
+
+    for e in enums.iter() {
+        let repr = e.repr()?;
+
+        // Used by the enum traits
+        let mut includes = vec![
+            "#include <array>".to_string(),
+            "#include <cstddef>".to_string(),
+            "#include <optional>".to_string(),
+            "#include <string_view>".to_string(),
+        ];
+        if let Some(repr) = &repr {
+            includes.append(&mut get_type_includes(repr));
+        }
+        includes.sort();
+        includes.dedup();
+
+        let code = format!(
+            "{}\n{}",
+            compile_cpp_enum(e, &repr),
+            compile_cpp_enum_traits(e)
+        );
+        let hpp_code = format!(
+            "#pragma once\n// This file was generated by wc-gen. Do not modify this file manually.\n{}\n\n{}",
+            includes.join("\n"),
+            wrap_namespace(&e.name, &code)
+        );
+
+        let hpp_path = output_folder.join(get_header_path(&e.name));
+        std::fs::create_dir_all(hpp_path.parent().unwrap()).unwrap();
+        if hpp_path.exists() {
+            std::fs::remove_file(&hpp_path).unwrap();
+        }
+        std::fs::write(hpp_path, hpp_code).unwrap();
+    }
+
+    // Add complex enums as classes holding a variant
+    let mut enums = env
+        .enums
+        .values()
+        .filter(|e| !e.is_simple())
+        .cloned()
+        .collect::<Vec<_>>();
+    enums.sort_by(|a, b| a.name.cmp(&b.name));
+
+    for e in enums.iter() {
+        let class = compile_cpp_complex_enum(env, e);
+
+        // Create directory
+        let class_folder = output_folder.join(get_class_folder(&e.name));
+        std::fs::create_dir_all(&class_folder).unwrap();
+
+        let local = compile_identifier(local_name(&e.name));
+        let hpp_name = format!("{}.hpp", local);
+        let cpp_path = class_folder.join(format!("{}_generated_impl.cpp", local));
+
+        let depth = module_path(&e.name).len();
+        let dependencies = get_enum_dependencies(env, e);
+
+        // Used by the variant and the enum traits
+        let mut includes = vec![
+            "#include <array>".to_string(),
+            "#include <cstddef>".to_string(),
+            "#include <optional>".to_string(),
+            "#include <string_view>".to_string(),
+            "#include <variant>".to_string(),
+        ];
+        for (_, ty) in e.get_related_types() {
+            includes.append(&mut get_type_includes(&ty));
+        }
+        includes.append(&mut get_header_includes(
+            &dependencies,
+            &get_relative_prefix(depth),
+        ));
+        includes.append(&mut get_forward_declaration_includes(env, &dependencies));
+        includes.sort();
+        includes.dedup();
+
+        let autogen_disclaimer =
+            "// This file was generated by wc-gen. Do not modify this file manually.\n";
+        let hpp_code = format!(
+            "#pragma once\n{}{}\n\n{}{}",
+            autogen_disclaimer,
+            includes.join("\n"),
+            get_forward_declarations(env, &dependencies),
+            wrap_namespace(&e.name, &class.header_definition)
+        );
+        let cpp_code = format!(
+            "{}\n#include \"../{}\"\n{}\n{}",
+            autogen_disclaimer,
+            hpp_name,
+            get_implementation_includes(&dependencies, &get_relative_prefix(depth + 1)),
+            wrap_namespace(&e.name, &class.implementation)
+        );
+
+        // Remove old files
+        let hpp_path = output_folder.join(get_header_path(&e.name));
+
+        if hpp_path.exists() {
+            std::fs::remove_file(&hpp_path).unwrap();
+        }
+
+        if cpp_path.exists() {
+            std::fs::remove_file(&cpp_path).unwrap();
+        }
+
+        std::fs::write(hpp_path, hpp_code).unwrap();
+        std::fs::write(cpp_path, cpp_code).unwrap();
+    }
+
+    // Add traits as abstract base classes
+    let mut traits = env.traits.values().cloned().collect::<Vec<_>>();
+    traits.sort_by(|a, b| a.name.cmp(&b.name));
+
+    for t in traits.iter() {
+        let prefix = get_relative_prefix(module_path(&t.name).len());
+
+        let mut dependencies = BTreeMap::new();
+        let mut includes = vec![];
+        for (_, ty) in t.get_related_types() {
+            includes.append(&mut get_type_includes(&ty));
+            add_type_dependencies(&ty, Usage::Declaration, &mut dependencies);
+        }
+        includes.append(&mut get_header_includes(&dependencies, &prefix));
+        includes.append(&mut get_forward_declaration_includes(env, &dependencies));
+        includes.sort();
+        includes.dedup();
+
+        let hpp_code = format!(
+            "#pragma once\n// This file was generated by wc-gen. Do not modify this file manually.\n{}\n\n{}{}",
+            includes.join("\n"),
+            get_forward_declarations(env, &dependencies),
+            wrap_namespace(&t.name, &compile_cpp_trait(t))
+        );
+
+        let hpp_path = output_folder.join(get_header_path(&t.name));
+        std::fs::create_dir_all(hpp_path.parent().unwrap()).unwrap();
+        if hpp_path.exists() {
+            std::fs::remove_file(&hpp_path).unwrap();
+        }
+        std::fs::write(hpp_path, hpp_code).unwrap();
+    }
+
+    // Add flags
+    let mut flags = env.flags.values().cloned().collect::<Vec<_>>();
+    flags.sort_by(|a, b| a.name.cmp(&b.name));
+
+    for f in flags.iter() {
+        let class = compile_cpp_flags(f);
+
+        // Create directory
+        let class_folder = output_folder.join(get_class_folder(&f.name));
+        std::fs::create_dir_all(&class_folder).unwrap();
+
+        let local = compile_identifier(local_name(&f.name));
+        let hpp_name = format!("{}.hpp", local);
+        let cpp_path = class_folder.join(format!("{}_generated_impl.cpp", local));
+
+        let autogen_disclaimer =
+            "// This file was generated by wc-gen. Do not modify this file manually.\n";
+        let hpp_code = format!(
+            "#pragma once\n{}#include <stdint.h>\n#include <string>\n#include <vector>\n\n{}",
+            autogen_disclaimer,
+            wrap_namespace(&f.name, &class.header_definition)
+        );
+        let cpp_code = format!(
+            "{}\n#include \"../{}\"\n\n{}",
+            autogen_disclaimer, hpp_name, class.implementation
+        );
+
+        // Remove old files
+        let hpp_path = output_folder.join(get_header_path(&f.name));
+
+        if hpp_path.exists() {
+            std::fs::remove_file(&hpp_path).unwrap();
+        }
+
+        if cpp_path.exists() {
+            std::fs::remove_file(&cpp_path).unwrap();
+        }
+
+        std::fs::write(hpp_path, hpp_code).unwrap();
+        std::fs::write(cpp_path, cpp_code).unwrap();
+    }
+
+    // Add constants
+    if !env.constants.is_empty() {
+        let mut constants = env.constants.values().cloned().collect::<Vec<_>>();
+        constants.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut dependencies = BTreeMap::new();
+        let mut includes = vec![];
+        let mut code = String::new();
+        for c in constants.iter() {
+            includes.append(&mut get_type_includes(&env.underlying_type(&c.type_.1)));
+            add_type_dependencies(&c.type_.1, Usage::Definition, &mut dependencies);
+            code.push_str(&wrap_namespace(&c.name, &compile_cpp_constant(env, c)));
+        }
+        includes.append(&mut get_header_includes(&dependencies, ""));
+        includes.sort();
+        includes.dedup();
+
+        let hpp_code = format!(
+            "#pragma once\n// This file was generated by wc-gen. Do not modify this file manually.\n{}\n\n{}",
+            includes.join("\n"),
+            code
+        );
+
+        let hpp_path = output_folder.join("constants.hpp");
+        if hpp_path.exists() {
+            std::fs::remove_file(&hpp_path).unwrap();
+        }
+        std::fs::write(hpp_path, hpp_code).unwrap();
+    }
+
+    // Add the macro that checks function contracts
+    let has_contracts = env
+        .functions
+        .values()
+        .chain(env.structs.values().flat_map(|s| s.functions.values()))
+        .any(|f| f.contracts().next().is_some());
+    if has_contracts {
+        let hpp_path = output_folder.join("contracts.hpp");
+        if hpp_path.exists() {
+            std::fs::remove_file(&hpp_path).unwrap();
+        }
+        std::fs::write(hpp_path, CONTRACTS_HPP).unwrap();
+    }
+
+    Ok(())
+}
+
+fn compile_cpp_constant(env: &Environment, c: &Constant) -> String {
+    let name = compile_identifier(local_name(&c.name));
+    let value = compile_value(env, &c.value.1, &c.type_.1);
+
+    match &c.type_.1 {
+        Type::Identifier(_) | Type::String => format!(
+            "static const {} {} = {};\n",
+            compile_cpp_type(&c.type_.1),
+            name,
+            value
+        ),
+        ty => format!("constexpr {} {} = {};\n", compile_cpp_type(ty), name, value),
+    }
+}
+
+/// Compiles a literal into a value of the given type, wrapping it in any newtypes.
+fn compile_value(env: &Environment, literal: &Literal, ty: &Type) -> String {
+    match ty {
+        Type::Identifier(name) => match env.newtypes.get(name) {
+            Some(n) => format!(
+                "{}({})",
+                compile_identifier(name),
+                compile_value(env, literal, &n.type_.1)
+            ),
+            None => compile_literal(literal, ty),
+        },
+        ty => compile_literal(literal, ty),
+    }
+}
+
+fn compile_literal(literal: &Literal, ty: &Type) -> String {
+    match literal {
+        Literal::Bool(value) => value.to_string(),
+        Literal::Int(value) => match ty {
+            Type::Float => format!("{}.0f", value),
+            // The literal is negated after it is read, so the minimum does not fit on its own
+            Type::I64 if *value == i64::MIN as i128 => format!("({}LL - 1)", value + 1),
+            Type::I64 => format!("{}LL", value),
+            Type::U32 => format!("{}u", value),
+            Type::U64 => format!("{}ULL", value),
+            _ => value.to_string(),
+        },
+        Literal::Float(value) => format!("{:?}f", value),
+        Literal::String(value) => format!(
+            "\"{}\"",
+            value
+                .replace("\\", "\\\\")
+                .replace("\"", "\\\"")
+                .replace("\n", "\\n")
+        ),
+    }
+}
+
+/// Sorts structs so that every struct comes after the structs its header must include.
+fn sort_structs(env: &Environment) -> Result<Vec<Struct>, Error> {
+    let mut graph = DependencyGraph::new();
+    for (name, s) in env.structs.iter() {
+        graph.add_node(name);
+        for (dependency, usage) in get_struct_dependencies(env, s) {
+            if usage == Usage::Definition && env.structs.contains_key(&dependency) {
+                graph.add_dependency(name, &dependency);
+            }
+        }
+    }
+
+    match graph.sort() {
+        Ok(order) => Ok(order
+            .iter()
+            .filter_map(|name| env.structs.get(name))
+            .cloned()
+            .collect()),
+        Err(cycle) => {
+            let location = env
+                .structs
+                .get(&cycle[0])
+                .map(|s| s.location.clone())
+                .unwrap_or_default();
+
+            Err(Error {
+                message: format!(
+                    "Unresolvable include cycle between headers: {}",
+                    cycle
+                        .iter()
+                        .map(|name| get_header_path(name))
+                        .collect::<Vec<_>>()
+                        .join(" -> ")
+                ),
+                location,
+            })
+        }
+    }
+}
+
+/// Returns the types a struct depends on and how they are used by its header.
+fn get_struct_dependencies(env: &Environment, s: &Struct) -> BTreeMap<String, Usage> {
+    let mut dependencies = BTreeMap::new();
+
+    // Pointers only need declarations, while values need the full definition.
+    for field in s.fields.values() {
+        let usage = match map_struct_field_type(env, field) {
+            TypeReference::Value(_) => Usage::Definition,
+            _ => Usage::Declaration,
+        };
+        add_type_dependencies(&field.type_, usage, &mut dependencies);
+    }
+
+    // Methods take references and only need declarations.
+    for f in s.functions.values() {
+        for (_, ty) in f.get_related_types() {
+            add_type_dependencies(&ty, Usage::Declaration, &mut dependencies);
+        }
+    }
+
+    // Embed accessors return the embedded struct and only need declarations.
+    for embed in s.embeds.iter() {
+        add_type_dependencies(&embed.type_, Usage::Declaration, &mut dependencies);
+    }
+
+    // Base classes must be defined before the class.
+    for t in env.get_struct_traits(&s.name) {
+        add_type_dependencies(
+            &Type::Identifier(t.name.clone()),
+            Usage::Definition,
+            &mut dependencies,
+        );
+    }
+
+    // The class declares itself.
+    dependencies.remove(&s.name);
+
+    dependencies
+}
+
+fn add_type_dependencies(ty: &Type, usage: Usage, dependencies: &mut BTreeMap<String, Usage>) {
+    match ty {
+        Type::Identifier(name) => {
+            let existing = dependencies.entry(name.clone()).or_insert(usage);
+            *existing = (*existing).max(usage);
+        }
+        Type::List(inner) | Type::Optional(inner) => {
+            add_type_dependencies(inner, usage, dependencies)
+        }
+        _ => {}
+    }
+}
+
+/// Returns the path of a definition's header relative to the output folder, such as `combat/Move.hpp`.
+fn get_header_path(name: &str) -> String {
+    let mut parts = module_path(name)
+        .iter()
+        .map(|m| m.to_string())
+        .collect::<Vec<_>>();
+    parts.push(format!("{}.hpp", compile_identifier(local_name(name))));
+    parts.join("/")
+}
+
+/// Returns the folder of a class's implementation files relative to the output folder, such as `combat/Move`.
+fn get_class_folder(name: &str) -> PathBuf {
+    let mut folder = PathBuf::new();
+    for module in module_path(name) {
+        folder.push(module);
+    }
+    folder.push(compile_identifier(local_name(name)));
+    folder
+}
+
+/// Returns the prefix that leads from a file nested `depth` folders deep back to the output folder.
+fn get_relative_prefix(depth: usize) -> String {
+    "../".repeat(depth)
+}
+
+/// Wraps the code in the namespace of the modules the definition is declared in.
+fn wrap_namespace(name: &str, code: &str) -> String {
+    let modules = module_path(name);
+    if modules.is_empty() {
+        return code.to_string();
+    }
+
+    format!("namespace {}\n{{\n{}}}\n", modules.join("::"), code)
+}
+
+fn get_header_includes(dependencies: &BTreeMap<String, Usage>, prefix: &str) -> Vec<String> {
+    dependencies
+        .iter()
+        .filter(|(_, usage)| **usage == Usage::Definition)
+        .map(|(name, _)| format!("#include \"{}{}\"", prefix, get_header_path(name)))
+        .collect()
+}
+
+fn get_forward_declarations(env: &Environment, dependencies: &BTreeMap<String, Usage>) -> String {
+    let mut code = String::new();
+    for (name, usage) in dependencies.iter() {
+        if *usage == Usage::Declaration {
+            // Enums are declared opaquely with the same underlying type as their definition
+            let declaration = match env.enums.get(name).filter(|e| e.is_simple()) {
+                Some(e) => match e.repr().ok().flatten() {
+                    Some(repr) => format!(
+                        "enum class {} : {};\n",
+                        compile_identifier(local_name(name)),
+                        compile_cpp_type(&repr)
+                    ),
+                    None => format!("enum class {};\n", compile_identifier(local_name(name))),
+                },
+                None => format!("class {};\n", compile_identifier(local_name(name))),
+            };
+            code.push_str(&wrap_namespace(name, &declaration));
+        }
+    }
+
+    if !code.is_empty() {
+        code.push('\n');
+    }
+
+    code
+}
+
+/// Returns the includes needed by the underlying types of forward declared enums.
+fn get_forward_declaration_includes(
+    env: &Environment,
+    dependencies: &BTreeMap<String, Usage>,
+) -> Vec<String> {
+    dependencies
+        .iter()
+        .filter(|(_, usage)| **usage == Usage::Declaration)
+        .filter_map(|(name, _)| env.enums.get(name).filter(|e| e.is_simple()))
+        .filter_map(|e| e.repr().ok().flatten())
+        .flat_map(|repr| get_type_includes(&repr))
+        .collect()
+}
+
+/// Returns the includes an implementation file needs for the full definitions of its dependencies.
+fn get_implementation_includes(dependencies: &BTreeMap<String, Usage>, prefix: &str) -> String {
+    let mut code = String::new();
+    for (name, _) in dependencies.iter() {
+        code.push_str(&format!(
+            "#include \"{}{}\"\n",
+            prefix,
+            get_header_path(name)
+        ));
+    }
+    code
+}
+
+struct Class {
+    pub header_definition: String,
+    pub implementation: String,
+    pub custom_methods: Vec<CustomMethod>,
+}
+struct CustomMethod {
+    pub function_definition: String,
+    pub implementation: String,
+    /// The definition the method had before contracts moved it to a `_impl` method, with the error reported if it
+    /// is still found in the custom implementation.
+    pub outdated: Option<(String, Error)>,
+}
+
+struct CppFunction {
+    pub header_declaration: String,
+    pub implementation: String,
+}
+
+struct ClassMethod {
+    pub header_declaration: String,
+    pub implementation: String,
+}
+
+fn compile_cpp_class(env: &Environment, s: &Struct) -> Result<Class, Error> {
+    let mut header_definition = String::new();
+    let mut implementation = String::new();
+
+    // Implemented traits are inherited as abstract base classes
+    let bases = env
+        .get_struct_traits(&s.name)
+        .iter()
+        .map(|t| format!("public {}", compile_identifier(&t.name)))
+        .collect::<Vec<_>>();
+    let bases = if bases.is_empty() {
+        String::new()
+    } else {
+        format!(" : {}", bases.join(", "))
+    };
+
+    header_definition.push_str(&compile_doc(&s.doc, ""));
+    header_definition.push_str(&format!(
+        "class {}{} \n{{\n",
+        compile_identifier(local_name(&s.name)),
+        bases
+    ));
+    header_definition.push_str("public:\n");
+    for (_, field) in s.fields.iter() {
+        let ty = map_struct_field_type(env, field);
+
+        header_definition.push_str(&compile_doc(&field.doc, "\t"));
+        header_definition.push_str(&format!(
+            "\t{} {};\n",
+            compile_type(ty),
+            compile_field_identifier(field)
+        ));
+    }
+
+    // Generate functions
+    let methods = [
+        generate_constructor(env, s),
+        generate_copy_constructor(s),
+        generate_destructor(env, s),
+        generate_copy_to(env, s),
+        generate_clone(s),
+        generate_equality_operator(env, s),
+        generate_inequality_operator(s),
+        generate_assignment_operator(s),
+        generate_validate(s),
+        generate_validate_into(env, s),
+    ];
+    for method in methods.iter() {
+        header_definition.push_str(&method.header_declaration);
+        implementation.push_str(&method.implementation);
+    }
+
+    // Embedded structs can be viewed on their own
+    for embed in s.embeds.iter() {
+        if let Some(embedded) = env.structs.get(&embed.type_.to_string()) {
+            let method = generate_embed_accessor(env, s, embedded);
+            header_definition.push_str(&method.header_declaration);
+            implementation.push_str(&method.implementation);
+        }
+    }
+
+    let mut custom_methods = vec![];
+    let mut private_declarations = String::new();
+    for (_name, f) in s.functions.iter() {
+        // Functions with a body are generated rather than implemented by hand
+        if !f.body.is_empty() {
+            let code = compile_contracts(env, Some(s), f, &compile_body(env, Some(s), f)?)?;
+            let method = generate_method(&s.name, f, &code);
+            header_definition.push_str(&compile_doc(&f.doc, "\t"));
+            header_definition.push_str(&method.header_declaration);
+            implementation.push_str(&method.implementation);
+            continue;
+        }
+
+        // Contracts are checked by a generated method that calls the custom implementation
+        let (method, outdated) = match f.contracts().next() {
+            Some(_) => {
+                let custom = get_custom_impl_function(f);
+                let call = compile_custom_impl_call(f, &custom);
+                let method =
+                    generate_method(&s.name, f, &compile_contracts(env, Some(s), f, &call)?);
+                header_definition.push_str(&compile_doc(&f.doc, "\t"));
+                header_definition.push_str(&method.header_declaration);
+                implementation.push_str(&method.implementation);
+
+                let method = generate_struct_fn(s, &custom);
+                private_declarations.push_str(&method.header_declaration);
+
+                let definition = generate_struct_fn(s, f).implementation;
+                let error = Error {
+                    message: format!(
+                        "Custom implementation of '{}::{}' must be renamed to '{}' now that it has contracts",
+                        compile_identifier(&s.name),
+                        compile_attribute_name(&f.attributes, &f.name),
+                        compile_identifier(&custom.name)
+                    ),
+                    location: f.location.clone(),
+                };
+                let outdated = (format_code(definition.lines().next().unwrap()), error);
+                (method, Some(outdated))
+            }
+            None => {
+                let method = generate_struct_fn(s, f);
+                header_definition.push_str(&compile_doc(&f.doc, "\t"));
+                header_definition.push_str(&method.header_declaration);
+                (method, None)
+            }
+        };
+
+        // Split off first line as we'll use that for checking if it exists
+        let function_definition = method.implementation.lines().next().unwrap().to_string();
+        custom_methods.push(CustomMethod {
+            function_definition: format_code(&function_definition),
+            implementation: format_code(&method.implementation),
+            outdated,
+        });
+        // TODO: check if existing implementation exists. If so, use that.
+        // implementation.push_str("\n/*\n");
+        // implementation.push_str(&method.implementation);
+        // implementation.push_str("*/\n");
+    }
+
+    if !private_declarations.is_empty() {
+        header_definition.push_str("private:\n");
+        header_definition.push_str(&private_declarations);
+    }
+    header_definition.push_str("};\n");
+
+    Ok(Class {
+        header_definition: format_code(&header_definition),
+        implementation: format_code(&implementation),
+        custom_methods,
+    })
+}
+
+/// Compiles a newtype into a wrapper class that can only be constructed explicitly.
+fn compile_cpp_newtype(n: &Newtype) -> Class {
+    let mut header_definition = String::new();
+    let mut implementation = String::new();
+
+    let class_name = compile_identifier(local_name(&n.name));
+    let value_type = n.type_.1.clone();
+    let self_type = Type::Identifier(n.name.clone());
+
+    header_definition.push_str(&format!("class {} \n{{\n", class_name));
+    header_definition.push_str("public:\n");
+    header_definition.push_str(&format!(
+        "\t{} value;\n",
+        compile_type(TypeReference::Value(value_type.clone()))
+    ));
+
+    let default_constructor = generate_class_method(
+        local_name(&n.name),
+        &n.name,
+        vec![],
+        None,
+        &format!("\tvalue = {};\n", get_type_default(&value_type)),
+        false,
+        false,
+        false,
+    );
+    header_definition.push_str(&default_constructor.header_declaration);
+    implementation.push_str(&default_constructor.implementation);
+
+    let parameter = if is_primitive(&value_type) {
+        TypeReference::Value(value_type.clone())
+    } else {
+        TypeReference::ConstReference(value_type.clone())
+    };
+    let value_constructor = generate_class_method(
+        local_name(&n.name),
+        &n.name,
+        vec![("value".to_string(), parameter)],
+        None,
+        "\tthis->value = value;\n",
+        false,
+        false,
+        false,
+    );
+    header_definition.push_str(&format!(
+        "\texplicit {}",
+        value_constructor.header_declaration.trim_start()
+    ));
+    implementation.push_str(&value_constructor.implementation);
+
+    // Ordering is only available when the wrapped value supports it
+    let mut operators = vec!["==", "!="];
+    if is_primitive(&value_type) {
+        operators.append(&mut vec!["<", "<=", ">", ">="]);
+    }
+
+    for operator in operators {
+        let method = generate_class_method(
+            &format!("operator{}", operator),
+            &n.name,
+            vec![(
+                "other".to_string(),
+                TypeReference::ConstReference(self_type.clone()),
+            )],
+            Some(TypeReference::Value(Type::Bool)),
+            &format!("\treturn value {} other.value;\n", operator),
+            true,
+            false,
+            false,
+        );
+        header_definition.push_str(&method.header_declaration);
+        implementation.push_str(&method.implementation);
+    }
+
+    header_definition.push_str("};\n");
+
+    Class {
+        header_definition: format_code(&header_definition),
+        implementation: format_code(&implementation),
+        custom_methods: vec![],
+    }
+}
+
+/// Compiles a trait into an abstract base class with a pure virtual method for each function.
+fn compile_cpp_trait(t: &Trait) -> String {
+    let class_name = compile_identifier(local_name(&t.name));
+
+    let mut code = compile_doc(&t.doc, "");
+    code.push_str(&format!("class {} \n{{\n", class_name));
+    code.push_str("public:\n");
+    code.push_str(&format!("\tvirtual ~{}() = default;\n", class_name));
+
+    let mut functions = t.functions.values().collect::<Vec<_>>();
+    functions.sort_by(|a, b| a.name.cmp(&b.name));
+    for f in functions {
+        let method = generate_method(&t.name, f, "");
+        code.push_str(&compile_doc(&f.doc, "\t"));
+        code.push_str(&format!(
+            "\tvirtual {} = 0;\n",
+            method.header_declaration.trim().trim_end_matches(';')
+        ));
+    }
+    code.push_str("};\n");
+
+    format_code(&code)
+}
+
+/// Compiles a simple enum into an enum class, using the repr as the underlying type.
+fn compile_cpp_enum(e: &Enum, repr: &Option<Type>) -> String {
+    let mut code = compile_doc(&e.doc, "");
+    code.push_str(&format!(
+        "enum class {}",
+        compile_identifier(local_name(&e.name))
+    ));
+    if let Some(repr) = repr {
+        code.push_str(&format!(" : {}", compile_cpp_type(repr)));
+    }
+    code.push_str("\n{\n");
+
+    for (variant, discriminant) in e.discriminants() {
+        code.push_str(&compile_doc(&variant.doc, "\t"));
+        code.push_str(&format!(
+            "\t{} = {},\n",
+            compile_attribute_name(&variant.attributes, &variant.name),
+            discriminant
+        ));
+    }
+    code.push_str("};\n");
+
+    code
+}
+
+/// Ensures the variants of complex enums don't clash with the members their C++ class declares.
+fn validate_enums(env: &Environment) -> Result<(), Error> {
+    let mut enums = env
+        .enums
+        .values()
+        .filter(|e| !e.is_simple())
+        .collect::<Vec<_>>();
+    enums.sort_by(|a, b| a.name.cmp(&b.name));
+
+    for e in enums {
+        let class_name = compile_identifier(local_name(&e.name));
+        for variant in e.ordered_variants() {
+            let name = compile_attribute_name(&variant.attributes, &variant.name);
+            if ["Tag", "tag", "value", class_name.as_str()].contains(&name.as_str()) {
+                return Err(Error {
+                    message: format!(
+                        "Variant '{}' of enum '{}' has the same C++ name as a member of its class",
+                        variant.name, e.name
+                    ),
+                    location: variant.location.clone(),
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the definitions a complex enum depends on, along with how much of each it needs.
+fn get_enum_dependencies(env: &Environment, e: &Enum) -> BTreeMap<String, Usage> {
+    let mut dependencies = BTreeMap::new();
+
+    // Values are stored like struct fields
+    for variant in e.variants.values() {
+        for field in variant.values.values() {
+            let usage = match map_struct_field_type(env, field) {
+                TypeReference::Value(_) => Usage::Definition,
+                _ => Usage::Declaration,
+            };
+            add_type_dependencies(&field.type_, usage, &mut dependencies);
+        }
+    }
+
+    // The class declares itself.
+    dependencies.remove(&e.name);
+
+    dependencies
+}
+
+/// Compiles an enum whose variants hold values into a class storing one struct per variant in a `std::variant`.
+/// The `Tag` of the class names the variant that is held.
+fn compile_cpp_complex_enum(env: &Environment, e: &Enum) -> Class {
+    let mut header_definition = String::new();
+    let mut implementation = String::new();
+
+    let class_name = compile_identifier(local_name(&e.name));
+    let variants = e
+        .ordered_variants()
+        .into_iter()
+        .map(|v| (v, compile_attribute_name(&v.attributes, &v.name)))
+        .collect::<Vec<_>>();
+
+    header_definition.push_str(&compile_doc(&e.doc, ""));
+    header_definition.push_str(&format!("class {} \n{{\npublic:\n", class_name));
+    header_definition.push_str("\tenum class Tag\n\t{\n");
+    for (_, name) in variants.iter() {
+        header_definition.push_str(&format!("\t\t{},\n", name));
+    }
+    header_definition.push_str("\t};\n\n");
+
+    for (variant, name) in variants.iter() {
+        let mut fields = variant.values.values().collect::<Vec<_>>();
+        fields.sort_by(|a, b| a.name.cmp(&b.name));
+
+        header_definition.push_str(&compile_doc(&variant.doc, "\t"));
+        header_definition.push_str(&format!("\tstruct {}\n\t{{\n", name));
+        for field in fields.iter() {
+            header_definition.push_str(&compile_doc(&field.doc, "\t\t"));
+            header_definition.push_str(&format!(
+                "\t\t{} {};\n",
+                compile_type(map_struct_field_type(env, field)),
+                compile_field_identifier(field)
+            ));
+        }
+        header_definition.push_str(&format!(
+            "\t\tbool operator==(const {} &other) const;\n\t}};\n",
+            name
+        ));
+
+        // Values held behind a pointer are empty unless they are set
+        let comparisons = fields
+            .iter()
+            .map(|field| {
+                let id = compile_field_identifier(field);
+                match map_struct_field_type(env, field) {
+                    TypeReference::Value(_) => format!("{id} == other.{id}"),
+                    _ => format!(
+                        "({id} == nullptr ? other.{id} == nullptr : other.{id} != nullptr && *{id} == *other.{id})"
+                    ),
+                }
+            })
+            .collect::<Vec<_>>();
+        let comparisons = match comparisons.is_empty() {
+            true => "true".to_string(),
+            false => comparisons.join(" && "),
+        };
+        implementation.push_str(&format!(
+            "bool {class_name}::{name}::operator==(const {class_name}::{name} &other) const\n{{\n\treturn {};\n}}\n",
+            comparisons
+        ));
+    }
+
+    header_definition.push_str(&format!(
+        "\n\tstd::variant<{}> value;\n\n",
+        variants
+            .iter()
+            .map(|(_, name)| name.clone())
+            .collect::<Vec<_>>()
+            .join(", ")
+    ));
+
+    header_definition.push_str(&format!("\t{}() = default;\n", class_name));
+    for (_, name) in variants.iter() {
+        header_definition.push_str(&format!("\t{}({} value);\n", class_name, name));
+        implementation.push_str(&format!(
+            "{class_name}::{class_name}({class_name}::{name} value): value(value)\n{{\n}}\n"
+        ));
+    }
+
+    header_definition.push_str("\tTag tag() const;\n");
+    implementation.push_str(&format!(
+        "{class_name}::Tag {class_name}::tag() const\n{{\n\treturn static_cast<{class_name}::Tag>(value.index());\n}}\n"
+    ));
+
+    // Structs copy their fields with copy_to
+    header_definition.push_str(&format!("\tvoid copy_to({class_name} &other) const;\n"));
+    implementation.push_str(&format!(
+        "void {class_name}::copy_to({class_name} &other) const\n{{\n\tother.value = value;\n}}\n"
+    ));
+
+    header_definition.push_str(&format!(
+        "\tbool operator==(const {class_name} &other) const;\n\tbool operator!=(const {class_name} &other) const;\n"
+    ));
+    implementation.push_str(&format!(
+        "bool {class_name}::operator==(const {class_name} &other) const\n{{\n\treturn value == other.value;\n}}\n"
+    ));
+    implementation.push_str(&format!(
+        "bool {class_name}::operator!=(const {class_name} &other) const\n{{\n\treturn !(*this == other);\n}}\n"
+    ));
+    header_definition.push_str("};\n\n");
+
+    header_definition.push_str(&compile_cpp_enum_traits(e));
+
+    Class {
+        header_definition: format_code(&header_definition),
+        implementation: format_code(&implementation),
+        custom_methods: vec![],
     }
+}
 
-    // Add function forward declarations
-    let functions = env
-        .functions
+/// Compiles a traits struct that converts an enum to and from the variant names and lists its variants.
+/// Complex enums are converted through the `Tag` naming their variants.
+fn compile_cpp_enum_traits(e: &Enum) -> String {
+    let enum_name = compile_identifier(local_name(&e.name));
+    let tag_name = match e.is_simple() {
+        true => enum_name.clone(),
+        false => format!("{}::Tag", enum_name),
+    };
+    let variants = e
+        .ordered_variants()
         .iter()
-        .map(|(_, f)| f.clone())
+        .map(|v| {
+            (
+                v.name.clone(),
+                format!(
+                    "{}::{}",
+                    tag_name,
+                    compile_attribute_name(&v.attributes, &v.name)
+                ),
+            )
+        })
         .collect::<Vec<_>>();
 
-    for f in functions.iter() {
-        let function = compile_cpp_function(f);
-        let hpp_name = format!("{}.hpp", compile_identifier(&f.name));
-        let cpp_name = format!("{}.cpp", compile_identifier(&f.name));
-
-        let mut includes = vec![];
-        for (_, ty) in f.get_related_types() {
-            includes.append(&mut get_type_includes(&ty));
-        }
-        includes.sort();
-        includes.dedup();
+    let mut code = format!("struct {}Traits\n{{\n", enum_name);
+    code.push_str(&format!(
+        "\tstatic constexpr size_t VARIANT_COUNT = {};\n\n",
+        variants.len()
+    ));
 
-        let includes = includes
+    code.push_str(&format!(
+        "\tstatic constexpr std::array<{}, VARIANT_COUNT> all_variants()\n\t{{\n",
+        tag_name
+    ));
+    code.push_str(&format!(
+        "\t\treturn {{{}}};\n\t}}\n\n",
+        variants
             .iter()
-            .map(|i| i.clone())
+            .map(|(_, value)| value.clone())
             .collect::<Vec<_>>()
-            .join("\n");
+            .join(", ")
+    ));
 
-        let hpp_code = format!(
-            "#pragma once\n{}\n\n{}",
-            includes, function.header_declaration
+    code.push_str(&format!(
+        "\tstatic std::string_view to_string({} value)\n\t{{\n\t\tswitch (value)\n\t\t{{\n",
+        tag_name
+    ));
+    for (name, value) in variants.iter() {
+        code.push_str(&format!(
+            "\t\tcase {}:\n\t\t\treturn \"{}\";\n",
+            value, name
+        ));
+    }
+    code.push_str("\t\t}\n\t\treturn \"\";\n\t}\n\n");
+
+    code.push_str(&format!(
+        "\tstatic std::optional<{}> from_string(std::string_view value)\n\t{{\n",
+        tag_name
+    ));
+    for (name, value) in variants.iter() {
+        code.push_str(&format!(
+            "\t\tif (value == \"{}\")\n\t\t{{\n\t\t\treturn {};\n\t\t}}\n",
+            name, value
+        ));
+    }
+    code.push_str("\t\treturn std::nullopt;\n\t}\n};\n\n");
+
+    code.push_str(&format!(
+        "inline std::string_view to_string({} value)\n{{\n\treturn {}Traits::to_string(value);\n}}\n",
+        tag_name, enum_name
+    ));
+    if !e.is_simple() {
+        code.push_str(&format!(
+            "inline std::string_view to_string(const {} &value)\n{{\n\treturn {}Traits::to_string(value.tag());\n}}\n",
+            enum_name, enum_name
+        ));
+    }
+
+    code
+}
+
+/// Compiles a flag set into a class that stores the flags as bits of the smallest fitting integer.
+fn compile_cpp_flags(f: &Flags) -> Class {
+    let mut header_definition = String::new();
+    let mut implementation = String::new();
+
+    let class_name = compile_identifier(local_name(&f.name));
+    let bits_type = f.underlying_type();
+    let self_type = Type::Identifier(f.name.clone());
+    let flag_type = Type::Identifier(format!("{}/Flag", f.name));
+    let all_bits = match f.values.len() {
+        64 => u64::MAX,
+        count => (1u64 << count) - 1,
+    };
+
+    header_definition.push_str(&compile_doc(&f.doc, ""));
+    header_definition.push_str(&format!("class {} \n{{\n", class_name));
+    header_definition.push_str("public:\n");
+    header_definition.push_str(&format!(
+        "\tenum Flag : {}\n\t{{\n",
+        compile_cpp_type(&bits_type)
+    ));
+    for (index, value) in f.values.iter().enumerate() {
+        header_definition.push_str(&format!(
+            "\t\t{} = 0x{:X},\n",
+            compile_identifier(value),
+            1u64 << index
+        ));
+    }
+    header_definition.push_str("\t};\n\n");
+    header_definition.push_str(&format!(
+        "\tstatic const {} all_bits = 0x{:X};\n",
+        compile_cpp_type(&bits_type),
+        all_bits
+    ));
+    header_definition.push_str(&format!("\t{} bits;\n", compile_cpp_type(&bits_type)));
+
+    let constructors = vec![
+        (vec![], "\tbits = 0;\n", ""),
+        (
+            vec![("flag".to_string(), TypeReference::Value(flag_type.clone()))],
+            "\tbits = flag;\n",
+            "",
+        ),
+        (
+            vec![("bits".to_string(), TypeReference::Value(bits_type.clone()))],
+            "\tthis->bits = bits & all_bits;\n",
+            "explicit ",
+        ),
+    ];
+    for (parameters, code, prefix) in constructors {
+        let method = generate_class_method(
+            local_name(&f.name),
+            &f.name,
+            parameters,
+            None,
+            code,
+            false,
+            false,
+            false,
         );
-        let cpp_code = format!("#include \"{}\"\n\n{}", hpp_name, function.implementation);
+        header_definition.push_str(&format!(
+            "\t{}{}",
+            prefix,
+            method.header_declaration.trim_start()
+        ));
+        implementation.push_str(&method.implementation);
+    }
 
-        // Remove old files
-        let hpp_path = output_folder.join(&hpp_name);
-        let cpp_path = output_folder.join(&cpp_name);
+    let other = || vec![("other".to_string(), TypeReference::Value(self_type.clone()))];
+    let with_bits = |expression: &str| {
+        format!(
+            "\treturn {}(static_cast<{}>({}));\n",
+            class_name,
+            compile_cpp_type(&bits_type),
+            expression
+        )
+    };
 
-        if hpp_path.exists() {
-            std::fs::remove_file(&hpp_path).unwrap();
-        }
+    // Only the flags that are set are listed or named
+    let mut values_code = "\tstd::vector<Flag> result;\n".to_string();
+    let mut to_string_code = "\tstd::string result;\n".to_string();
+    for value in f.values.iter() {
+        let id = compile_identifier(value);
+        values_code.push_str(&format!(
+            "\tif (bits & {})\n\t{{\n\t\tresult.push_back({});\n\t}}\n",
+            id, id
+        ));
+        to_string_code.push_str(&format!(
+            "\tif (bits & {})\n\t{{\n\t\tresult += result.empty() ? \"{}\" : \" | {}\";\n\t}}\n",
+            id, value, value
+        ));
+    }
+    values_code.push_str("\treturn result;\n");
+    to_string_code.push_str("\treturn result;\n");
+
+    let methods = vec![
+        generate_class_method(
+            "has",
+            &f.name,
+            other(),
+            Some(TypeReference::Value(Type::Bool)),
+            "\treturn (bits & other.bits) == other.bits;\n",
+            true,
+            false,
+            false,
+        ),
+        generate_class_method(
+            "is_empty",
+            &f.name,
+            vec![],
+            Some(TypeReference::Value(Type::Bool)),
+            "\treturn bits == 0;\n",
+            true,
+            false,
+            false,
+        ),
+        generate_class_method(
+            "values",
+            &f.name,
+            vec![],
+            Some(TypeReference::Value(Type::List(Box::new(
+                flag_type.clone(),
+            )))),
+            &values_code,
+            true,
+            false,
+            false,
+        ),
+        generate_class_method(
+            "to_string",
+            &f.name,
+            vec![],
+            Some(TypeReference::Value(Type::String)),
+            &to_string_code,
+            true,
+            false,
+            false,
+        ),
+        generate_class_method(
+            "operator|",
+            &f.name,
+            other(),
+            Some(TypeReference::Value(self_type.clone())),
+            &with_bits("bits | other.bits"),
+            true,
+            false,
+            false,
+        ),
+        generate_class_method(
+            "operator&",
+            &f.name,
+            other(),
+            Some(TypeReference::Value(self_type.clone())),
+            &with_bits("bits & other.bits"),
+            true,
+            false,
+            false,
+        ),
+        generate_class_method(
+            "operator~",
+            &f.name,
+            vec![],
+            Some(TypeReference::Value(self_type.clone())),
+            &with_bits("~bits & all_bits"),
+            true,
+            false,
+            false,
+        ),
+        generate_class_method(
+            "operator|=",
+            &f.name,
+            other(),
+            Some(TypeReference::Reference(self_type.clone())),
+            "\tbits |= other.bits;\n\treturn *this;\n",
+            false,
+            false,
+            false,
+        ),
+        generate_class_method(
+            "operator&=",
+            &f.name,
+            other(),
+            Some(TypeReference::Reference(self_type.clone())),
+            "\tbits &= other.bits;\n\treturn *this;\n",
+            false,
+            false,
+            false,
+        ),
+        generate_class_method(
+            "operator==",
+            &f.name,
+            other(),
+            Some(TypeReference::Value(Type::Bool)),
+            "\treturn bits == other.bits;\n",
+            true,
+            false,
+            false,
+        ),
+        generate_class_method(
+            "operator!=",
+            &f.name,
+            other(),
+            Some(TypeReference::Value(Type::Bool)),
+            "\treturn bits != other.bits;\n",
+            true,
+            false,
+            false,
+        ),
+    ];
+    for method in methods {
+        header_definition.push_str(&method.header_declaration);
+        implementation.push_str(&method.implementation);
+    }
 
-        if cpp_path.exists() {
-            std::fs::remove_file(&cpp_path).unwrap();
-        }
+    header_definition.push_str("};\n\n");
 
-        std::fs::write(hpp_path, hpp_code).unwrap();
-        std::fs::write(cpp_path, cpp_code).unwrap();
+    // Combining two flags would otherwise produce an integer
+    header_definition.push_str(&format!(
+        "inline {} operator|({}::Flag a, {}::Flag b)\n{{\n\treturn {}(a) | {}(b);\n}}\n",
+        class_name, class_name, class_name, class_name, class_name
+    ));
+
+    Class {
+        header_definition: format_code(&header_definition),
+        implementation: format_code(&implementation),
+        custom_methods: vec![],
     }
 }
 
-struct Class {
-    pub header_definition: String,
-    pub implementation: String,
-    pub custom_methods: Vec<CustomMethod>,
+/// Returns the C++ name of a field, which may be overridden with `(@ cpp-name "...")`.
+fn compile_field_identifier(field: &Field) -> String {
+    compile_attribute_name(&field.attributes, &field.name)
 }
-struct CustomMethod {
-    pub function_definition: String,
-    pub implementation: String,
+
+/// Returns the qualified C++ name of a free function, which may be overridden with `(@ cpp-name "...")`.
+fn compile_function_name(f: &Function) -> String {
+    let mut parts = module_path(&f.name)
+        .into_iter()
+        .map(compile_identifier)
+        .collect::<Vec<_>>();
+    parts.push(compile_attribute_name(&f.attributes, local_name(&f.name)));
+    parts.join("::")
 }
 
-struct CppFunction {
-    pub header_declaration: String,
-    pub implementation: String,
+/// Returns the name set by a `cpp-name` attribute, or the compiled name otherwise.
+fn compile_attribute_name(attributes: &[Attribute], name: &str) -> String {
+    match Attribute::find(attributes, "cpp-name").map(|a| a.string_value()) {
+        Some(Ok(name)) => name,
+        _ => compile_identifier(name),
+    }
 }
 
-struct ClassMethod {
-    pub header_declaration: String,
-    pub implementation: String,
+/// Compiles the name of a variant of the enum, using its `cpp-name` if it has one.
+fn compile_variant_name(e: &Enum, variant: &str) -> String {
+    match e.variants.get(variant) {
+        Some(v) => compile_attribute_name(&v.attributes, &v.name),
+        None => compile_identifier(variant),
+    }
 }
 
-fn compile_cpp_class(env: &Environment, s: &Struct) -> Class {
-    let mut header_definition = String::new();
-    let mut implementation = String::new();
+/// Returns whether the name can be used as a C++ identifier.
+fn is_cpp_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    let starts_well = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
 
-    header_definition.push_str(&format!("class {} \n{{\n", compile_identifier(&s.name)));
-    header_definition.push_str("public:\n");
-    for (name, field) in s.fields.iter() {
-        let ty = map_struct_field_type(field);
+    starts_well
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !CPP_KEYWORDS.split_whitespace().any(|k| k == name)
+}
 
-        header_definition.push_str(&format!(
-            "\t{} {};\n",
-            compile_type(ty),
-            compile_identifier(name)
+/// Ensures names set with `cpp-name` are C++ identifiers, and that no two members of a C++ scope share a name.
+fn validate_cpp_names(env: &Environment) -> Result<(), Error> {
+    let mut scopes: Vec<Vec<ScopeMember>> = vec![];
+    for s in env.structs.values() {
+        let fields = s
+            .fields
+            .values()
+            .map(|f| (f.name.as_str(), f.attributes.as_slice(), &f.location));
+        let functions = s
+            .functions
+            .values()
+            .map(|f| (f.name.as_str(), f.attributes.as_slice(), &f.location));
+        scopes.push(fields.chain(functions).collect());
+    }
+    for e in env.enums.values() {
+        let variants = e
+            .variants
+            .values()
+            .map(|v| (v.name.as_str(), v.attributes.as_slice(), &v.location));
+        scopes.push(variants.collect());
+    }
+    for t in env.traits.values() {
+        let functions = t
+            .functions
+            .values()
+            .map(|f| (f.name.as_str(), f.attributes.as_slice(), &f.location));
+        scopes.push(functions.collect());
+    }
+
+    // Free functions share the namespace of their module
+    let mut modules: BTreeMap<Vec<&str>, Vec<ScopeMember>> = BTreeMap::new();
+    for f in env.functions.values() {
+        modules.entry(module_path(&f.name)).or_default().push((
+            local_name(&f.name),
+            f.attributes.as_slice(),
+            &f.location,
         ));
     }
+    scopes.extend(modules.into_values());
+
+    for mut members in scopes {
+        members.sort_by_key(|(name, _, _)| *name);
+
+        let mut names: HashMap<String, &str> = HashMap::new();
+        for (name, attributes, location) in members {
+            if let Some(attribute) = Attribute::find(attributes, "cpp-name") {
+                let value = attribute.string_value()?;
+                if !is_cpp_identifier(&value) {
+                    return Err(Error {
+                        message: format!("'{}' is not a valid C++ identifier", value),
+                        location: attribute.location.clone(),
+                    });
+                }
+            }
 
-    // Generate functions
-    let functions = vec![
-        generate_constructor,
-        generate_copy_constructor,
-        generate_destructor,
-        generate_copy_to,
-        generate_clone,
-        generate_equality_operator,
-        generate_inequality_operator,
-        generate_assignment_operator,
-    ];
-    for f in functions.iter() {
-        let method = f(s);
-        header_definition.push_str(&method.header_declaration);
-        implementation.push_str(&method.implementation);
+            let compiled = compile_attribute_name(attributes, name);
+            if let Some(other) = names.insert(compiled.clone(), name) {
+                return Err(Error {
+                    message: format!(
+                        "'{}' and '{}' have the same C++ name '{}'",
+                        other, name, compiled
+                    ),
+                    location: location.clone(),
+                });
+            }
+        }
     }
 
-    let mut custom_methods = vec![];
-    for (_name, f) in s.functions.iter() {
-        let method = generate_struct_fn(s, f);
-        header_definition.push_str(&method.header_declaration);
+    Ok(())
+}
 
-        // Split off first line as we'll use that for checking if it exists
-        let function_definition = method.implementation.lines().next().unwrap().to_string();
-        custom_methods.push(CustomMethod {
-            function_definition: format_code(&function_definition),
-            implementation: format_code(&method.implementation),
-        });
-        // TODO: check if existing implementation exists. If so, use that.
-        // implementation.push_str("\n/*\n");
-        // implementation.push_str(&method.implementation);
-        // implementation.push_str("*/\n");
+/// Prints a warning for every attribute the C++ backend does not understand.
+fn warn_unknown_attributes(env: &Environment) {
+    // Whether the owner can be renamed with `cpp-name`. Types can not, as their names also name files.
+    let mut owners: Vec<(String, &[Attribute], bool)> = vec![];
+
+    for s in env.structs.values() {
+        owners.push((s.name.clone(), &s.attributes, false));
+        for field in s.fields.values() {
+            owners.push((
+                format!("{}.{}", s.name, field.name),
+                &field.attributes,
+                true,
+            ));
+        }
+        for f in s.functions.values() {
+            owners.push((format!("{}.{}", s.name, f.name), &f.attributes, true));
+        }
+    }
+    for e in env.enums.values() {
+        owners.push((e.name.clone(), &e.attributes, false));
+        for variant in e.variants.values() {
+            owners.push((
+                format!("{}.{}", e.name, variant.name),
+                &variant.attributes,
+                true,
+            ));
+        }
+    }
+    for f in env.functions.values() {
+        owners.push((f.name.clone(), &f.attributes, true));
+    }
+    for f in env.flags.values() {
+        owners.push((f.name.clone(), &f.attributes, false));
+    }
+    for t in env.traits.values() {
+        owners.push((t.name.clone(), &t.attributes, false));
+        for f in t.functions.values() {
+            owners.push((format!("{}.{}", t.name, f.name), &f.attributes, true));
+        }
+    }
+    for output in env.outputs.iter() {
+        if output.language == TargetLanguage::Cpp {
+            owners.push((
+                format!("output {}", output.folder.display()),
+                &output.attributes,
+                false,
+            ));
+        }
     }
 
-    header_definition.push_str("};\n");
+    let mut attributes = owners
+        .into_iter()
+        .flat_map(|(owner, list, renamable)| {
+            list.iter().map(move |a| (owner.clone(), a, renamable))
+        })
+        .collect::<Vec<_>>();
 
-    Class {
-        header_definition: format_code(&header_definition),
-        implementation: format_code(&implementation),
-        custom_methods,
+    attributes.sort_by(|a, b| a.0.cmp(&b.0));
+    for (owner, attribute, renamable) in attributes {
+        if !KNOWN_ATTRIBUTES.contains(&attribute.name.as_str()) {
+            eprintln!(
+                "warning: unknown attribute '{}' on '{}' is ignored by the C++ backend",
+                attribute.name, owner
+            );
+        } else if attribute.name == "cpp-name" && !renamable {
+            eprintln!(
+                "warning: attribute 'cpp-name' on '{}' is ignored, as the C++ backend can not rename it",
+                owner
+            );
+        } else if let Err(e) = attribute.string_value() {
+            eprintln!("warning: {} on '{}'", e.message, owner);
+        }
+    }
+}
+
+/// Compiles a doc into a Doxygen comment with a line for each line of the doc.
+fn compile_doc(doc: &Option<String>, indent: &str) -> String {
+    match doc {
+        Some(doc) => doc
+            .lines()
+            .map(|line| format!("{}/// {}\n", indent, line.trim()))
+            .collect(),
+        None => String::new(),
+    }
+}
+
+/// Appends the custom methods missing from the custom implementation.
+/// Fails if the custom implementation still defines a method under a name it no longer has.
+fn merge_custom_methods(custom_code: &str, methods: &[CustomMethod]) -> Result<String, Error> {
+    let mut custom_code = custom_code.to_string();
+    for method in methods.iter() {
+        if custom_code.contains(&method.function_definition) {
+            continue;
+        }
+
+        if let Some((definition, error)) = &method.outdated {
+            if custom_code.contains(definition) {
+                return Err(error.clone());
+            }
+        }
+        custom_code.push_str(&method.implementation);
     }
+    Ok(custom_code)
 }
 
 fn format_code(code: &str) -> String {
@@ -242,58 +1853,80 @@ fn format_code(code: &str) -> String {
 }
 
 fn generate_struct_fn(s: &Struct, f: &Function) -> ClassMethod {
-    let class_name = compile_identifier(&s.name);
-    let function_name = compile_identifier(&f.name);
+    generate_method(&s.name, f, "\t// TODO: Implement function\n")
+}
 
-    let code = format!("\t// TODO: Implement function\n");
-    let return_type: Option<TypeReference> = match f.return_type.1 {
-        Type::Void => None,
-        _ => Some(TypeReference::Value(f.return_type.1.clone())),
-    };
+/// Generates a method declared by the definition with the given implementation.
+fn generate_method(class: &str, f: &Function, code: &str) -> ClassMethod {
+    let class_name = compile_identifier(class);
+    let function_name = compile_attribute_name(&f.attributes, &f.name);
+    // A missing return type would declare a constructor, so void is kept explicit
+    let return_type = Some(TypeReference::Value(f.return_type.1.clone()));
 
     let parameters = f
         .parameters
         .iter()
         .map(|p| {
-            (
-                p.name.clone(),
-                if p.type_.is_identifier() {
-                    TypeReference::Reference(p.type_.clone())
-                } else {
-                    TypeReference::Value(p.type_.clone())
-                },
-            )
+            let default = if p.type_.is_identifier() {
+                TypeReference::Reference(p.type_.clone())
+            } else {
+                TypeReference::Value(p.type_.clone())
+            };
+            (p.name.clone(), map_parameter_type(p, default))
         })
         .collect::<Vec<_>>();
 
-    generate_class_method(
+    let mut method = generate_class_method(
         &function_name,
         &class_name,
         parameters,
         return_type,
-        &code,
-        false,
+        code,
+        f.is_const,
         false,
         false,
-    )
+    );
+
+    // Only the declaration is marked static
+    if f.is_static {
+        method.header_declaration = format!("\tstatic {}", method.header_declaration.trim_start());
+    }
+    method
+}
+
+/// Returns how the parameter is passed based on its mode, falling back to `default` when it has none.
+/// Primitives read with `in` are cheaper to copy than to reference, except for strings.
+fn map_parameter_type(parameter: &Parameter, default: TypeReference) -> TypeReference {
+    match parameter.mode {
+        None => default,
+        Some(ParameterMode::In)
+            if is_primitive(&parameter.type_) && parameter.type_ != Type::String =>
+        {
+            TypeReference::Value(parameter.type_.clone())
+        }
+        Some(ParameterMode::In) => TypeReference::ConstReference(parameter.type_.clone()),
+        Some(ParameterMode::Mut) | Some(ParameterMode::Out) => {
+            TypeReference::Reference(parameter.type_.clone())
+        }
+    }
 }
 
-fn generate_constructor(s: &Struct) -> ClassMethod {
+fn generate_constructor(env: &Environment, s: &Struct) -> ClassMethod {
     let mut constructor_code = s
         .fields
-        .iter()
-        .map(|(name, field)| {
-            format!(
-                "\t{} = {};",
-                compile_identifier(name),
-                init_type_value(map_struct_field_type(field))
-            )
+        .values()
+        .map(|field| {
+            let value = match &field.default {
+                Some((_, default)) => compile_value(env, default, &field.type_),
+                None => init_type_value(map_struct_field_type(env, field)),
+            };
+            format!("\t{} = {};", compile_field_identifier(field), value)
         })
         .collect::<Vec<_>>()
         .join("\n");
     constructor_code.push_str("\n");
     let constructor = generate_class_method(
-        &s.name,
+        local_name(&s.name),
         &s.name,
         vec![],
         None,
@@ -305,13 +1938,20 @@ fn generate_constructor(s: &Struct) -> ClassMethod {
     constructor
 }
 
-fn generate_destructor(s: &Struct) -> ClassMethod {
+fn generate_destructor(env: &Environment, s: &Struct) -> ClassMethod {
     let mut destructor_code = String::new();
     s.fields
         .iter()
-        .map(|(_, field)| (field.name.clone(), map_struct_field_type(field)))
-        .filter_map(|(name, ty)| match ty {
-            TypeReference::Pointer(_) => Some(format!("delete {};", compile_identifier(&name))),
+        .map(|(_, field)| {
+            (
+                compile_field_identifier(field),
+                map_struct_field_type(env, field),
+            )
+        })
+        .filter_map(|(id, ty)| match ty {
+            TypeReference::Pointer(_) | TypeReference::NullablePointer(_) => {
+                Some(format!("delete {};", id))
+            }
             _ => None,
         })
         .for_each(|code| {
@@ -319,7 +1959,7 @@ fn generate_destructor(s: &Struct) -> ClassMethod {
         });
 
     let destructor = generate_class_method(
-        &format!("~{}", s.name),
+        &format!("~{}", local_name(&s.name)),
         &s.name,
         vec![],
         None,
@@ -331,24 +1971,51 @@ fn generate_destructor(s: &Struct) -> ClassMethod {
     destructor
 }
 
-fn generate_copy_to(s: &Struct) -> ClassMethod {
+/// Generates the code that copies the given fields of this object into `target`.
+fn compile_copy_fields<'a>(
+    env: &Environment,
+    fields: impl Iterator<Item = &'a Field>,
+    target: &str,
+) -> String {
     let mut code = String::new();
-
-    // Copy fields
-    for (_, field) in s.fields.iter() {
-        let ty = map_struct_field_type(field);
+    for field in fields {
+        let ty = map_struct_field_type(env, field);
         match ty {
             TypeReference::Pointer(_) => {
                 // Call copy_to on the pointer
-                let id = compile_identifier(&field.name);
-                code.push_str(&format!("\t{}->copy_to(*other.{});\n", id, id));
+                let id = compile_field_identifier(field);
+                code.push_str(&format!("\t{}->copy_to(*{}.{});\n", id, target, id));
+            }
+            TypeReference::NullablePointer(ty) => {
+                // Mirror the presence of the value, then copy it over
+                let id = compile_field_identifier(field);
+                code.push_str(&format!("\tif ({} == nullptr)\n\t{{\n", id));
+                code.push_str(&format!("\t\tdelete {}.{};\n", target, id));
+                code.push_str(&format!("\t\t{}.{} = nullptr;\n", target, id));
+                code.push_str("\t}\n\telse\n\t{\n");
+                code.push_str(&format!("\t\tif ({}.{} == nullptr)\n\t\t{{\n", target, id));
+                code.push_str(&format!(
+                    "\t\t\t{}.{} = new {}();\n",
+                    target,
+                    id,
+                    compile_cpp_type(&ty)
+                ));
+                code.push_str("\t\t}\n");
+                code.push_str(&format!("\t\t*{}.{} = *{};\n", target, id, id));
+                code.push_str("\t}\n");
             }
             _ => {
-                let id = compile_identifier(&field.name);
-                code.push_str(&format!("\tother.{} = {};\n", id, id));
+                let id = compile_field_identifier(field);
+                code.push_str(&format!("\t{}.{} = {};\n", target, id, id));
             }
         }
     }
+    code
+}
+
+fn generate_copy_to(env: &Environment, s: &Struct) -> ClassMethod {
+    // Copy fields
+    let code = compile_copy_fields(env, s.fields.values(), "other");
 
     generate_class_method(
         "copy_to",
@@ -365,12 +2032,34 @@ fn generate_copy_to(s: &Struct) -> ClassMethod {
     )
 }
 
+/// Generates an accessor that returns a copy of the fields an embedded struct contributed, such as `as_stats()`.
+fn generate_embed_accessor(env: &Environment, s: &Struct, embedded: &Struct) -> ClassMethod {
+    let mut code = String::new();
+    let id = compile_identifier(&embedded.name);
+    code.push_str(&format!("\t{} view;\n", id));
+    code.push_str(&compile_copy_fields(env, embedded.fields.values(), "view"));
+    code.push_str("\treturn view;\n");
+
+    generate_class_method(
+        &format!("as_{}", compile_snake_case(local_name(&embedded.name))),
+        &s.name,
+        vec![],
+        Some(TypeReference::Value(Type::Identifier(
+            embedded.name.clone(),
+        ))),
+        &code,
+        true,
+        false,
+        false,
+    )
+}
+
 fn generate_copy_constructor(s: &Struct) -> ClassMethod {
     let mut code = String::new();
     code.push_str("\tother.copy_to(*this);\n");
 
     generate_class_method(
-        &s.name,
+        local_name(&s.name),
         &s.name,
         vec![(
             "other".to_string(),
@@ -403,7 +2092,7 @@ fn generate_clone(s: &Struct) -> ClassMethod {
     )
 }
 
-fn generate_equality_operator(s: &Struct) -> ClassMethod {
+fn generate_equality_operator(env: &Environment, s: &Struct) -> ClassMethod {
     generate_class_method(
         "operator==",
         &s.name,
@@ -415,20 +2104,18 @@ fn generate_equality_operator(s: &Struct) -> ClassMethod {
         &format!(
             "\treturn {};\n",
             s.fields
-                .iter()
-                .map(|(name, f)| {
+                .values()
+                .map(|f| {
                     //
-                    let mapped = map_struct_field_type(f);
-                    let prefix = if let TypeReference::Pointer(_) = mapped {
-                        "*"
-                    } else {
-                        ""
-                    };
-                    format!(
-                        "{prefix}{} == {prefix}other.{}",
-                        compile_identifier(name),
-                        compile_identifier(name)
-                    )
+                    let mapped = map_struct_field_type(env, f);
+                    let id = compile_field_identifier(f);
+                    match mapped {
+                        TypeReference::Pointer(_) => format!("*{id} == *other.{id}"),
+                        TypeReference::NullablePointer(_) => format!(
+                            "({id} == nullptr ? other.{id} == nullptr : other.{id} != nullptr && *{id} == *other.{id})"
+                        ),
+                        _ => format!("{id} == other.{id}"),
+                    }
                 })
                 .collect::<Vec<_>>()
                 .join(" && ")
@@ -462,20 +2149,142 @@ fn generate_assignment_operator(s: &Struct) -> ClassMethod {
     code.push_str("\treturn *this;\n");
 
     generate_class_method(
-        "operator=",
+        "operator=",
+        &s.name,
+        vec![(
+            "other".to_string(),
+            TypeReference::ConstReference(Type::Identifier(s.name.clone())),
+        )],
+        Some(TypeReference::Reference(Type::Identifier(s.name.clone()))),
+        &code,
+        false,
+        false,
+        false,
+    )
+}
+
+fn generate_validate(s: &Struct) -> ClassMethod {
+    let mut code = String::new();
+    code.push_str("\tstd::vector<std::string> errors;\n");
+    code.push_str("\tvalidate_into(\"\", errors);\n");
+    code.push_str("\treturn errors;\n");
+
+    generate_class_method(
+        "validate",
+        &s.name,
+        vec![],
+        Some(TypeReference::Value(Type::List(Box::new(Type::String)))),
+        &code,
+        true,
+        false,
+        false,
+    )
+}
+
+/// Generates a method that appends every constraint violation to `errors`, prefixed with the field path.
+fn generate_validate_into(env: &Environment, s: &Struct) -> ClassMethod {
+    let mut code = String::new();
+
+    let mut fields = s.fields.values().collect::<Vec<_>>();
+    fields.sort_by(|a, b| a.name.cmp(&b.name));
+
+    for field in fields {
+        let id = compile_field_identifier(field);
+        let (value, underlying) = get_underlying_value(env, id.clone(), &field.type_);
+
+        for constraint in field.constraints.iter() {
+            let (condition, message) = match &constraint.kind {
+                ConstraintKind::Range { min, max } => (
+                    format!(
+                        "{} < {} || {} > {}",
+                        value,
+                        compile_literal(min, &underlying),
+                        value,
+                        compile_literal(max, &underlying)
+                    ),
+                    format!("must be between {} and {}", min, max),
+                ),
+                ConstraintKind::MaxLen(max) => (
+                    format!("{}.size() > {}", value, max),
+                    format!("must be at most {} bytes", max),
+                ),
+                ConstraintKind::MaxCount(max) => (
+                    format!("{}.size() > {}", value, max),
+                    format!("must have at most {} items", max),
+                ),
+            };
+
+            code.push_str(&format!("\tif ({})\n\t{{\n", condition));
+            code.push_str(&format!(
+                "\t\terrors.push_back(path + \"{}: {}\");\n",
+                field.name, message
+            ));
+            code.push_str("\t}\n");
+        }
+
+        // Validate nested structs
+        let path = format!("path + \"{}.\"", field.name);
+        match map_struct_field_type(env, field) {
+            TypeReference::Pointer(Type::Identifier(name)) if env.structs.contains_key(&name) => {
+                code.push_str(&format!("\t{}->validate_into({}, errors);\n", id, path));
+            }
+            TypeReference::NullablePointer(Type::Identifier(name))
+                if env.structs.contains_key(&name) =>
+            {
+                code.push_str(&format!("\tif ({} != nullptr)\n\t{{\n", id));
+                code.push_str(&format!("\t\t{}->validate_into({}, errors);\n", id, path));
+                code.push_str("\t}\n");
+            }
+            TypeReference::Value(Type::List(inner)) => match *inner {
+                Type::Identifier(name) if env.structs.contains_key(&name) => {
+                    code.push_str(&format!(
+                        "\tfor (size_t i = 0; i < {}.size(); i++)\n\t{{\n",
+                        id
+                    ));
+                    code.push_str(&format!(
+                        "\t\t{}[i].validate_into(path + \"{}[\" + std::to_string(i) + \"].\", errors);\n",
+                        id, field.name
+                    ));
+                    code.push_str("\t}\n");
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    generate_class_method(
+        "validate_into",
         &s.name,
-        vec![(
-            "other".to_string(),
-            TypeReference::ConstReference(Type::Identifier(s.name.clone())),
-        )],
-        Some(TypeReference::Reference(Type::Identifier(s.name.clone()))),
+        vec![
+            (
+                "path".to_string(),
+                TypeReference::ConstReference(Type::String),
+            ),
+            (
+                "errors".to_string(),
+                TypeReference::Reference(Type::List(Box::new(Type::String))),
+            ),
+        ],
+        Some(TypeReference::Value(Type::Void)),
         &code,
-        false,
+        true,
         false,
         false,
     )
 }
 
+/// Returns the expression for the value stored in a field, unwrapping newtypes, along with its type.
+fn get_underlying_value(env: &Environment, value: String, ty: &Type) -> (String, Type) {
+    match ty {
+        Type::Identifier(name) => match env.newtypes.get(name) {
+            Some(n) => get_underlying_value(env, format!("{}.value", value), &n.type_.1),
+            None => (value, ty.clone()),
+        },
+        _ => (value, ty.clone()),
+    }
+}
+
 fn generate_class_method(
     name: &str,
     class: &str,
@@ -535,79 +2344,749 @@ fn generate_class_method(
     }
 }
 
-fn compile_cpp_function(f: &Function) -> CppFunction {
-    let mut header_declaration = String::new();
+fn compile_cpp_function(env: &Environment, f: &Function) -> Result<CppFunction, Error> {
+    let mut header_declaration = compile_doc(&f.doc, "");
     let mut implementation = String::new();
 
-    header_declaration.push_str(&format!(
-        "{} {}({});",
-        compile_cpp_type(&f.return_type.1),
-        compile_identifier(&f.name),
-        f.parameters
-            .iter()
-            .map(|param| format!(
+    // Free functions take their parameters by value unless a mode says otherwise
+    let parameters = f
+        .parameters
+        .iter()
+        .map(|param| {
+            format!(
                 "{} {}",
-                compile_cpp_type(&param.type_),
+                compile_type(map_parameter_type(
+                    param,
+                    TypeReference::Value(param.type_.clone())
+                )),
                 compile_identifier(&param.name)
-            ))
-            .collect::<Vec<_>>()
-            .join(", ")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    header_declaration.push_str(&format!(
+        "{} {}({});\n",
+        compile_cpp_type(&f.return_type.1),
+        compile_attribute_name(&f.attributes, local_name(&f.name)),
+        parameters
     ));
 
     implementation.push_str(&format!(
         "{} {}({})\n{{\n",
         compile_cpp_type(&f.return_type.1),
-        compile_identifier(&f.name),
-        f.parameters
-            .iter()
-            .map(|param| format!(
-                "{} {}",
-                compile_cpp_type(&param.type_),
-                compile_identifier(&param.name)
-            ))
-            .collect::<Vec<_>>()
-            .join(", ")
+        compile_function_name(f),
+        parameters
     ));
-    implementation.push_str("\t// TODO: Implement function\n");
+    let mut code = String::new();
+    if f.body.is_empty() {
+        code.push_str("\t// TODO: Implement function\n");
 
-    let return_value = get_type_default(&f.return_type.1);
-    if f.return_type.1 != Type::Void {
-        implementation.push_str(&format!("\treturn {};\n", return_value));
+        let return_value = get_type_default(&f.return_type.1);
+        if f.return_type.1 != Type::Void {
+            code.push_str(&format!("\treturn {};\n", return_value));
+        }
+    } else {
+        code.push_str(&compile_body(env, None, f)?);
     }
+    implementation.push_str(&compile_contracts(env, None, f, &code)?);
     implementation.push_str("}\n");
 
-    CppFunction {
+    Ok(CppFunction {
         header_declaration,
         implementation,
+    })
+}
+
+/// The bindings visible while compiling a function body, along with their types where they are known.
+#[derive(Clone)]
+struct BodyScope<'a> {
+    env: &'a Environment,
+    self_struct: Option<&'a Struct>,
+    /// The qualified name calls are resolved from. Struct functions use the name of their struct.
+    caller: &'a str,
+    return_type: &'a Type,
+    variables: HashMap<String, Option<Type>>,
+}
+
+/// Returns the scope of a function body, which starts with the parameters.
+fn get_body_scope<'a>(
+    env: &'a Environment,
+    self_struct: Option<&'a Struct>,
+    f: &'a Function,
+) -> BodyScope<'a> {
+    BodyScope {
+        env,
+        self_struct,
+        caller: self_struct.map_or(&f.name, |s| &s.name),
+        return_type: &f.return_type.1,
+        variables: f
+            .parameters
+            .iter()
+            .map(|p| (p.name.clone(), Some(p.type_.clone())))
+            .collect(),
+    }
+}
+
+/// Compiles the body of a function into C++ statements. The last expression is returned by non-void functions.
+fn compile_body(
+    env: &Environment,
+    self_struct: Option<&Struct>,
+    f: &Function,
+) -> Result<String, Error> {
+    let mut scope = get_body_scope(env, self_struct, f);
+
+    let mut code = String::new();
+    let returns_value = f.return_type.1 != Type::Void;
+    for (index, expression) in f.body.iter().enumerate() {
+        let tail = returns_value && index == f.body.len() - 1;
+        compile_statement(&mut scope, expression, tail, 1, &mut code)?;
+    }
+    Ok(code)
+}
+
+/// Wraps the code of a function in checks of its `requires` and `ensures` conditions.
+/// `ensures` conditions run after the code, which is moved into a lambda so every return is checked.
+/// The checks are compiled out when `WC_NO_CONTRACTS` is defined.
+fn compile_contracts(
+    env: &Environment,
+    self_struct: Option<&Struct>,
+    f: &Function,
+    code: &str,
+) -> Result<String, Error> {
+    let mut scope = get_body_scope(env, self_struct, f);
+    let mut contracts = String::new();
+    for contract in f.requires.iter() {
+        contracts.push_str(&compile_contract(
+            &scope,
+            f,
+            ContractKind::Requires,
+            contract,
+        )?);
+    }
+
+    if f.ensures.is_empty() {
+        contracts.push_str(code);
+        return Ok(contracts);
+    }
+
+    let return_type = compile_cpp_type(&f.return_type.1);
+    let code = code
+        .lines()
+        .map(|line| format!("\t{}\n", line))
+        .collect::<String>();
+    let result = match f.return_type.1 {
+        Type::Void => "",
+        _ => "auto result = ",
+    };
+    contracts.push_str(&format!(
+        "\t{}[&]() -> {} {{\n{}\t}}();\n",
+        result, return_type, code
+    ));
+
+    if f.return_type.1 != Type::Void {
+        scope
+            .variables
+            .insert("result".into(), Some(f.return_type.1.clone()));
+    }
+    for contract in f.ensures.iter() {
+        contracts.push_str(&compile_contract(
+            &scope,
+            f,
+            ContractKind::Ensures,
+            contract,
+        )?);
+    }
+
+    if f.return_type.1 != Type::Void {
+        contracts.push_str("\treturn result;\n");
+    }
+    Ok(contracts)
+}
+
+fn compile_contract(
+    scope: &BodyScope,
+    f: &Function,
+    kind: ContractKind,
+    contract: &Contract,
+) -> Result<String, Error> {
+    let message = format!(
+        "{} '{}' of function '{}' failed",
+        kind.describe(),
+        contract.source,
+        f.name
+    );
+    Ok(format!(
+        "\tWC_CONTRACT({}, {});\n",
+        compile_expression(scope, &contract.condition, Some(&Type::Bool))?,
+        compile_literal(&Literal::String(message), &Type::String)
+    ))
+}
+
+/// Returns the function the custom implementation of a function with contracts is written in.
+fn get_custom_impl_function(f: &Function) -> Function {
+    let mut custom = f.clone();
+    custom.name = format!("{}_impl", compile_attribute_name(&f.attributes, &f.name));
+    custom.attributes.retain(|a| a.name != "cpp-name");
+    custom.requires.clear();
+    custom.ensures.clear();
+    custom
+}
+
+/// Compiles a call that passes the parameters of a function on to its custom implementation.
+fn compile_custom_impl_call(f: &Function, custom: &Function) -> String {
+    let arguments = f
+        .parameters
+        .iter()
+        .map(|p| compile_identifier(&p.name))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let call = format!("{}({})", compile_identifier(&custom.name), arguments);
+    match f.return_type.1 {
+        Type::Void => format!("\t{};\n", call),
+        _ => format!("\treturn {};\n", call),
+    }
+}
+
+/// Compiles the expression as a block of statements in a nested scope.
+fn compile_block(
+    scope: &BodyScope,
+    expression: &Expression,
+    tail: bool,
+    depth: usize,
+    code: &mut String,
+) -> Result<(), Error> {
+    let mut scope = scope.clone();
+    match &expression.kind {
+        ExpressionKind::Do(body) => {
+            for (index, e) in body.iter().enumerate() {
+                compile_statement(&mut scope, e, tail && index == body.len() - 1, depth, code)?;
+            }
+            Ok(())
+        }
+        _ => compile_statement(&mut scope, expression, tail, depth, code),
+    }
+}
+
+/// Compiles the expression as a statement. Tail expressions return their value.
+fn compile_statement(
+    scope: &mut BodyScope,
+    expression: &Expression,
+    tail: bool,
+    depth: usize,
+    code: &mut String,
+) -> Result<(), Error> {
+    let indent = "\t".repeat(depth);
+    match &expression.kind {
+        ExpressionKind::Let(name, value) => {
+            let ty = get_expression_type(scope, value);
+            code.push_str(&format!(
+                "{}auto {} = {};\n",
+                indent,
+                compile_identifier(name),
+                compile_expression(scope, value, None)?
+            ));
+            scope.variables.insert(name.clone(), ty);
+        }
+        ExpressionKind::Set(target, value) => {
+            let ty = get_expression_type(scope, target);
+            code.push_str(&format!(
+                "{}{} = {};\n",
+                indent,
+                compile_expression(scope, target, None)?,
+                compile_expression(scope, value, ty.as_ref())?
+            ));
+        }
+        ExpressionKind::If(condition, then, otherwise) => {
+            code.push_str(&format!(
+                "{}if ({})\n{}{{\n",
+                indent,
+                compile_expression(scope, condition, Some(&Type::Bool))?,
+                indent
+            ));
+            compile_block(scope, then, tail, depth + 1, code)?;
+            code.push_str(&format!("{}}}\n", indent));
+            if let Some(otherwise) = otherwise {
+                code.push_str(&format!("{}else\n{}{{\n", indent, indent));
+                compile_block(scope, otherwise, tail, depth + 1, code)?;
+                code.push_str(&format!("{}}}\n", indent));
+            }
+        }
+        ExpressionKind::Cond(clauses, otherwise) => {
+            for (index, (condition, body)) in clauses.iter().enumerate() {
+                let keyword = if index == 0 { "if" } else { "else if" };
+                code.push_str(&format!(
+                    "{}{} ({})\n{}{{\n",
+                    indent,
+                    keyword,
+                    compile_expression(scope, condition, Some(&Type::Bool))?,
+                    indent
+                ));
+                compile_block(scope, body, tail, depth + 1, code)?;
+                code.push_str(&format!("{}}}\n", indent));
+            }
+            if let Some(otherwise) = otherwise {
+                code.push_str(&format!("{}else\n{}{{\n", indent, indent));
+                compile_block(scope, otherwise, tail, depth + 1, code)?;
+                code.push_str(&format!("{}}}\n", indent));
+            }
+        }
+        ExpressionKind::For(item, items, body) => {
+            let item_type = match get_expression_type(scope, items) {
+                Some(Type::List(inner)) => Some(*inner),
+                _ => None,
+            };
+            code.push_str(&format!(
+                "{}for (auto &{} : {})\n{}{{\n",
+                indent,
+                compile_identifier(item),
+                compile_expression(scope, items, None)?,
+                indent
+            ));
+            let mut inner = scope.clone();
+            inner.variables.insert(item.clone(), item_type);
+            compile_block(&inner, body, false, depth + 1, code)?;
+            code.push_str(&format!("{}}}\n", indent));
+        }
+        ExpressionKind::Do(_) => {
+            code.push_str(&format!("{}{{\n", indent));
+            compile_block(scope, expression, tail, depth + 1, code)?;
+            code.push_str(&format!("{}}}\n", indent));
+        }
+        ExpressionKind::Match(value, arms) => {
+            let e = get_match_enum(scope, value)?;
+            code.push_str(&format!(
+                "{}switch ({})\n{}{{\n",
+                indent,
+                compile_expression(scope, value, None)?,
+                indent
+            ));
+            for (index, arm) in arms.iter().enumerate() {
+                // The last arm is the default so the compiler knows every path is covered
+                let label = match &arm.pattern {
+                    Pattern::Variant(variant, _) if index < arms.len() - 1 => format!(
+                        "case {}::{}:",
+                        compile_identifier(&e.name),
+                        compile_variant_name(e, variant)
+                    ),
+                    _ => "default:".to_string(),
+                };
+                code.push_str(&format!("{}{}\n{}{{\n", indent, label, indent));
+                compile_block(scope, &arm.body, tail, depth + 1, code)?;
+                if !tail {
+                    code.push_str(&format!("{}\tbreak;\n", indent));
+                }
+                code.push_str(&format!("{}}}\n", indent));
+            }
+            code.push_str(&format!("{}}}\n", indent));
+        }
+        ExpressionKind::Return(value) => match value {
+            Some(value) => code.push_str(&format!(
+                "{}return {};\n",
+                indent,
+                compile_expression(scope, value, Some(scope.return_type))?
+            )),
+            None => code.push_str(&format!("{}return;\n", indent)),
+        },
+        _ => {
+            let expected = Some(scope.return_type).filter(|_| tail);
+            let value = compile_expression(scope, expression, expected)?;
+            match tail {
+                true => code.push_str(&format!("{}return {};\n", indent, value)),
+                false => code.push_str(&format!("{}{};\n", indent, value)),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Compiles an expression that results in a value. The expected type, if any, decides the type of literals.
+fn compile_expression(
+    scope: &BodyScope,
+    expression: &Expression,
+    expected: Option<&Type>,
+) -> Result<String, Error> {
+    let not_a_value = |keyword: &str| {
+        Err(Error {
+            message: format!("'{}' can not be used as a value", keyword),
+            location: expression.location.clone(),
+        })
+    };
+
+    let code = match &expression.kind {
+        ExpressionKind::Literal(Literal::String(value)) => format!(
+            "std::string({})",
+            compile_literal(&Literal::String(value.clone()), &Type::String)
+        ),
+        ExpressionKind::Literal(literal) => {
+            compile_literal(literal, expected.unwrap_or(&Type::I32))
+        }
+        ExpressionKind::Variable(name) if name == "self" => "(*this)".to_string(),
+        ExpressionKind::Variable(name) => compile_identifier(name),
+        ExpressionKind::Get(value, field_name) => {
+            let field = match get_expression_type(scope, value) {
+                Some(Type::Identifier(name)) => scope
+                    .env
+                    .structs
+                    .get(&name)
+                    .and_then(|s| s.fields.get(field_name)),
+                _ => None,
+            };
+
+            let object = match &value.kind {
+                ExpressionKind::Variable(name) if name == "self" => "this->".to_string(),
+                _ => format!("{}.", compile_expression(scope, value, None)?),
+            };
+            match field {
+                Some(field) => match map_struct_field_type(scope.env, field) {
+                    TypeReference::Pointer(_) => {
+                        format!("(*{}{})", object, compile_field_identifier(field))
+                    }
+                    _ => format!("{}{}", object, compile_field_identifier(field)),
+                },
+                None => format!("{}{}", object, compile_identifier(field_name)),
+            }
+        }
+        ExpressionKind::Operation(operator, operands) => {
+            let operand_type = get_operand_type(scope, operator, operands, expected);
+            let operands = operands
+                .iter()
+                .map(|e| compile_expression(scope, e, operand_type.as_ref()))
+                .collect::<Result<Vec<_>, _>>()?;
+            match (operator, operands.as_slice()) {
+                (Operator::Not, [value]) => format!("!({})", value),
+                (Operator::Subtract, [value]) => format!("-({})", value),
+                (Operator::Remainder, [a, b]) if operand_type == Some(Type::Float) => {
+                    format!("std::fmod({}, {})", a, b)
+                }
+                (operator, operands) => {
+                    let symbol = match operator {
+                        Operator::Equal => "==",
+                        Operator::And => "&&",
+                        Operator::Or => "||",
+                        operator => operator.symbol(),
+                    };
+                    format!("({})", operands.join(&format!(" {} ", symbol)))
+                }
+            }
+        }
+        ExpressionKind::If(condition, then, otherwise) => match otherwise {
+            Some(otherwise) => format!(
+                "({} ? {} : {})",
+                compile_expression(scope, condition, Some(&Type::Bool))?,
+                compile_expression(scope, then, expected)?,
+                compile_expression(scope, otherwise, expected)?
+            ),
+            None => return not_a_value("if without else"),
+        },
+        ExpressionKind::Cond(clauses, otherwise) => {
+            let mut code = match otherwise {
+                Some(otherwise) => compile_expression(scope, otherwise, expected)?,
+                None => return not_a_value("cond without else"),
+            };
+            for (condition, body) in clauses.iter().rev() {
+                code = format!(
+                    "({} ? {} : {})",
+                    compile_expression(scope, condition, Some(&Type::Bool))?,
+                    compile_expression(scope, body, expected)?,
+                    code
+                );
+            }
+            code
+        }
+        ExpressionKind::Call(name, arguments) => {
+            let f = scope.env.resolve_function(scope.caller, name);
+            let name = match f {
+                Some(f) => compile_function_name(f),
+                None => compile_identifier(name),
+            };
+            let mut compiled = vec![];
+            for (index, argument) in arguments.iter().enumerate() {
+                let expected = f.and_then(|f| f.parameters.get(index)).map(|p| &p.type_);
+                compiled.push(compile_expression(scope, argument, expected)?);
+            }
+            format!("{}({})", name, compiled.join(", "))
+        }
+        ExpressionKind::Construct {
+            type_,
+            variant,
+            fields,
+        } => compile_construct(scope, expression, type_, variant, fields)?,
+        ExpressionKind::Match(value, arms) => {
+            let e = get_match_enum(scope, value)?;
+            let (last, arms) = arms.split_last().unwrap();
+            let mut code = String::from("[&](auto match_value) { return ");
+            for arm in arms {
+                if let Pattern::Variant(variant, _) = &arm.pattern {
+                    code.push_str(&format!(
+                        "match_value == {}::{} ? {} : ",
+                        compile_identifier(&e.name),
+                        compile_variant_name(e, variant),
+                        compile_expression(scope, &arm.body, expected)?
+                    ));
+                }
+            }
+            code.push_str(&format!(
+                "{}; }}({})",
+                compile_expression(scope, &last.body, expected)?,
+                compile_expression(scope, value, None)?
+            ));
+            code
+        }
+        ExpressionKind::Let(_, _) => return not_a_value("let"),
+        ExpressionKind::Set(_, _) => return not_a_value("set"),
+        ExpressionKind::For(_, _, _) => return not_a_value("for"),
+        ExpressionKind::Do(_) => return not_a_value("do"),
+        ExpressionKind::Return(_) => return not_a_value("return"),
+    };
+
+    Ok(code)
+}
+
+/// Compiles the construction of a struct into an immediately invoked lambda, or a simple enum variant into its value.
+fn compile_construct(
+    scope: &BodyScope,
+    expression: &Expression,
+    type_: &Type,
+    variant: &Option<String>,
+    fields: &[(String, Expression)],
+) -> Result<String, Error> {
+    let name = type_.to_string();
+    if let Some(e) = scope.env.enums.get(&name) {
+        return match variant {
+            Some(variant) if e.is_simple() && fields.is_empty() => Ok(format!(
+                "{}::{}",
+                compile_identifier(&name),
+                compile_variant_name(e, variant)
+            )),
+            _ => Err(Error {
+                message: format!(
+                    "Only simple enum variants can be constructed in C++, got '{}'",
+                    name
+                ),
+                location: expression.location.clone(),
+            }),
+        };
+    }
+
+    let s = match (scope.env.structs.get(&name), variant) {
+        (Some(s), None) => s,
+        _ => {
+            return Err(Error {
+                message: format!("Expected struct or enum to construct, got '{}'", name),
+                location: expression.location.clone(),
+            })
+        }
+    };
+
+    let mut code = format!("[&]() {{ {} value; ", compile_identifier(&name));
+    for (field_name, value) in fields.iter() {
+        let field = match s.fields.get(field_name) {
+            Some(field) => field,
+            None => {
+                return Err(Error {
+                    message: format!("Unknown field '{}' of '{}'", field_name, name),
+                    location: value.location.clone(),
+                })
+            }
+        };
+
+        let id = compile_field_identifier(field);
+        let value = compile_expression(scope, value, Some(&field.type_))?;
+        match map_struct_field_type(scope.env, field) {
+            TypeReference::Pointer(_) => code.push_str(&format!("*value.{} = {}; ", id, value)),
+            TypeReference::NullablePointer(ty) => code.push_str(&format!(
+                "value.{} = new {}({}); ",
+                id,
+                compile_cpp_type(&ty),
+                value
+            )),
+            _ => code.push_str(&format!("value.{} = {}; ", id, value)),
+        }
+    }
+    code.push_str("return value; }()");
+
+    Ok(code)
+}
+
+/// Returns the simple enum a matched value holds. Other enums are not emitted in C++.
+fn get_match_enum<'a>(scope: &BodyScope<'a>, value: &Expression) -> Result<&'a Enum, Error> {
+    let e = match get_expression_type(scope, value) {
+        Some(Type::Identifier(name)) => scope.env.enums.get(&name),
+        _ => None,
+    };
+
+    match e {
+        Some(e) if e.is_simple() => Ok(e),
+        Some(e) => Err(Error {
+            message: format!("Only simple enums can be matched in C++, got '{}'", e.name),
+            location: value.location.clone(),
+        }),
+        None => Err(Error {
+            message: "Expected a parameter, binding, field or call to match in C++".into(),
+            location: value.location.clone(),
+        }),
+    }
+}
+
+/// Returns the type of the expression where it can be told from the bindings and definitions.
+fn get_expression_type(scope: &BodyScope, expression: &Expression) -> Option<Type> {
+    match &expression.kind {
+        ExpressionKind::Variable(name) if name == "self" => {
+            scope.self_struct.map(|s| Type::Identifier(s.name.clone()))
+        }
+        ExpressionKind::Variable(name) => match scope.variables.get(name) {
+            Some(ty) => ty.clone(),
+            None => scope
+                .env
+                .resolve_constant(scope.caller, name)
+                .map(|c| c.type_.1.clone()),
+        },
+        ExpressionKind::Literal(Literal::Bool(_)) => Some(Type::Bool),
+        ExpressionKind::Literal(Literal::Float(_)) => Some(Type::Float),
+        ExpressionKind::Literal(Literal::String(_)) => Some(Type::String),
+        ExpressionKind::Operation(operator, _) if operator.is_comparison() => Some(Type::Bool),
+        ExpressionKind::Operation(operator, operands) => {
+            get_operand_type(scope, operator, operands, None)
+        }
+        ExpressionKind::Get(value, field) => match get_expression_type(scope, value)? {
+            Type::Identifier(name) => scope
+                .env
+                .structs
+                .get(&name)?
+                .fields
+                .get(field)
+                .map(|f| f.type_.clone()),
+            _ => None,
+        },
+        ExpressionKind::Call(name, _) => scope
+            .env
+            .resolve_function(scope.caller, name)
+            .map(|f| f.return_type.1.clone()),
+        ExpressionKind::Construct { type_, .. } => Some(type_.clone()),
+        ExpressionKind::If(_, then, _) => get_expression_type(scope, then),
+        _ => None,
+    }
+}
+
+/// Returns the type the operands of an operation share. Like the type checker, it is decided by the first
+/// operand that is not a literal, falling back to the type the operation is expected to result in.
+fn get_operand_type(
+    scope: &BodyScope,
+    operator: &Operator,
+    operands: &[Expression],
+    expected: Option<&Type>,
+) -> Option<Type> {
+    if operator.is_logical() {
+        return Some(Type::Bool);
+    }
+
+    match operands
+        .iter()
+        .find(|o| !matches!(o.kind, ExpressionKind::Literal(_)))
+        .and_then(|o| get_expression_type(scope, o))
+    {
+        Some(ty) => Some(ty),
+        None => expected.filter(|_| !operator.is_comparison()).cloned(),
     }
 }
 
+/// Adds the definitions used by the function bodies to the dependencies of their implementation.
+/// Calls are resolved from the caller. Returns whether the bodies use any constants.
+fn add_body_dependencies<'a>(
+    env: &Environment,
+    caller: &str,
+    bodies: impl Iterator<Item = &'a Expression>,
+    dependencies: &mut BTreeMap<String, Usage>,
+) -> bool {
+    let mut uses_constants = false;
+    let mut pending = bodies.collect::<Vec<_>>();
+    while let Some(expression) = pending.pop() {
+        match &expression.kind {
+            ExpressionKind::Construct { type_, .. } => {
+                add_type_dependencies(type_, Usage::Declaration, dependencies);
+            }
+            ExpressionKind::Call(name, _) => {
+                if let Some(f) = env.resolve_function(caller, name) {
+                    dependencies
+                        .entry(f.name.clone())
+                        .or_insert(Usage::Declaration);
+                    add_type_dependencies(&f.return_type.1, Usage::Declaration, dependencies);
+                }
+            }
+            ExpressionKind::Variable(name) => {
+                uses_constants |= env.resolve_constant(caller, name).is_some();
+            }
+            _ => {}
+        }
+        pending.extend(expression.children());
+    }
+    uses_constants
+}
+
+/// Returns whether the bodies apply the operator anywhere.
+fn uses_operator<'a>(bodies: impl Iterator<Item = &'a Expression>, operator: Operator) -> bool {
+    let mut pending = bodies.collect::<Vec<_>>();
+    while let Some(expression) = pending.pop() {
+        if matches!(&expression.kind, ExpressionKind::Operation(o, _) if *o == operator) {
+            return true;
+        }
+        pending.extend(expression.children());
+    }
+    false
+}
+
 fn compile_type(value: TypeReference) -> String {
     match value {
         TypeReference::Reference(t) => format!("{}&", compile_cpp_type(&t)),
         TypeReference::ConstReference(t) => format!("const {}&", compile_cpp_type(&t)),
         TypeReference::Pointer(t) => format!("{}*", compile_cpp_type(&t)),
+        TypeReference::NullablePointer(t) => format!("{}*", compile_cpp_type(&t)),
         TypeReference::Value(t) => compile_cpp_type(&t),
     }
 }
 
-fn map_struct_field_type(field: &Field) -> TypeReference {
-    if field.type_.is_identifier() {
-        TypeReference::Pointer(field.type_.clone())
-    } else {
-        TypeReference::Value(field.type_.clone())
+fn map_struct_field_type(env: &Environment, field: &Field) -> TypeReference {
+    match &field.type_ {
+        Type::Optional(inner) => TypeReference::NullablePointer(*inner.clone()),
+        Type::Identifier(name) if is_value_type(env, name) => {
+            TypeReference::Value(field.type_.clone())
+        }
+        Type::Identifier(_) => TypeReference::Pointer(field.type_.clone()),
+        _ => TypeReference::Value(field.type_.clone()),
     }
 }
 
+/// Returns whether the named type is small enough to be stored by value instead of behind a pointer.
+fn is_value_type(env: &Environment, name: &str) -> bool {
+    env.newtypes.contains_key(name)
+        || env.flags.contains_key(name)
+        || env.enums.get(name).is_some_and(|e| e.is_simple())
+}
+
 fn init_type_value(ty: TypeReference) -> String {
     match ty {
         TypeReference::Reference(_) => "TODO".to_string(),
         TypeReference::ConstReference(_) => "TODO".to_string(),
         TypeReference::Pointer(t) => format!("new {}()", compile_cpp_type(&t).replace("*", "")),
+        TypeReference::NullablePointer(_) => "nullptr".to_string(),
         TypeReference::Value(t) => get_type_default(&t),
     }
 }
 
+/// Returns whether the type maps to a builtin C++ type.
+fn is_primitive(ty: &Type) -> bool {
+    !matches!(
+        ty,
+        Type::Identifier(_)
+            | Type::List(_)
+            | Type::Optional(_)
+            | Type::Generic(_, _)
+            | Type::Parameter(_)
+    )
+}
+
 fn get_type_includes(ty: &Type) -> Vec<String> {
     let mut includes = vec![];
     match ty {
@@ -615,8 +3094,11 @@ fn get_type_includes(ty: &Type) -> Vec<String> {
             includes.push(format!("#include <vector>"));
             includes.append(&mut get_type_includes(t));
         }
-        Type::Identifier(i) => {
-            includes.push(format!("#include \"{}.hpp\"", compile_identifier(i)));
+        Type::Optional(t) => {
+            includes.append(&mut get_type_includes(t));
+        }
+        Type::Identifier(_) | Type::Generic(_, _) | Type::Parameter(_) => {
+            // Identifiers are resolved through the dependency graph
         }
         Type::I8
         | Type::I16
@@ -653,6 +3135,10 @@ fn get_type_default(ty: &Type) -> String {
         Type::Void => "()".to_string(),
         Type::Identifier(i) => format!("{}()", compile_identifier(i)),
         Type::List(ty) => format!("std::vector<{}>()", compile_cpp_type(ty).replace("*", "")),
+        Type::Optional(_) => "nullptr".to_string(),
+        Type::Generic(_, _) | Type::Parameter(_) => {
+            unreachable!("Generic types are monomorphized before compiling")
+        }
     }
 }
 
@@ -672,5 +3158,292 @@ fn compile_cpp_type(ty: &Type) -> String {
         Type::Void => "void".to_string(),
         Type::Identifier(i) => format!("{}", compile_identifier(i)),
         Type::List(t) => format!("std::vector<{}>", compile_cpp_type(t).replace("*", "")),
+        Type::Optional(t) => format!("{}*", compile_cpp_type(t)),
+        Type::Generic(_, _) | Type::Parameter(_) => {
+            unreachable!("Generic types are monomorphized before compiling")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lisper::Location;
+
+    fn parse(input: &str) -> Environment {
+        Environment::parse(lisper::parse_str(input).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn validate_enums_simple_enum_returns_ok() {
+        let env = parse("(enum Direction (@ repr u8) (North 0) (East 1))");
+        assert_eq!(validate_enums(&env), Ok(()));
+    }
+
+    #[test]
+    fn validate_enums_complex_enum_returns_ok() {
+        let env = parse("(enum Shape (Circle f32) Square)");
+        assert_eq!(validate_enums(&env), Ok(()));
+    }
+
+    #[test]
+    fn validate_enums_variant_named_like_member_returns_err() {
+        let env = parse("(enum Shape (Circle f32) (Square (@ cpp-name \"Tag\")))");
+        let expected = Err(Error {
+            message:
+                "Variant 'Square' of enum 'Shape' has the same C++ name as a member of its class"
+                    .to_string(),
+            location: Location::default(),
+        });
+
+        assert_eq!(validate_enums(&env), expected);
+    }
+
+    #[test]
+    fn compile_cpp_complex_enum_declares_variant_structs() {
+        let env = parse(
+            "(struct Point (fields (i32 x)))
+            (enum Shape (Circle (f32 radius) (Point center)) (Line f32) Empty)",
+        );
+        let class = compile_cpp_complex_enum(&env, &env.enums["Shape"]);
+
+        let header = &class.header_definition;
+        assert!(header.contains("\tenum class Tag\n\t{\n\t\tCircle,\n\t\tLine,\n\t\tEmpty,\n\t};"));
+        assert!(header.contains("\tstruct Circle\n\t{\n\t\tPoint *center;\n\t\tfloat radius;"));
+        assert!(header.contains("\tstruct Line\n\t{\n\t\tfloat value;"));
+        assert!(header.contains("std::variant<Circle, Line, Empty> value;"));
+        assert!(header.contains("\tShape(Circle value);"));
+        assert!(header.contains("\tTag tag() const;"));
+
+        let implementation = &class.implementation;
+        assert!(implementation.contains("return static_cast<Shape::Tag>(value.index());"));
+        assert!(implementation.contains(
+            "return (center == nullptr ? other.center == nullptr : other.center != nullptr && *center == *other.center) && radius == other.radius;"
+        ));
+        assert!(implementation.contains(
+            "bool Shape::Empty::operator==(const Shape::Empty &other) const\n{\n\treturn true;"
+        ));
+    }
+
+    #[test]
+    fn compile_cpp_complex_enum_emits_traits_over_tag() {
+        let env = parse("(enum Shape (Circle f32) Square)");
+        let class = compile_cpp_complex_enum(&env, &env.enums["Shape"]);
+
+        let header = &class.header_definition;
+        assert!(
+            header.contains("struct ShapeTraits\n{\n\tstatic constexpr size_t VARIANT_COUNT = 2;")
+        );
+        assert!(header.contains("std::array<Shape::Tag, VARIANT_COUNT> all_variants()"));
+        assert!(header.contains("return {Shape::Tag::Circle, Shape::Tag::Square};"));
+        assert!(
+            header.contains("static std::optional<Shape::Tag> from_string(std::string_view value)")
+        );
+        assert!(header.contains("inline std::string_view to_string(Shape::Tag value)"));
+        assert!(header.contains(
+            "inline std::string_view to_string(const Shape &value)\n{\n\treturn ShapeTraits::to_string(value.tag());"
+        ));
+    }
+
+    #[test]
+    fn compile_cpp_enum_traits_simple_enum() {
+        let env = parse("(enum Direction North (East (@ cpp-name \"Right\")))");
+        let traits = compile_cpp_enum_traits(&env.enums["Direction"]);
+
+        assert!(traits.contains("return {Direction::North, Direction::Right};"));
+        assert!(traits.contains("\t\tcase Direction::Right:\n\t\t\treturn \"East\";"));
+        assert!(
+            traits.contains("\t\tif (value == \"East\")\n\t\t{\n\t\t\treturn Direction::Right;")
+        );
+    }
+
+    #[test]
+    fn compile_body_literal_uses_return_type() {
+        let env = parse("(fn big () i64 9223372036854775807)");
+        let code = compile_body(&env, None, &env.functions["big"]).unwrap();
+        assert!(code.contains("return 9223372036854775807LL;"));
+    }
+
+    #[test]
+    fn compile_body_literal_uses_operand_type() {
+        let env = parse("(fn next ((i64 level)) i64 (+ level 1))");
+        let code = compile_body(&env, None, &env.functions["next"]).unwrap();
+        assert!(code.contains("return (level + 1LL);"));
+    }
+
+    #[test]
+    fn compile_body_call_uses_cpp_name_and_parameter_types() {
+        let env = parse(
+            r#"
+            (module game
+                (fn scale (@ cpp-name "Scale") ((i64 factor)) i64 factor)
+                (fn even? () bool (= (scale 2) 4)))
+            "#,
+        );
+        let f = &env.functions["game/even?"];
+        let code = compile_body(&env, None, f).unwrap();
+        assert!(code.contains("return (game::Scale(2LL) == 4LL);"));
+
+        let f = compile_cpp_function(&env, &env.functions["game/scale"]).unwrap();
+        assert!(f
+            .header_declaration
+            .contains("int64_t Scale(int64_t factor);"));
+        assert!(f
+            .implementation
+            .contains("int64_t game::Scale(int64_t factor)"));
+    }
+
+    #[test]
+    fn compile_body_float_remainder_uses_fmod() {
+        let env = parse(
+            "(fn wrap ((f32 angle) (f32 turn)) f32 (% angle turn))
+            (fn wrap-turn ((f32 angle)) f32 (% angle 360.0))
+            (fn odd? ((i32 n)) bool (= (% n 2) 1))",
+        );
+        let code = compile_body(&env, None, &env.functions["wrap"]).unwrap();
+        assert!(code.contains("return std::fmod(angle, turn);"));
+
+        let code = compile_body(&env, None, &env.functions["wrap-turn"]).unwrap();
+        assert!(code.contains("return std::fmod(angle, 360.0f);"));
+
+        let code = compile_body(&env, None, &env.functions["odd?"]).unwrap();
+        assert!(code.contains("return ((n % 2) == 1);"));
+
+        let bodies = env.functions["wrap"].body.iter();
+        assert!(uses_operator(bodies, Operator::Remainder));
+    }
+
+    #[test]
+    fn compile_body_renamed_variant_uses_cpp_name() {
+        let env = parse(
+            r#"
+            (enum Direction North (East (@ cpp-name "Right")))
+            (fn turn ((in Direction d)) Direction
+                (match d (East (Direction East)) (_ (Direction North))))
+            (fn east? ((in Direction d)) bool
+                (let east (match d (East true) (_ false)))
+                east)
+            "#,
+        );
+        let code = compile_body(&env, None, &env.functions["turn"]).unwrap();
+        assert!(code.contains("case Direction::Right:"));
+        assert!(code.contains("return Direction::Right;"));
+
+        let code = compile_body(&env, None, &env.functions["east?"]).unwrap();
+        assert!(code.contains("match_value == Direction::Right ? true : false"));
+    }
+
+    #[test]
+    fn merge_custom_methods_adds_missing_impl() {
+        let env = parse(
+            "(struct Counter (fields (i32 count))
+                (fn next (self const) () i32 (requires (>= (get self count) 0))))",
+        );
+        let class = compile_cpp_class(&env, &env.structs["Counter"]).unwrap();
+
+        let code = merge_custom_methods("", &class.custom_methods).unwrap();
+        assert!(code.contains("int32_t Counter::next_impl() const"));
+    }
+
+    #[test]
+    fn merge_custom_methods_outdated_definition_returns_err() {
+        let env = parse(
+            "(struct Counter (fields (i32 count))
+                (fn next (self const) () i32 (requires (>= (get self count) 0))))",
+        );
+        let class = compile_cpp_class(&env, &env.structs["Counter"]).unwrap();
+        let custom_code = "int32_t Counter::next() const\n{\n\treturn count + 1;\n}\n";
+
+        let expected = Err(Error {
+            message:
+                "Custom implementation of 'Counter::next' must be renamed to 'next_impl' now that it has contracts"
+                    .to_string(),
+            location: Location::default(),
+        });
+        assert_eq!(
+            merge_custom_methods(custom_code, &class.custom_methods),
+            expected
+        );
+    }
+
+    #[test]
+    fn generate_validate_into_max_len_counts_bytes() {
+        let env = parse("(struct Hero (fields (string name (max-len 16))))");
+        let method = generate_validate_into(&env, &env.structs["Hero"]);
+        assert!(method.implementation.contains("if (name.size() > 16)"));
+        assert!(method
+            .implementation
+            .contains("errors.push_back(path + \"name: must be at most 16 bytes\");"));
+    }
+
+    #[test]
+    fn validate_cpp_names_invalid_identifier_returns_err() {
+        let env = parse("(struct Hero (fields (i32 hp (@ cpp-name \"hit points\"))))");
+        let expected = Err(Error {
+            message: "'hit points' is not a valid C++ identifier".to_string(),
+            location: Location::default(),
+        });
+        assert_eq!(validate_cpp_names(&env), expected);
+
+        let env = parse("(fn level-up (@ cpp-name \"delete\") () void)");
+        let expected = Err(Error {
+            message: "'delete' is not a valid C++ identifier".to_string(),
+            location: Location::default(),
+        });
+        assert_eq!(validate_cpp_names(&env), expected);
+    }
+
+    #[test]
+    fn validate_cpp_names_shared_name_returns_err() {
+        let env = parse(
+            "(struct Hero (fields (i32 hp) (i32 health (@ cpp-name \"hp\")))
+                (fn heal (@ cpp-name \"Heal\") () void))",
+        );
+        let expected = Err(Error {
+            message: "'health' and 'hp' have the same C++ name 'hp'".to_string(),
+            location: Location::default(),
+        });
+        assert_eq!(validate_cpp_names(&env), expected);
+    }
+
+    #[test]
+    fn validate_cpp_names_renamed_members_returns_ok() {
+        let env = parse(
+            "(struct Hero (fields (i32 hp (@ cpp-name \"HitPoints\")))
+                (fn heal (@ cpp-name \"Heal\") () void))
+            (module game (fn heal () void))
+            (fn heal () void)",
+        );
+        assert_eq!(validate_cpp_names(&env), Ok(()));
+    }
+
+    #[test]
+    fn compile_cpp_flags_declares_bit_operations() {
+        let env = parse("(flags Element Fire Water)");
+        let class = compile_cpp_flags(&env.flags["Element"]);
+
+        let header = &class.header_definition;
+        assert!(
+            header.contains("\tenum Flag : uint8_t\n\t{\n\t\tFire = 0x1,\n\t\tWater = 0x2,\n\t};")
+        );
+        assert!(header.contains("static const uint8_t all_bits = 0x3;"));
+        assert!(header.contains("bool has(Element other) const;"));
+        assert!(header.contains("std::string to_string() const;"));
+        assert!(header.contains("Element operator|(Element other) const;"));
+        assert!(header.contains("Element operator~() const;"));
+        assert!(header.contains("Element &operator|=(Element other);"));
+        assert!(header.contains("inline Element operator|(Element::Flag a, Element::Flag b)"));
+    }
+
+    #[test]
+    fn compile_cpp_flags_implements_has_and_to_string() {
+        let env = parse("(flags Element Fire Water)");
+        let class = compile_cpp_flags(&env.flags["Element"]);
+
+        let implementation = &class.implementation;
+        assert!(implementation.contains("return (bits  &other.bits) == other.bits;"));
+        assert!(implementation.contains("result += result.empty() ? \"Fire\" : \" | Fire\";"));
+        assert!(implementation.contains("result += result.empty() ? \"Water\" : \" | Water\";"));
+        assert!(implementation.contains("return Element(static_cast<uint8_t>(~bits  &all_bits));"));
     }
 }
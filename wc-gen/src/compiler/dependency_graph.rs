@@ -0,0 +1,124 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A directed graph of named nodes, where an edge means a node depends on another.
+#[derive(Debug, Clone, Default)]
+pub struct DependencyGraph {
+    edges: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl DependencyGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a node without any dependencies.
+    pub fn add_node(&mut self, node: &str) {
+        self.edges.entry(node.to_string()).or_default();
+    }
+
+    /// Adds an edge where `node` depends on `dependency`.
+    pub fn add_dependency(&mut self, node: &str, dependency: &str) {
+        self.add_node(dependency);
+        self.edges
+            .entry(node.to_string())
+            .or_default()
+            .insert(dependency.to_string());
+    }
+
+    /// Returns the nodes ordered so each node comes after its dependencies.
+    /// If there is a cycle, the path of the cycle is returned instead.
+    pub fn sort(&self) -> Result<Vec<String>, Vec<String>> {
+        let mut sorted = vec![];
+        let mut visited = BTreeSet::new();
+        let mut path = vec![];
+
+        for node in self.edges.keys() {
+            self.visit(node, &mut path, &mut visited, &mut sorted)?;
+        }
+
+        Ok(sorted)
+    }
+
+    fn visit(
+        &self,
+        node: &String,
+        path: &mut Vec<String>,
+        visited: &mut BTreeSet<String>,
+        sorted: &mut Vec<String>,
+    ) -> Result<(), Vec<String>> {
+        if visited.contains(node) {
+            return Ok(());
+        }
+
+        if let Some(start) = path.iter().position(|n| n == node) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(node.clone());
+            return Err(cycle);
+        }
+
+        path.push(node.clone());
+        if let Some(dependencies) = self.edges.get(node) {
+            for dependency in dependencies.iter() {
+                self.visit(dependency, path, visited, sorted)?;
+            }
+        }
+        path.pop();
+
+        visited.insert(node.clone());
+        sorted.push(node.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sort_empty_returns_empty() {
+        let graph = DependencyGraph::new();
+        assert_eq!(graph.sort(), Ok(vec![]));
+    }
+
+    #[test]
+    fn sort_places_dependencies_first() {
+        let mut graph = DependencyGraph::new();
+        graph.add_dependency("a", "c");
+        graph.add_dependency("c", "b");
+        graph.add_node("d");
+
+        let expected = vec![
+            "b".to_string(),
+            "c".to_string(),
+            "a".to_string(),
+            "d".to_string(),
+        ];
+        assert_eq!(graph.sort(), Ok(expected));
+    }
+
+    #[test]
+    fn sort_shared_dependency_returns_once() {
+        let mut graph = DependencyGraph::new();
+        graph.add_dependency("a", "c");
+        graph.add_dependency("b", "c");
+
+        let expected = vec!["c".to_string(), "a".to_string(), "b".to_string()];
+        assert_eq!(graph.sort(), Ok(expected));
+    }
+
+    #[test]
+    fn sort_cycle_returns_path() {
+        let mut graph = DependencyGraph::new();
+        graph.add_dependency("a", "b");
+        graph.add_dependency("b", "c");
+        graph.add_dependency("c", "a");
+
+        let expected = vec![
+            "a".to_string(),
+            "b".to_string(),
+            "c".to_string(),
+            "a".to_string(),
+        ];
+        assert_eq!(graph.sort(), Err(expected));
+    }
+}
@@ -1,18 +1,50 @@
 mod cpp;
+mod dependency_graph;
 
 use crate::{definition::output::TargetLanguage, env::Environment};
+use lisper::Error;
 
-pub fn compile(env: Environment) {
+pub fn compile(env: Environment) -> Result<(), Error> {
     for output in env.outputs.iter() {
         match output.language {
             TargetLanguage::Cpp => {
                 std::fs::create_dir_all(output.folder.clone()).unwrap();
-                cpp::compile(output.folder.clone(), &env);
+                cpp::compile(output.folder.clone(), &env)?;
             }
         }
     }
+
+    Ok(())
 }
 
 fn compile_identifier(i: &str) -> String {
-    i.replace("-", "_").replace("?", "")
+    i.replace("-", "_").replace("?", "").replace("/", "::")
+}
+
+/// Converts a type name such as `SpAttack` into snake case, such as `sp_attack`.
+fn compile_snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in compile_identifier(name).chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 && !snake.ends_with('_') {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+/// Returns the name of a definition without the modules it is declared in.
+fn local_name(name: &str) -> &str {
+    name.rsplit('/').next().unwrap_or(name)
+}
+
+/// Returns the modules a definition is declared in, outermost first.
+fn module_path(name: &str) -> Vec<&str> {
+    let mut modules = name.split('/').collect::<Vec<_>>();
+    modules.pop();
+    modules
 }
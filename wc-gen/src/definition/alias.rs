@@ -0,0 +1,140 @@
+use super::{type_::Type, FromLisp};
+use lisper::{Error, Location};
+
+/// A transparent alias for another type. Uses of the alias are replaced with the aliased type.
+#[derive(Debug, Clone)]
+pub struct Alias {
+    pub location: Location,
+    pub name: String,
+    pub type_: (Location, Type),
+}
+impl PartialEq for Alias {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.type_.1 == other.type_.1
+    }
+}
+
+impl FromLisp for Alias {
+    fn identifier() -> &'static str {
+        "alias"
+    }
+
+    fn get_related_types(&self) -> Vec<(Location, Type)> {
+        vec![self.type_.clone()]
+    }
+
+    fn parse_values(list: &mut lisper::List) -> Result<Self, Error> {
+        let (name, location) = list.pop_identifier("alias name")?;
+
+        let (ty, loc) = list.pop_identifier("aliased type")?;
+        let ty = Type::try_parse(&ty, loc.clone())?;
+        match ty {
+            Type::Void => {
+                return Err(Error {
+                    message: "void can not be aliased".into(),
+                    location: loc,
+                })
+            }
+            Type::Optional(_) => {
+                return Err(Error {
+                    message: "Optionals can not be aliased".into(),
+                    location: loc,
+                })
+            }
+            _ => {}
+        }
+
+        if !list.is_empty() {
+            let n = list.pop_front("Unexpected values")?;
+            return Err(Error {
+                message: "Expected end of list".into(),
+                location: n.first_location(),
+            });
+        }
+
+        Ok(Alias {
+            location,
+            name,
+            type_: (loc, ty),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> lisper::List {
+        lisper::parse_str(input).unwrap()[0].clone()
+    }
+
+    #[test]
+    fn can_try_not_identifier_returns_false() {
+        let input = parse("(newtype EntityId u32)");
+        assert!(!Alias::can_try(&input));
+    }
+
+    #[test]
+    fn can_try_identifier_returns_true() {
+        let input = parse("(alias EntityId u32)");
+        assert!(Alias::can_try(&input));
+    }
+
+    #[test]
+    fn from_lisp_returns_alias() {
+        let input = parse("(alias EntityId u32)");
+        let expected = Alias {
+            location: Location::default(),
+            name: "EntityId".to_string(),
+            type_: (Location::default(), Type::U32),
+        };
+
+        assert_eq!(Alias::from_lisp(input), Ok(expected));
+    }
+
+    #[test]
+    fn from_lisp_no_type_returns_err() {
+        let input = parse("(alias EntityId)");
+        let expected = Err("Expected aliased type".into());
+
+        assert_eq!(Alias::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn from_lisp_void_returns_err() {
+        let input = parse("(alias Nothing void)");
+        let expected = Err("void can not be aliased".into());
+
+        assert_eq!(Alias::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn from_lisp_optional_returns_err() {
+        let input = parse("(alias MaybeId u32?)");
+        let expected = Err("Optionals can not be aliased".into());
+
+        assert_eq!(Alias::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn from_lisp_extra_values_returns_err() {
+        let input = parse("(alias EntityId u32 u64)");
+        let expected = Err("Expected end of list".into());
+
+        assert_eq!(Alias::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn get_related_types_returns_aliased_type() {
+        let input = parse("(alias Path Point[])");
+        let value = Alias::from_lisp(input).unwrap();
+        let result = value
+            .get_related_types()
+            .iter()
+            .map(|(_, ty)| ty.clone())
+            .collect::<Vec<_>>();
+
+        let expected = vec![Type::List(Box::new(Type::Identifier("Point".into())))];
+        assert_eq!(result, expected);
+    }
+}
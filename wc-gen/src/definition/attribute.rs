@@ -0,0 +1,155 @@
+use super::{literal::Literal, type_::Type, FromLisp};
+use lisper::{Error, List, Location};
+
+/// A value passed to an attribute.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValue {
+    Identifier(String),
+    Literal(Literal),
+}
+
+impl std::fmt::Display for AttributeValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AttributeValue::Identifier(value) => write!(f, "{}", value),
+            AttributeValue::Literal(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// An annotation attached to a definition, written as `(@ name values...)`.
+/// Attributes are interpreted by the backends that understand them.
+#[derive(Debug, Clone)]
+pub struct Attribute {
+    pub location: Location,
+    pub name: String,
+    pub values: Vec<AttributeValue>,
+}
+impl PartialEq for Attribute {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.values == other.values
+    }
+}
+
+impl Attribute {
+    /// Returns the attribute with the given name.
+    pub fn find<'a>(attributes: &'a [Attribute], name: &str) -> Option<&'a Attribute> {
+        attributes.iter().find(|a| a.name == name)
+    }
+
+    /// Returns the single string value of the attribute.
+    pub fn string_value(&self) -> Result<String, Error> {
+        match self.values.as_slice() {
+            [AttributeValue::Literal(Literal::String(value))] => Ok(value.clone()),
+            [AttributeValue::Identifier(value)] => Ok(value.clone()),
+            _ => Err(Error {
+                message: format!("Attribute '{}' expects a single string value", self.name),
+                location: self.location.clone(),
+            }),
+        }
+    }
+}
+
+impl FromLisp for Attribute {
+    fn identifier() -> &'static str {
+        "@"
+    }
+
+    fn get_related_types(&self) -> Vec<(Location, Type)> {
+        vec![]
+    }
+
+    fn parse_values(list: &mut List) -> Result<Self, Error> {
+        let (name, location) = list.pop_identifier("attribute name")?;
+
+        let mut values = vec![];
+        while !list.is_empty() {
+            if list.front_is_identifier() {
+                let (value, _) = list.pop_identifier("attribute value")?;
+                values.push(AttributeValue::Identifier(value));
+            } else {
+                let (value, _) = Literal::pop(list, "attribute value")?;
+                values.push(AttributeValue::Literal(value));
+            }
+        }
+
+        Ok(Attribute {
+            location,
+            name,
+            values,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> List {
+        lisper::parse_str(input).unwrap()[0].clone()
+    }
+
+    #[test]
+    fn can_try_identifier_returns_true() {
+        let input = parse("(@ cpp-name)");
+        assert!(Attribute::can_try(&input));
+    }
+
+    #[test]
+    fn from_lisp_without_values_returns_attribute() {
+        let input = parse("(@ cpp-name)");
+        let expected = Attribute {
+            location: Location::default(),
+            name: "cpp-name".into(),
+            values: vec![],
+        };
+
+        assert_eq!(Attribute::from_lisp(input), Ok(expected));
+    }
+
+    #[test]
+    fn from_lisp_with_values_returns_attribute() {
+        let input = parse("(@ cpp-name \"Hp\" u8 4)");
+        let expected = Attribute {
+            location: Location::default(),
+            name: "cpp-name".into(),
+            values: vec![
+                AttributeValue::Literal(Literal::String("Hp".into())),
+                AttributeValue::Identifier("u8".into()),
+                AttributeValue::Literal(Literal::Int(4)),
+            ],
+        };
+
+        assert_eq!(Attribute::from_lisp(input), Ok(expected));
+    }
+
+    #[test]
+    fn from_lisp_no_name_returns_err() {
+        let input = parse("(@)");
+        let expected = Err("Expected attribute name".into());
+
+        assert_eq!(Attribute::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn from_lisp_list_value_returns_err() {
+        let input = parse("(@ cpp-name (Hp))");
+        let expected = Err("Expected attribute value".into());
+
+        assert_eq!(Attribute::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn string_value_returns_value() {
+        let attribute = Attribute::from_lisp(parse("(@ cpp-name \"Hp\")")).unwrap();
+        assert_eq!(attribute.string_value(), Ok("Hp".to_string()));
+    }
+
+    #[test]
+    fn string_value_without_value_returns_err() {
+        let attribute = Attribute::from_lisp(parse("(@ cpp-name)")).unwrap();
+        let expected = Err("Attribute 'cpp-name' expects a single string value".into());
+
+        assert_eq!(attribute.string_value(), expected);
+    }
+}
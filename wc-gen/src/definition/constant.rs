@@ -0,0 +1,206 @@
+use super::{
+    expression::{Expression, ExpressionKind},
+    literal::Literal,
+    type_::Type,
+    FromLisp,
+};
+use lisper::{Error, Location};
+
+/// A named value that is shared between the definitions and the generated code.
+#[derive(Debug, Clone)]
+pub struct Constant {
+    pub location: Location,
+    pub name: String,
+    pub type_: (Location, Type),
+    /// The value. Constants written as an expression hold 0 until the expression is evaluated.
+    pub value: (Location, Literal),
+    /// The expression the value is computed from, if it is not a literal. It is cleared once evaluated.
+    pub expression: Option<Expression>,
+}
+impl PartialEq for Constant {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.type_.1 == other.type_.1
+            && self.value.1 == other.value.1
+            && self.expression == other.expression
+    }
+}
+
+impl FromLisp for Constant {
+    fn identifier() -> &'static str {
+        "const"
+    }
+
+    fn get_related_types(&self) -> Vec<(Location, Type)> {
+        vec![self.type_.clone()]
+    }
+
+    fn parse_values(list: &mut lisper::List) -> Result<Self, Error> {
+        let (name, location) = list.pop_identifier("constant name")?;
+
+        let (ty, loc) = list.pop_identifier("constant type")?;
+        let ty = Type::try_parse(&ty, loc.clone())?;
+        match ty {
+            Type::List(_) | Type::Optional(_) | Type::Void => {
+                return Err(Error {
+                    message: format!("Constants can not be of type '{}'", ty),
+                    location: loc,
+                })
+            }
+            _ => {}
+        }
+
+        if list.is_empty() {
+            Literal::pop(list, "constant value")?;
+        }
+
+        // Anything other than a literal is an expression, evaluated once every definition is known
+        let expression = Expression::pop(list)?;
+        let (value, expression) = match expression.kind {
+            ExpressionKind::Literal(literal) => {
+                // Identifiers are checked once the types they refer to are known
+                if !ty.is_identifier() {
+                    literal.type_check(&ty, expression.location.clone())?;
+                }
+                ((expression.location, literal), None)
+            }
+            _ => (
+                (expression.location.clone(), Literal::Int(0)),
+                Some(expression),
+            ),
+        };
+
+        if !list.is_empty() {
+            let n = list.pop_front("Unexpected values")?;
+            return Err(Error {
+                message: "Expected end of list".into(),
+                location: n.first_location(),
+            });
+        }
+
+        Ok(Constant {
+            location,
+            name,
+            type_: (loc, ty),
+            value,
+            expression,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> lisper::List {
+        lisper::parse_str(input).unwrap()[0].clone()
+    }
+
+    #[test]
+    fn can_try_not_identifier_returns_false() {
+        let input = parse("(constant MAX-LEVEL i32 100)");
+        assert!(!Constant::can_try(&input));
+    }
+
+    #[test]
+    fn can_try_identifier_returns_true() {
+        let input = parse("(const MAX-LEVEL i32 100)");
+        assert!(Constant::can_try(&input));
+    }
+
+    #[test]
+    fn from_lisp_returns_constant() {
+        let input = parse("(const MAX-LEVEL i32 100)");
+        let expected = Constant {
+            location: Location::default(),
+            name: "MAX-LEVEL".to_string(),
+            type_: (Location::default(), Type::I32),
+            value: (Location::default(), Literal::Int(100)),
+            expression: None,
+        };
+
+        assert_eq!(Constant::from_lisp(input), Ok(expected));
+    }
+
+    #[test]
+    fn from_lisp_string_returns_constant() {
+        let input = parse("(const DEFAULT-NAME string \"Unnamed\")");
+        let expected = Constant {
+            location: Location::default(),
+            name: "DEFAULT-NAME".to_string(),
+            type_: (Location::default(), Type::String),
+            value: (Location::default(), Literal::String("Unnamed".into())),
+            expression: None,
+        };
+
+        assert_eq!(Constant::from_lisp(input), Ok(expected));
+    }
+
+    #[test]
+    fn from_lisp_expression_returns_constant() {
+        let input = parse("(const MAX-EXP i32 (* MAX-LEVEL 1000))");
+        let expression = Expression::pop(&mut parse("((* MAX-LEVEL 1000))")).unwrap();
+        let expected = Constant {
+            location: Location::default(),
+            name: "MAX-EXP".to_string(),
+            type_: (Location::default(), Type::I32),
+            value: (Location::default(), Literal::Int(0)),
+            expression: Some(expression),
+        };
+
+        assert_eq!(Constant::from_lisp(input), Ok(expected));
+    }
+
+    #[test]
+    fn from_lisp_no_value_returns_err() {
+        let input = parse("(const MAX-LEVEL i32)");
+        let expected = Err("Expected constant value".into());
+
+        assert_eq!(Constant::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn from_lisp_wrong_type_returns_err() {
+        let input = parse("(const MAX-LEVEL bool 100)");
+        let expected = Err("Expected bool value, got '100'".into());
+
+        assert_eq!(Constant::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn from_lisp_out_of_range_returns_err() {
+        let input = parse("(const MAX-LEVEL u8 1000)");
+        let expected = Err("Value 1000 does not fit in u8".into());
+
+        assert_eq!(Constant::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn from_lisp_list_returns_err() {
+        let input = parse("(const LEVELS i32[] 100)");
+        let expected = Err("Constants can not be of type 'i32[]'".into());
+
+        assert_eq!(Constant::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn from_lisp_extra_values_returns_err() {
+        let input = parse("(const MAX-LEVEL i32 100 200)");
+        let expected = Err("Expected end of list".into());
+
+        assert_eq!(Constant::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn get_related_types_returns_type() {
+        let input = parse("(const STARTING-GOLD Gold 100)");
+        let value = Constant::from_lisp(input).unwrap();
+        let result = value
+            .get_related_types()
+            .iter()
+            .map(|(_, ty)| ty.clone())
+            .collect::<Vec<_>>();
+
+        assert_eq!(result, vec![Type::Identifier("Gold".into())]);
+    }
+}
@@ -0,0 +1,247 @@
+use super::{literal::Literal, type_::Type};
+use lisper::{Error, List, Location};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstraintKind {
+    /// The value must be within the inclusive range.
+    Range { min: Literal, max: Literal },
+    /// The string must not be longer than the limit, counted in bytes of UTF-8.
+    MaxLen(u64),
+    /// The list must not have more items than the limit.
+    MaxCount(u64),
+}
+
+/// A constraint on the values a field may hold.
+#[derive(Debug, Clone)]
+pub struct Constraint {
+    pub location: Location,
+    pub kind: ConstraintKind,
+}
+impl PartialEq for Constraint {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+    }
+}
+
+impl std::fmt::Display for Constraint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            ConstraintKind::Range { min, max } => write!(f, "(range {} {})", min, max),
+            ConstraintKind::MaxLen(max) => write!(f, "(max-len {})", max),
+            ConstraintKind::MaxCount(max) => write!(f, "(max-count {})", max),
+        }
+    }
+}
+
+impl Constraint {
+    /// Returns whether the list is a constraint.
+    pub fn can_try(list: &List) -> bool {
+        if let Some(i) = list.peek_front() {
+            if let Ok(id) = i.as_identifier() {
+                return id == "range" || id == "max-len" || id == "max-count";
+            }
+        }
+        false
+    }
+
+    /// Parses a constraint such as `(range 1 100)`.
+    pub fn parse(mut list: List) -> Result<Self, Error> {
+        let (id, location) = list.pop_identifier("constraint")?;
+
+        let kind = match id.as_str() {
+            "range" => {
+                let (min, _) = Literal::pop(&mut list, "minimum value")?;
+                let (max, _) = Literal::pop(&mut list, "maximum value")?;
+                ConstraintKind::Range { min, max }
+            }
+            "max-len" => ConstraintKind::MaxLen(Self::pop_limit(&mut list)?),
+            "max-count" => ConstraintKind::MaxCount(Self::pop_limit(&mut list)?),
+            _ => {
+                return Err(Error {
+                    message: format!("Unknown constraint '{}'", id),
+                    location,
+                })
+            }
+        };
+
+        if !list.is_empty() {
+            let n = list.pop_front("Unexpected values")?;
+            return Err(Error {
+                message: "Expected end of list".into(),
+                location: n.first_location(),
+            });
+        }
+
+        Ok(Constraint { location, kind })
+    }
+
+    fn pop_limit(list: &mut List) -> Result<u64, Error> {
+        let (limit, location) = Literal::pop(list, "limit")?;
+        match limit {
+            Literal::Int(limit) if limit >= 0 && limit <= u64::MAX as i128 => Ok(limit as u64),
+            _ => Err(Error {
+                message: format!("Expected a positive integer limit, got '{}'", limit),
+                location,
+            }),
+        }
+    }
+
+    /// Ensures the constraint can be applied to values of the given type.
+    pub fn validate(&self, ty: &Type) -> Result<(), Error> {
+        match &self.kind {
+            ConstraintKind::Range { min, max } => {
+                if Literal::int_range(ty).is_none() && *ty != Type::Float {
+                    return Err(Error {
+                        message: format!("Range constraint requires a numeric type, got '{}'", ty),
+                        location: self.location.clone(),
+                    });
+                }
+
+                min.type_check(ty, self.location.clone())?;
+                max.type_check(ty, self.location.clone())?;
+
+                let is_empty = match (min, max) {
+                    (Literal::Int(min), Literal::Int(max)) => min > max,
+                    (Literal::Int(min), Literal::Float(max)) => *min as f64 > *max,
+                    (Literal::Float(min), Literal::Int(max)) => *min > *max as f64,
+                    (Literal::Float(min), Literal::Float(max)) => min > max,
+                    _ => false,
+                };
+                if is_empty {
+                    return Err(Error {
+                        message: format!("Range minimum {} is greater than maximum {}", min, max),
+                        location: self.location.clone(),
+                    });
+                }
+            }
+            ConstraintKind::MaxLen(_) => {
+                if *ty != Type::String {
+                    return Err(Error {
+                        message: format!("max-len constraint requires a string, got '{}'", ty),
+                        location: self.location.clone(),
+                    });
+                }
+            }
+            ConstraintKind::MaxCount(_) => {
+                if !ty.is_list() {
+                    return Err(Error {
+                        message: format!("max-count constraint requires a list, got '{}'", ty),
+                        location: self.location.clone(),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> Result<Constraint, Error> {
+        Constraint::parse(lisper::parse_str(input).unwrap()[0].clone())
+    }
+
+    #[test]
+    fn can_try_returns_true() {
+        let list = lisper::parse_str("(max-len 16)").unwrap()[0].clone();
+        assert!(Constraint::can_try(&list));
+    }
+
+    #[test]
+    fn can_try_returns_false() {
+        let list = lisper::parse_str("(min-len 16)").unwrap()[0].clone();
+        assert!(!Constraint::can_try(&list));
+    }
+
+    #[test]
+    fn parse_range() {
+        let expected = ConstraintKind::Range {
+            min: Literal::Int(1),
+            max: Literal::Int(100),
+        };
+        assert_eq!(parse("(range 1 100)").map(|c| c.kind), Ok(expected));
+    }
+
+    #[test]
+    fn parse_max_len() {
+        let expected = ConstraintKind::MaxLen(16);
+        assert_eq!(parse("(max-len 16)").map(|c| c.kind), Ok(expected));
+    }
+
+    #[test]
+    fn parse_max_count() {
+        let expected = ConstraintKind::MaxCount(4);
+        assert_eq!(parse("(max-count 4)").map(|c| c.kind), Ok(expected));
+    }
+
+    #[test]
+    fn parse_negative_limit_returns_err() {
+        let expected = Err("Expected a positive integer limit, got '-4'".into());
+        assert_eq!(parse("(max-count -4)"), expected);
+    }
+
+    #[test]
+    fn parse_missing_max_returns_err() {
+        let expected = Err("Expected maximum value".into());
+        assert_eq!(parse("(range 1)"), expected);
+    }
+
+    #[test]
+    fn parse_extra_values_returns_err() {
+        let expected = Err("Expected end of list".into());
+        assert_eq!(parse("(max-len 1 2)"), expected);
+    }
+
+    #[test]
+    fn validate_range_on_int_returns_ok() {
+        let constraint = parse("(range 1 100)").unwrap();
+        assert_eq!(constraint.validate(&Type::I32), Ok(()));
+    }
+
+    #[test]
+    fn validate_range_on_float_returns_ok() {
+        let constraint = parse("(range 0 1.5)").unwrap();
+        assert_eq!(constraint.validate(&Type::Float), Ok(()));
+    }
+
+    #[test]
+    fn validate_range_on_string_returns_err() {
+        let constraint = parse("(range 1 100)").unwrap();
+        let expected = Err("Range constraint requires a numeric type, got 'string'".into());
+        assert_eq!(constraint.validate(&Type::String), expected);
+    }
+
+    #[test]
+    fn validate_range_out_of_type_returns_err() {
+        let constraint = parse("(range 1 300)").unwrap();
+        let expected = Err("Value 300 does not fit in u8".into());
+        assert_eq!(constraint.validate(&Type::U8), expected);
+    }
+
+    #[test]
+    fn validate_range_min_greater_than_max_returns_err() {
+        let constraint = parse("(range 100 1)").unwrap();
+        let expected = Err("Range minimum 100 is greater than maximum 1".into());
+        assert_eq!(constraint.validate(&Type::I32), expected);
+    }
+
+    #[test]
+    fn validate_max_len_on_list_returns_err() {
+        let constraint = parse("(max-len 16)").unwrap();
+        let expected = Err("max-len constraint requires a string, got 'i32[]'".into());
+        assert_eq!(
+            constraint.validate(&Type::List(Box::new(Type::I32))),
+            expected
+        );
+    }
+
+    #[test]
+    fn validate_max_count_on_string_returns_err() {
+        let constraint = parse("(max-count 4)").unwrap();
+        let expected = Err("max-count constraint requires a list, got 'string'".into());
+        assert_eq!(constraint.validate(&Type::String), expected);
+    }
+}
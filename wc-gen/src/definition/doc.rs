@@ -0,0 +1,108 @@
+use super::{literal::Literal, type_::Type, FromLisp};
+use lisper::{Error, List, Location};
+
+/// Documentation attached to a definition, written as `(doc "...")`.
+#[derive(Debug, Clone)]
+pub struct Doc {
+    pub location: Location,
+    pub text: String,
+}
+impl PartialEq for Doc {
+    fn eq(&self, other: &Self) -> bool {
+        self.text == other.text
+    }
+}
+
+impl Doc {
+    /// Pops the doc from the front of the list if there is one.
+    pub fn pop(list: &mut List) -> Result<Option<String>, Error> {
+        Ok(Doc::try_pop(list)?.map(|doc| doc.text))
+    }
+}
+
+impl FromLisp for Doc {
+    fn identifier() -> &'static str {
+        "doc"
+    }
+
+    fn get_related_types(&self) -> Vec<(Location, Type)> {
+        vec![]
+    }
+
+    fn parse_values(list: &mut List) -> Result<Self, Error> {
+        let (text, location) = Literal::pop(list, "doc string")?;
+        let text = match text {
+            Literal::String(text) => text.replace("\\n", "\n"),
+            text => {
+                return Err(Error {
+                    message: format!("Expected doc string, got '{}'", text),
+                    location,
+                })
+            }
+        };
+
+        if !list.is_empty() {
+            let n = list.pop_front("Unexpected values")?;
+            return Err(Error {
+                message: "Expected end of list".into(),
+                location: n.first_location(),
+            });
+        }
+
+        Ok(Doc { location, text })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> List {
+        lisper::parse_str(input).unwrap()[0].clone()
+    }
+
+    #[test]
+    fn from_lisp_returns_text() {
+        let input = parse("(doc \"The unit's health\")");
+        let expected = Doc {
+            location: Location::default(),
+            text: "The unit's health".into(),
+        };
+
+        assert_eq!(Doc::from_lisp(input), Ok(expected));
+    }
+
+    #[test]
+    fn from_lisp_number_returns_err() {
+        let input = parse("(doc 42)");
+        let expected = Err("Expected doc string, got '42'".into());
+
+        assert_eq!(Doc::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn from_lisp_extra_values_returns_err() {
+        let input = parse("(doc \"a\" \"b\")");
+        let expected = Err("Expected end of list".into());
+
+        assert_eq!(Doc::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn pop_returns_doc_and_removes_it() {
+        let mut input = parse("(struct (doc \"A unit\") (fields))");
+        input.pop_identifier("struct").unwrap();
+
+        assert_eq!(Doc::pop(&mut input), Ok(Some("A unit".to_string())));
+        assert_eq!(input.to_string(), parse("((fields))").to_string());
+    }
+
+    #[test]
+    fn pop_other_list_returns_none() {
+        let mut input = parse("(struct (fields))");
+        input.pop_identifier("struct").unwrap();
+
+        assert_eq!(Doc::pop(&mut input), Ok(None));
+        assert_eq!(input.to_string(), parse("((fields))").to_string());
+    }
+}
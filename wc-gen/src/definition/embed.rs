@@ -0,0 +1,93 @@
+use super::{type_::Type, FromLisp};
+use lisper::{Error, List, Location};
+
+/// A struct whose fields are flattened into the struct embedding it, written as `(embed Stats)`.
+#[derive(Debug, Clone)]
+pub struct Embed {
+    pub location: Location,
+    pub type_: Type,
+}
+impl PartialEq for Embed {
+    fn eq(&self, other: &Self) -> bool {
+        self.type_ == other.type_
+    }
+}
+
+impl FromLisp for Embed {
+    fn identifier() -> &'static str {
+        "embed"
+    }
+
+    fn get_related_types(&self) -> Vec<(Location, Type)> {
+        vec![(self.location.clone(), self.type_.clone())]
+    }
+
+    fn parse_values(list: &mut List) -> Result<Self, Error> {
+        let (name, location) = list.pop_identifier("struct to embed")?;
+        let type_ = match Type::try_parse(&name, location.clone())? {
+            Type::Identifier(name) => Type::Identifier(name),
+            _ => {
+                return Err(Error {
+                    message: format!("Expected struct to embed, got '{}'", name),
+                    location,
+                })
+            }
+        };
+
+        if !list.is_empty() {
+            let n = list.pop_front("Unexpected values")?;
+            return Err(Error {
+                message: "Expected end of list".into(),
+                location: n.first_location(),
+            });
+        }
+
+        Ok(Embed { location, type_ })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> List {
+        lisper::parse_str(input).unwrap()[0].clone()
+    }
+
+    #[test]
+    fn from_lisp_returns_type() {
+        let input = parse("(embed Stats)");
+        let expected = Embed {
+            location: Location::default(),
+            type_: Type::Identifier("Stats".into()),
+        };
+
+        assert_eq!(Embed::from_lisp(input), Ok(expected));
+    }
+
+    #[test]
+    fn from_lisp_builtin_type_returns_err() {
+        let input = parse("(embed i32)");
+        let expected = Err("Expected struct to embed, got 'i32'".into());
+
+        assert_eq!(Embed::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn from_lisp_extra_values_returns_err() {
+        let input = parse("(embed Stats Buffs)");
+        let expected = Err("Expected end of list".into());
+
+        assert_eq!(Embed::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn pop_other_list_returns_none() {
+        let mut input = parse("(struct Character (fields (i32 health)))");
+        input.pop_identifier("struct").unwrap();
+        input.pop_identifier("name").unwrap();
+
+        assert_eq!(Embed::try_pop(&mut input), Ok(None));
+        assert!(!input.is_empty());
+    }
+}
@@ -1,6 +1,12 @@
-use lisper::Location;
-
-use super::{type_::Type, FromLisp};
+use lisper::{Error, Location};
+
+use super::{
+    attribute::{Attribute, AttributeValue},
+    literal::Literal,
+    parse_annotations, parse_definition_name,
+    type_::Type,
+    FromLisp,
+};
 use crate::definition::field::Field;
 use std::collections::HashMap;
 
@@ -8,23 +14,189 @@ use std::collections::HashMap;
 pub struct Enum {
     pub location: lisper::Location,
     pub name: String,
+    /// The type parameters of a generic enum. Empty if the enum is not generic.
+    pub type_parameters: Vec<String>,
     pub variants: HashMap<String, Variant>,
+    pub doc: Option<String>,
+    pub attributes: Vec<Attribute>,
 }
 impl PartialEq for Enum {
     fn eq(&self, other: &Self) -> bool {
-        self.name == other.name && self.variants == other.variants
+        self.name == other.name
+            && self.type_parameters == other.type_parameters
+            && self.variants == other.variants
+            && self.doc == other.doc
+            && self.attributes == other.attributes
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Variant {
     pub location: lisper::Location,
+    /// The position the variant was declared at.
+    pub index: usize,
     pub name: String,
     pub values: HashMap<String, Field>,
+    /// The explicit value of a simple variant, written as `(North 0)`.
+    pub discriminant: Option<(lisper::Location, i128)>,
+    pub doc: Option<String>,
+    pub attributes: Vec<Attribute>,
 }
 impl PartialEq for Variant {
     fn eq(&self, other: &Self) -> bool {
-        self.name == other.name && self.values == other.values
+        self.name == other.name
+            && self.values == other.values
+            && self.discriminant.as_ref().map(|(_, d)| d)
+                == other.discriminant.as_ref().map(|(_, d)| d)
+            && self.doc == other.doc
+            && self.attributes == other.attributes
+    }
+}
+
+impl Enum {
+    pub fn is_generic(&self) -> bool {
+        !self.type_parameters.is_empty()
+    }
+
+    /// Returns whether no variant holds values.
+    pub fn is_simple(&self) -> bool {
+        self.variants.values().all(|v| v.values.is_empty())
+    }
+
+    /// Returns the variants in the order they were declared.
+    pub fn ordered_variants(&self) -> Vec<&Variant> {
+        let mut variants = self.variants.values().collect::<Vec<_>>();
+        variants.sort_by_key(|v| v.index);
+        variants
+    }
+
+    /// Returns the value of each variant in declaration order.
+    /// Variants without an explicit discriminant are numbered by their position.
+    pub fn discriminants(&self) -> Vec<(&Variant, i128)> {
+        self.ordered_variants()
+            .into_iter()
+            .map(|v| match &v.discriminant {
+                Some((_, value)) => (v, *value),
+                None => (v, v.index as i128),
+            })
+            .collect()
+    }
+
+    /// Returns the integer type set with `(@ repr u8)`, if any.
+    pub fn repr(&self) -> Result<Option<Type>, Error> {
+        let attribute = match Attribute::find(&self.attributes, "repr") {
+            Some(attribute) => attribute,
+            None => return Ok(None),
+        };
+
+        let ty = match attribute.values.as_slice() {
+            [AttributeValue::Identifier(ty)] => Type::try_parse(ty, attribute.location.clone())?,
+            _ => {
+                return Err(Error {
+                    message: "Attribute 'repr' expects a single type".into(),
+                    location: attribute.location.clone(),
+                })
+            }
+        };
+
+        if Literal::int_range(&ty).is_none() {
+            return Err(Error {
+                message: format!(
+                    "Enum '{}' repr must be an integer type, got '{}'",
+                    self.name, ty
+                ),
+                location: attribute.location.clone(),
+            });
+        }
+
+        Ok(Some(ty))
+    }
+
+    /// Ensures discriminants are only used by simple enums, are given for every variant or none of them,
+    /// are unique and fit in the repr.
+    fn validate_discriminants(&self) -> Result<(), Error> {
+        let repr = self.repr()?;
+        let variants = self.ordered_variants();
+        let has_discriminants = variants.iter().any(|v| v.discriminant.is_some());
+        if repr.is_none() && !has_discriminants {
+            return Ok(());
+        }
+
+        if let Some(complex) = variants.iter().find(|v| !v.values.is_empty()) {
+            return Err(Error {
+                message: format!(
+                    "Enum '{}' has a complex variant '{}', so it can not have a repr or discriminants",
+                    self.name, complex.name
+                ),
+                location: complex.location.clone(),
+            });
+        }
+
+        let mut seen = HashMap::new();
+        for variant in variants.iter() {
+            let (location, value) = match &variant.discriminant {
+                Some(discriminant) => discriminant,
+                None if has_discriminants => {
+                    return Err(Error {
+                        message: format!(
+                            "Variant '{}' needs a discriminant, as other variants of '{}' have one",
+                            variant.name, self.name
+                        ),
+                        location: variant.location.clone(),
+                    })
+                }
+                None => continue,
+            };
+
+            if let Some(other) = seen.insert(*value, &variant.name) {
+                return Err(Error {
+                    message: format!(
+                        "Duplicate discriminant {} for variants '{}' and '{}'",
+                        value, other, variant.name
+                    ),
+                    location: location.clone(),
+                });
+            }
+
+            if let Some(repr) = &repr {
+                Literal::Int(*value).type_check(repr, location.clone())?;
+            }
+        }
+
+        // Implicit discriminants are the variant positions
+        if let (Some(repr), false) = (&repr, has_discriminants) {
+            if let Some(last) = variants.last() {
+                Literal::Int(last.index as i128).type_check(repr, last.location.clone())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the types of the variant fields with the result of `f`.
+    pub fn map_types(&mut self, f: &dyn Fn(&Type) -> Type) {
+        for variant in self.variants.values_mut() {
+            for field in variant.values.values_mut() {
+                field.type_ = f(&field.type_);
+            }
+        }
+    }
+
+    /// Returns the types that are stored directly in the enum's variants.
+    /// Lists and optionals are excluded as they are allocated on demand.
+    pub fn get_direct_referenced_types(&self) -> Vec<(Location, Type)> {
+        let mut types = vec![];
+        for variant in self.variants.values() {
+            for field in variant.values.values() {
+                match &field.type_ {
+                    Type::Identifier(_) | Type::Generic(_, _) | Type::Parameter(_) => {
+                        types.push((field.location.clone(), field.type_.clone()));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        types
     }
 }
 
@@ -35,10 +207,13 @@ impl FromLisp for Enum {
 
     fn parse_values(list: &mut lisper::List) -> Result<Self, lisper::Error> {
         // Get the name of the enum
-        let (name, location) = list.pop_identifier("enum name")?;
+        let (name, location, type_parameters) = parse_definition_name(list, "enum name")?;
+        let (doc, attributes) = parse_annotations(list)?;
 
         let mut variants = HashMap::new();
         while !list.is_empty() {
+            let index = variants.len();
+
             // Determine if it's a simple or complex variant
             let is_complex = list.front_is_list();
 
@@ -46,16 +221,42 @@ impl FromLisp for Enum {
                 // Pop list
                 let mut list = list.pop_list("complex variant")?;
                 let (variant_name, variant_location) = list.pop_identifier("variant name")?;
+                let (doc, attributes) = parse_annotations(&mut list)?;
 
                 let mut complex_variants = HashMap::new();
-                if list.front_is_identifier() {
+                let mut discriminant = None;
+                if Literal::can_pop(&list) {
+                    // Parse the discriminant of a simple variant
+                    let (value, loc) = Literal::pop(&mut list, "discriminant")?;
+                    match value {
+                        Literal::Int(value) => discriminant = Some((loc, value)),
+                        value => {
+                            return Err(lisper::Error {
+                                message: format!("Expected discriminant, got '{}'", value),
+                                location: loc,
+                            })
+                        }
+                    }
+
+                    if !list.is_empty() {
+                        return Err(lisper::Error {
+                            message: "A variant with a discriminant can not have values".into(),
+                            location: list.location(),
+                        });
+                    }
+                } else if list.front_is_identifier() {
                     // Parse the single value
                     let (field_type, loc) = list.pop_identifier("complex variant type")?;
                     let ty = Type::try_parse(&field_type, loc.clone())?;
                     let field = Field {
                         name: "value".to_string(),
                         type_: ty,
+                        default: None,
+                        default_expression: None,
+                        constraints: vec![],
                         location: loc,
+                        doc: None,
+                        attributes: vec![],
                     };
                     complex_variants.insert("value".to_string(), field);
 
@@ -78,15 +279,23 @@ impl FromLisp for Enum {
                 }
                 Variant {
                     location: variant_location,
+                    index,
                     name: variant_name.clone(),
                     values: complex_variants,
+                    discriminant,
+                    doc,
+                    attributes,
                 }
             } else {
                 let (variant_name, variant_location) = list.pop_identifier("variant name")?;
                 Variant {
                     location: variant_location,
+                    index,
                     name: variant_name.clone(),
                     values: HashMap::new(),
+                    discriminant: None,
+                    doc: None,
+                    attributes: vec![],
                 }
             };
 
@@ -103,11 +312,17 @@ impl FromLisp for Enum {
             }
         }
 
-        Ok(Enum {
+        let enum_ = Enum {
             location,
             name,
+            type_parameters,
             variants,
-        })
+            doc,
+            attributes,
+        };
+        enum_.validate_discriminants()?;
+
+        Ok(enum_)
     }
 
     fn get_related_types(&self) -> Vec<(Location, Type)> {
@@ -163,7 +378,10 @@ mod tests {
         let expected = Enum {
             location: Location::default(),
             name: "Shape".to_string(),
+            type_parameters: vec![],
             variants: HashMap::new(),
+            doc: None,
+            attributes: vec![],
         };
         let result = Enum::from_lisp(list);
         assert_eq!(result, Ok(expected));
@@ -185,34 +403,49 @@ mod tests {
         let expected = Enum {
             location: Location::default(),
             name: "Shape".to_string(),
+            type_parameters: vec![],
             variants: vec![
                 (
                     "Circle".to_string(),
                     Variant {
                         location: Location::default(),
+                        index: 0,
                         name: "Circle".to_string(),
                         values: HashMap::new(),
+                        discriminant: None,
+                        doc: None,
+                        attributes: vec![],
                     },
                 ),
                 (
                     "Square".to_string(),
                     Variant {
                         location: Location::default(),
+                        index: 1,
                         name: "Square".to_string(),
                         values: HashMap::new(),
+                        discriminant: None,
+                        doc: None,
+                        attributes: vec![],
                     },
                 ),
                 (
                     "Triangle".to_string(),
                     Variant {
                         location: Location::default(),
+                        index: 2,
                         name: "Triangle".to_string(),
                         values: HashMap::new(),
+                        discriminant: None,
+                        doc: None,
+                        attributes: vec![],
                     },
                 ),
             ]
             .into_iter()
             .collect(),
+            doc: None,
+            attributes: vec![],
         };
         let result = Enum::from_lisp(list);
         assert_eq!(result, Ok(expected));
@@ -224,10 +457,12 @@ mod tests {
         let expected = Enum {
             location: Location::default(),
             name: "Literal".to_string(),
+            type_parameters: vec![],
             variants: vec![(
                 "String".to_string(),
                 Variant {
                     location: Location::default(),
+                    index: 0,
                     name: "String".to_string(),
                     values: vec![(
                         "value".to_string(),
@@ -235,14 +470,24 @@ mod tests {
                             location: Location::default(),
                             name: "value".to_string(),
                             type_: Type::String,
+                            default: None,
+                            default_expression: None,
+                            constraints: vec![],
+                            doc: None,
+                            attributes: vec![],
                         },
                     )]
                     .into_iter()
                     .collect(),
+                    discriminant: None,
+                    doc: None,
+                    attributes: vec![],
                 },
             )]
             .into_iter()
             .collect(),
+            doc: None,
+            attributes: vec![],
         };
 
         assert_eq!(Enum::from_lisp(parse(input)), Ok(expected));
@@ -264,10 +509,12 @@ mod tests {
         let expected = Enum {
             location: Location::default(),
             name: "Shape".to_string(),
+            type_parameters: vec![],
             variants: vec![(
                 "Point".to_string(),
                 Variant {
                     location: Location::default(),
+                    index: 0,
                     name: "Point".to_string(),
                     values: vec![
                         (
@@ -276,6 +523,11 @@ mod tests {
                                 location: Location::default(),
                                 name: "x".to_string(),
                                 type_: Type::I64,
+                                default: None,
+                                default_expression: None,
+                                constraints: vec![],
+                                doc: None,
+                                attributes: vec![],
                             },
                         ),
                         (
@@ -284,15 +536,25 @@ mod tests {
                                 location: Location::default(),
                                 name: "y".to_string(),
                                 type_: Type::I64,
+                                default: None,
+                                default_expression: None,
+                                constraints: vec![],
+                                doc: None,
+                                attributes: vec![],
                             },
                         ),
                     ]
                     .into_iter()
                     .collect(),
+                    discriminant: None,
+                    doc: None,
+                    attributes: vec![],
                 },
             )]
             .into_iter()
             .collect(),
+            doc: None,
+            attributes: vec![],
         };
         let result = Enum::from_lisp(list);
         assert_eq!(result, Ok(expected));
@@ -350,4 +612,175 @@ mod tests {
 
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn from_lisp_generic_returns_type_parameters() {
+        let input = "(enum (Maybe T) None (Some T))";
+        let list = parse(input);
+        let expected = Enum {
+            location: Location::default(),
+            name: "Maybe".to_string(),
+            type_parameters: vec!["T".to_string()],
+            variants: vec![
+                (
+                    "None".to_string(),
+                    Variant {
+                        location: Location::default(),
+                        index: 0,
+                        name: "None".to_string(),
+                        values: HashMap::new(),
+                        discriminant: None,
+                        doc: None,
+                        attributes: vec![],
+                    },
+                ),
+                (
+                    "Some".to_string(),
+                    Variant {
+                        location: Location::default(),
+                        index: 1,
+                        name: "Some".to_string(),
+                        values: vec![(
+                            "value".to_string(),
+                            Field {
+                                location: Location::default(),
+                                name: "value".to_string(),
+                                type_: Type::Identifier("T".to_string()),
+                                default: None,
+                                default_expression: None,
+                                constraints: vec![],
+                                doc: None,
+                                attributes: vec![],
+                            },
+                        )]
+                        .into_iter()
+                        .collect(),
+                        discriminant: None,
+                        doc: None,
+                        attributes: vec![],
+                    },
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            doc: None,
+            attributes: vec![],
+        };
+        let result = Enum::from_lisp(list);
+        assert_eq!(result, Ok(expected));
+    }
+
+    #[test]
+    fn from_lisp_parses_variant_docs() {
+        let input = "(enum Shape (Circle (doc \"A round shape\") f32) (Square (doc \"Four sides\")) Triangle)";
+        let list = parse(input);
+        let result = Enum::from_lisp(list).unwrap();
+
+        assert_eq!(
+            result.variants["Circle"].doc,
+            Some("A round shape".to_string())
+        );
+        assert_eq!(result.variants["Circle"].values.len(), 1);
+        assert_eq!(
+            result.variants["Square"].doc,
+            Some("Four sides".to_string())
+        );
+        assert_eq!(result.variants["Square"].values.len(), 0);
+        assert_eq!(result.variants["Triangle"].doc, None);
+    }
+
+    #[test]
+    fn from_lisp_parses_variant_attributes() {
+        let input = "(enum Shape (Circle (@ cpp-name \"Round\") f32) Square)";
+        let list = parse(input);
+        let result = Enum::from_lisp(list).unwrap();
+
+        assert_eq!(result.variants["Circle"].attributes.len(), 1);
+        assert_eq!(result.variants["Circle"].values.len(), 1);
+        assert_eq!(result.variants["Square"].attributes, vec![]);
+    }
+
+    #[test]
+    fn from_lisp_parses_discriminants() {
+        let input = "(enum Direction (@ repr u8) (North 0) (East 1) (South 2) (West 4))";
+        let result = Enum::from_lisp(parse(input)).unwrap();
+
+        assert_eq!(result.repr(), Ok(Some(Type::U8)));
+        assert_eq!(
+            result
+                .discriminants()
+                .iter()
+                .map(|(v, d)| (v.name.as_str(), *d))
+                .collect::<Vec<_>>(),
+            vec![("North", 0), ("East", 1), ("South", 2), ("West", 4)]
+        );
+    }
+
+    #[test]
+    fn discriminants_without_values_use_declaration_order() {
+        let input = "(enum Direction North East South West)";
+        let result = Enum::from_lisp(parse(input)).unwrap();
+
+        assert_eq!(
+            result
+                .discriminants()
+                .iter()
+                .map(|(v, d)| (v.name.as_str(), *d))
+                .collect::<Vec<_>>(),
+            vec![("North", 0), ("East", 1), ("South", 2), ("West", 3)]
+        );
+    }
+
+    #[test]
+    fn from_lisp_duplicate_discriminant_returns_err() {
+        let input = "(enum Direction (North 0) (East 1) (South 1))";
+        let expected = Err("Duplicate discriminant 1 for variants 'East' and 'South'".into());
+
+        assert_eq!(Enum::from_lisp(parse(input)), expected);
+    }
+
+    #[test]
+    fn from_lisp_discriminant_does_not_fit_repr_returns_err() {
+        let input = "(enum Direction (@ repr u8) (North 0) (East 256))";
+        let expected = Err("Value 256 does not fit in u8".into());
+
+        assert_eq!(Enum::from_lisp(parse(input)), expected);
+    }
+
+    #[test]
+    fn from_lisp_missing_discriminant_returns_err() {
+        let input = "(enum Direction (North 0) East)";
+        let expected = Err(
+            "Variant 'East' needs a discriminant, as other variants of 'Direction' have one".into(),
+        );
+
+        assert_eq!(Enum::from_lisp(parse(input)), expected);
+    }
+
+    #[test]
+    fn from_lisp_discriminant_on_complex_enum_returns_err() {
+        let input = "(enum Shape (Point 0) (Circle f32))";
+        let expected = Err(
+            "Enum 'Shape' has a complex variant 'Circle', so it can not have a repr or discriminants"
+                .into(),
+        );
+
+        assert_eq!(Enum::from_lisp(parse(input)), expected);
+    }
+
+    #[test]
+    fn from_lisp_non_integer_discriminant_returns_err() {
+        let input = "(enum Direction (North 0.5))";
+        let expected = Err("Expected discriminant, got '0.5'".into());
+
+        assert_eq!(Enum::from_lisp(parse(input)), expected);
+    }
+
+    #[test]
+    fn from_lisp_non_integer_repr_returns_err() {
+        let input = "(enum Direction (@ repr f32) North)";
+        let expected = Err("Enum 'Direction' repr must be an integer type, got 'f32'".into());
+
+        assert_eq!(Enum::from_lisp(parse(input)), expected);
+    }
 }
@@ -0,0 +1,710 @@
+use super::{literal::Literal, type_::Type};
+use lisper::{Error, List, Location};
+
+/// An operator applied to the operands of an operation, such as `(+ a b)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    And,
+    Or,
+    Not,
+}
+impl Operator {
+    /// Returns the operator the identifier names, if any.
+    pub fn try_parse(identifier: &str) -> Option<Operator> {
+        match identifier {
+            "+" => Some(Operator::Add),
+            "-" => Some(Operator::Subtract),
+            "*" => Some(Operator::Multiply),
+            "/" => Some(Operator::Divide),
+            "%" => Some(Operator::Remainder),
+            "=" => Some(Operator::Equal),
+            "!=" => Some(Operator::NotEqual),
+            "<" => Some(Operator::Less),
+            "<=" => Some(Operator::LessEqual),
+            ">" => Some(Operator::Greater),
+            ">=" => Some(Operator::GreaterEqual),
+            "and" => Some(Operator::And),
+            "or" => Some(Operator::Or),
+            "not" => Some(Operator::Not),
+            _ => None,
+        }
+    }
+
+    /// Returns the identifier the operator is written as.
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Operator::Add => "+",
+            Operator::Subtract => "-",
+            Operator::Multiply => "*",
+            Operator::Divide => "/",
+            Operator::Remainder => "%",
+            Operator::Equal => "=",
+            Operator::NotEqual => "!=",
+            Operator::Less => "<",
+            Operator::LessEqual => "<=",
+            Operator::Greater => ">",
+            Operator::GreaterEqual => ">=",
+            Operator::And => "and",
+            Operator::Or => "or",
+            Operator::Not => "not",
+        }
+    }
+
+    /// Returns whether the operator compares its operands and results in a bool.
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            Operator::Equal
+                | Operator::NotEqual
+                | Operator::Less
+                | Operator::LessEqual
+                | Operator::Greater
+                | Operator::GreaterEqual
+        )
+    }
+
+    /// Returns whether the operator works on bools.
+    pub fn is_logical(&self) -> bool {
+        matches!(self, Operator::And | Operator::Or | Operator::Not)
+    }
+
+    /// Returns the inclusive range of operands the operator takes. `None` means there is no upper limit.
+    fn arity(&self) -> (usize, Option<usize>) {
+        match self {
+            Operator::Not => (1, Some(1)),
+            Operator::Subtract => (1, None),
+            Operator::Add | Operator::Multiply | Operator::And | Operator::Or => (2, None),
+            _ => (2, Some(2)),
+        }
+    }
+}
+
+/// An expression in the body of a function.
+#[derive(Debug, Clone)]
+pub struct Expression {
+    pub location: Location,
+    pub kind: ExpressionKind,
+}
+impl PartialEq for Expression {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExpressionKind {
+    Literal(Literal),
+    /// A parameter, binding, constant or `self`.
+    Variable(String),
+    /// `(let name value)` binds the value for the rest of the block.
+    Let(String, Box<Expression>),
+    /// `(set target value)` assigns to a binding or field.
+    Set(Box<Expression>, Box<Expression>),
+    /// `(get value field)` reads the field of a struct.
+    Get(Box<Expression>, String),
+    /// `(+ a b)` and the other operators.
+    Operation(Operator, Vec<Expression>),
+    /// `(if condition then else)`, where the else branch is optional.
+    If(Box<Expression>, Box<Expression>, Option<Box<Expression>>),
+    /// `(cond (condition body...)... (else body...))`.
+    Cond(Vec<(Expression, Expression)>, Option<Box<Expression>>),
+    /// `(for item list body...)` runs the body for each item of the list.
+    For(String, Box<Expression>, Box<Expression>),
+    /// `(do body...)` evaluates to its last expression.
+    Do(Vec<Expression>),
+    /// `(return value)`, where the value is left out for void functions.
+    Return(Option<Box<Expression>>),
+    /// `(name arguments...)` calls a function.
+    Call(String, Vec<Expression>),
+    /// `(Type (field value)...)` builds a struct and `(Type Variant (field value)...)` an enum variant.
+    /// Types are told apart from functions by their capitalized name.
+    Construct {
+        type_: Type,
+        variant: Option<String>,
+        fields: Vec<(String, Expression)>,
+    },
+    /// `(match value (Variant body...) ((Variant value...) body...) (_ body...))` picks the arm of the enum variant.
+    Match(Box<Expression>, Vec<MatchArm>),
+}
+
+/// An arm of a `match`, evaluated when its pattern matches the value.
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub location: Location,
+    pub pattern: Pattern,
+    pub body: Expression,
+}
+impl PartialEq for MatchArm {
+    fn eq(&self, other: &Self) -> bool {
+        self.pattern == other.pattern && self.body == other.body
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    /// A variant along with the names of the values it binds.
+    Variant(String, Vec<String>),
+    /// `_` matches any variant.
+    Wildcard,
+}
+
+impl Expression {
+    /// Pops an expression from the front of the list.
+    pub fn pop(list: &mut List) -> Result<Expression, Error> {
+        if list.front_is_list() {
+            let inner = list.pop_list("expression")?;
+            return Expression::parse_list(inner);
+        }
+
+        if list.front_is_identifier() {
+            let (identifier, location) = list.pop_identifier("expression")?;
+            let kind = match identifier.as_str() {
+                "true" => ExpressionKind::Literal(Literal::Bool(true)),
+                "false" => ExpressionKind::Literal(Literal::Bool(false)),
+                _ => ExpressionKind::Variable(identifier),
+            };
+            return Ok(Expression { location, kind });
+        }
+
+        let (literal, location) = Literal::pop(list, "expression")?;
+        Ok(Expression {
+            location,
+            kind: ExpressionKind::Literal(literal),
+        })
+    }
+
+    /// Pops the remaining expressions of the list into a block, which evaluates to its last expression.
+    fn pop_block(list: &mut List, location: Location) -> Result<Expression, Error> {
+        let mut body = vec![];
+        while !list.is_empty() {
+            body.push(Expression::pop(list)?);
+        }
+
+        if body.is_empty() {
+            return Err(Error {
+                message: "Expected expression".into(),
+                location,
+            });
+        }
+
+        if body.len() == 1 {
+            return Ok(body.remove(0));
+        }
+
+        Ok(Expression {
+            location,
+            kind: ExpressionKind::Do(body),
+        })
+    }
+
+    fn parse_list(mut list: List) -> Result<Expression, Error> {
+        let location = list.location();
+        if !list.front_is_identifier() {
+            if list.is_empty() {
+                return Err(Error {
+                    message: "Expected expression".into(),
+                    location,
+                });
+            }
+
+            let n = list.pop_front("expression")?;
+            return Err(Error {
+                message: "Expected function name".into(),
+                location: n.first_location(),
+            });
+        }
+
+        let (head, location) = list.pop_identifier("function name")?;
+        let kind = match head.as_str() {
+            "let" => {
+                let (name, _) = list.pop_identifier("binding name")?;
+                let value = Expression::pop(&mut list)?;
+                ExpressionKind::Let(name, Box::new(value))
+            }
+            "set" => {
+                let target = Expression::pop(&mut list)?;
+                match target.kind {
+                    ExpressionKind::Variable(_) | ExpressionKind::Get(_, _) => {}
+                    _ => {
+                        return Err(Error {
+                            message: "Expected binding or field to set".into(),
+                            location: target.location,
+                        })
+                    }
+                }
+                let value = Expression::pop(&mut list)?;
+                ExpressionKind::Set(Box::new(target), Box::new(value))
+            }
+            "get" => {
+                let value = Expression::pop(&mut list)?;
+                let (field, _) = list.pop_identifier("field name")?;
+                ExpressionKind::Get(Box::new(value), field)
+            }
+            "if" => {
+                let condition = Expression::pop(&mut list)?;
+                let then = Expression::pop(&mut list)?;
+                let otherwise = match list.is_empty() {
+                    true => None,
+                    false => Some(Box::new(Expression::pop(&mut list)?)),
+                };
+                ExpressionKind::If(Box::new(condition), Box::new(then), otherwise)
+            }
+            "cond" => {
+                let mut clauses = vec![];
+                let mut otherwise = None;
+                while !list.is_empty() {
+                    let mut clause = list.pop_list("cond clause")?;
+                    let clause_location = clause.location();
+                    if otherwise.is_some() {
+                        return Err(Error {
+                            message: "Expected else to be the last clause".into(),
+                            location: clause_location,
+                        });
+                    }
+
+                    let is_else = match clause.peek_front().map(|n| n.as_identifier()) {
+                        Some(Ok(id)) => id == "else",
+                        _ => false,
+                    };
+                    if is_else {
+                        clause.pop_identifier("else")?;
+                        otherwise = Some(Box::new(Expression::pop_block(
+                            &mut clause,
+                            clause_location,
+                        )?));
+                        continue;
+                    }
+
+                    let condition = Expression::pop(&mut clause)?;
+                    let body = Expression::pop_block(&mut clause, clause_location)?;
+                    clauses.push((condition, body));
+                }
+
+                if clauses.is_empty() {
+                    return Err(Error {
+                        message: "Expected cond clause".into(),
+                        location,
+                    });
+                }
+                ExpressionKind::Cond(clauses, otherwise)
+            }
+            "for" => {
+                let (item, _) = list.pop_identifier("item name")?;
+                let items = Expression::pop(&mut list)?;
+                let body = Expression::pop_block(&mut list, location.clone())?;
+                ExpressionKind::For(item, Box::new(items), Box::new(body))
+            }
+            "do" => match Expression::pop_block(&mut list, location.clone())? {
+                Expression {
+                    kind: ExpressionKind::Do(body),
+                    ..
+                } => ExpressionKind::Do(body),
+                expression => ExpressionKind::Do(vec![expression]),
+            },
+            "match" => {
+                let value = Expression::pop(&mut list)?;
+                let mut arms = vec![];
+                while !list.is_empty() {
+                    arms.push(Expression::parse_arm(list.pop_list("match arm")?)?);
+                }
+
+                if arms.is_empty() {
+                    return Err(Error {
+                        message: "Expected match arm".into(),
+                        location,
+                    });
+                }
+                ExpressionKind::Match(Box::new(value), arms)
+            }
+            "return" => match list.is_empty() {
+                true => ExpressionKind::Return(None),
+                false => ExpressionKind::Return(Some(Box::new(Expression::pop(&mut list)?))),
+            },
+            _ => {
+                if let Some(operator) = Operator::try_parse(&head) {
+                    let mut operands = vec![];
+                    while !list.is_empty() {
+                        operands.push(Expression::pop(&mut list)?);
+                    }
+
+                    let (min, max) = operator.arity();
+                    if operands.len() < min || max.is_some_and(|max| operands.len() > max) {
+                        return Err(Error {
+                            message: format!(
+                                "Operator '{}' can not take {} operands",
+                                head,
+                                operands.len()
+                            ),
+                            location,
+                        });
+                    }
+                    ExpressionKind::Operation(operator, operands)
+                } else if is_type_name(&head) {
+                    Expression::parse_construct(&mut list, head, location.clone())?
+                } else {
+                    let mut arguments = vec![];
+                    while !list.is_empty() {
+                        arguments.push(Expression::pop(&mut list)?);
+                    }
+                    ExpressionKind::Call(head, arguments)
+                }
+            }
+        };
+
+        if !list.is_empty() {
+            let n = list.pop_front("Unexpected values")?;
+            return Err(Error {
+                message: "Expected end of list".into(),
+                location: n.first_location(),
+            });
+        }
+
+        Ok(Expression { location, kind })
+    }
+
+    fn parse_arm(mut list: List) -> Result<MatchArm, Error> {
+        let location = list.location();
+        let pattern = match list.front_is_list() {
+            true => {
+                let mut pattern = list.pop_list("pattern")?;
+                let (variant, _) = pattern.pop_identifier("variant")?;
+                let mut bindings: Vec<String> = vec![];
+                while !pattern.is_empty() {
+                    let (binding, binding_location) = pattern.pop_identifier("binding name")?;
+                    if bindings.contains(&binding) {
+                        return Err(Error {
+                            message: format!("Duplicate binding '{}'", binding),
+                            location: binding_location,
+                        });
+                    }
+                    bindings.push(binding);
+                }
+                Pattern::Variant(variant, bindings)
+            }
+            false => match list.pop_identifier("variant or _")?.0 {
+                wildcard if wildcard == "_" => Pattern::Wildcard,
+                variant => Pattern::Variant(variant, vec![]),
+            },
+        };
+
+        let body = Expression::pop_block(&mut list, location.clone())?;
+        Ok(MatchArm {
+            location,
+            pattern,
+            body,
+        })
+    }
+
+    fn parse_construct(
+        list: &mut List,
+        type_name: String,
+        location: Location,
+    ) -> Result<ExpressionKind, Error> {
+        let type_ = Type::try_parse(&type_name, location)?;
+        let variant = match list.front_is_identifier() {
+            true => Some(list.pop_identifier("variant")?.0),
+            false => None,
+        };
+
+        let mut fields: Vec<(String, Expression)> = vec![];
+        while !list.is_empty() {
+            let mut field = list.pop_list("field value")?;
+            let (name, location) = field.pop_identifier("field name")?;
+            if fields.iter().any(|(existing, _)| *existing == name) {
+                return Err(Error {
+                    message: format!("Duplicate field '{}'", name),
+                    location,
+                });
+            }
+
+            let value = Expression::pop(&mut field)?;
+            if !field.is_empty() {
+                let n = field.pop_front("Unexpected values")?;
+                return Err(Error {
+                    message: "Expected end of list".into(),
+                    location: n.first_location(),
+                });
+            }
+            fields.push((name, value));
+        }
+
+        Ok(ExpressionKind::Construct {
+            type_,
+            variant,
+            fields,
+        })
+    }
+
+    /// Returns the expressions directly inside this one.
+    pub fn children(&self) -> Vec<&Expression> {
+        match &self.kind {
+            ExpressionKind::Literal(_) | ExpressionKind::Variable(_) => vec![],
+            ExpressionKind::Let(_, value) | ExpressionKind::Get(value, _) => vec![value],
+            ExpressionKind::Set(target, value) => vec![target, value],
+            ExpressionKind::Operation(_, operands) => operands.iter().collect(),
+            ExpressionKind::If(condition, then, otherwise) => {
+                let mut children = vec![condition.as_ref(), then.as_ref()];
+                children.extend(otherwise.iter().map(|e| e.as_ref()));
+                children
+            }
+            ExpressionKind::Cond(clauses, otherwise) => {
+                let mut children = vec![];
+                for (condition, body) in clauses.iter() {
+                    children.push(condition);
+                    children.push(body);
+                }
+                children.extend(otherwise.iter().map(|e| e.as_ref()));
+                children
+            }
+            ExpressionKind::For(_, items, body) => vec![items, body],
+            ExpressionKind::Do(body) => body.iter().collect(),
+            ExpressionKind::Return(value) => value.iter().map(|e| e.as_ref()).collect(),
+            ExpressionKind::Call(_, arguments) => arguments.iter().collect(),
+            ExpressionKind::Construct { fields, .. } => fields.iter().map(|(_, e)| e).collect(),
+            ExpressionKind::Match(value, arms) => {
+                let mut children = vec![value.as_ref()];
+                children.extend(arms.iter().map(|arm| &arm.body));
+                children
+            }
+        }
+    }
+
+    /// Replaces the types of constructed values with the result of `f`.
+    pub fn map_types(&mut self, f: &dyn Fn(&Type) -> Type) {
+        if let ExpressionKind::Construct { type_, .. } = &mut self.kind {
+            *type_ = f(type_);
+        }
+
+        match &mut self.kind {
+            ExpressionKind::Literal(_) | ExpressionKind::Variable(_) => {}
+            ExpressionKind::Let(_, value) | ExpressionKind::Get(value, _) => value.map_types(f),
+            ExpressionKind::Set(target, value) => {
+                target.map_types(f);
+                value.map_types(f);
+            }
+            ExpressionKind::Operation(_, operands) => {
+                operands.iter_mut().for_each(|e| e.map_types(f));
+            }
+            ExpressionKind::If(condition, then, otherwise) => {
+                condition.map_types(f);
+                then.map_types(f);
+                otherwise.iter_mut().for_each(|e| e.map_types(f));
+            }
+            ExpressionKind::Cond(clauses, otherwise) => {
+                for (condition, body) in clauses.iter_mut() {
+                    condition.map_types(f);
+                    body.map_types(f);
+                }
+                otherwise.iter_mut().for_each(|e| e.map_types(f));
+            }
+            ExpressionKind::For(_, items, body) => {
+                items.map_types(f);
+                body.map_types(f);
+            }
+            ExpressionKind::Do(body) => body.iter_mut().for_each(|e| e.map_types(f)),
+            ExpressionKind::Return(value) => value.iter_mut().for_each(|e| e.map_types(f)),
+            ExpressionKind::Call(_, arguments) => {
+                arguments.iter_mut().for_each(|e| e.map_types(f));
+            }
+            ExpressionKind::Construct { fields, .. } => {
+                fields.iter_mut().for_each(|(_, e)| e.map_types(f));
+            }
+            ExpressionKind::Match(value, arms) => {
+                value.map_types(f);
+                arms.iter_mut().for_each(|arm| arm.body.map_types(f));
+            }
+        }
+    }
+}
+
+/// Returns whether the identifier names a type, which starts with a capital letter after any modules.
+fn is_type_name(identifier: &str) -> bool {
+    identifier
+        .rsplit('/')
+        .next()
+        .and_then(|name| name.chars().next())
+        .is_some_and(|c| c.is_ascii_uppercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> Result<Expression, Error> {
+        let mut list = lisper::parse_str(&format!("({})", input)).unwrap()[0].clone();
+        Expression::pop(&mut list)
+    }
+
+    fn variable(name: &str) -> Expression {
+        Expression {
+            location: Location::default(),
+            kind: ExpressionKind::Variable(name.into()),
+        }
+    }
+
+    fn int(value: i128) -> Expression {
+        Expression {
+            location: Location::default(),
+            kind: ExpressionKind::Literal(Literal::Int(value)),
+        }
+    }
+
+    #[test]
+    fn pop_literal_returns_literal() {
+        assert_eq!(parse("42"), Ok(int(42)));
+        assert_eq!(
+            parse("true").map(|e| e.kind),
+            Ok(ExpressionKind::Literal(Literal::Bool(true)))
+        );
+    }
+
+    #[test]
+    fn pop_operation_returns_operation() {
+        let expected = ExpressionKind::Operation(Operator::Add, vec![variable("a"), int(1)]);
+        assert_eq!(parse("(+ a 1)").map(|e| e.kind), Ok(expected));
+    }
+
+    #[test]
+    fn pop_comparison_with_three_operands_returns_err() {
+        let expected = Err("Operator '<' can not take 3 operands".into());
+        assert_eq!(parse("(< a b c)"), expected);
+    }
+
+    #[test]
+    fn pop_let_and_get_returns_binding() {
+        let expected = ExpressionKind::Let(
+            "width".into(),
+            Box::new(Expression {
+                location: Location::default(),
+                kind: ExpressionKind::Get(Box::new(variable("self")), "x-max".into()),
+            }),
+        );
+        assert_eq!(
+            parse("(let width (get self x-max))").map(|e| e.kind),
+            Ok(expected)
+        );
+    }
+
+    #[test]
+    fn pop_set_literal_returns_err() {
+        let expected = Err("Expected binding or field to set".into());
+        assert_eq!(parse("(set 1 2)"), expected);
+    }
+
+    #[test]
+    fn pop_cond_returns_clauses() {
+        let result = parse("(cond ((< a 0) 0) ((> a 10) (let b 10) b) (else a))").unwrap();
+        match result.kind {
+            ExpressionKind::Cond(clauses, otherwise) => {
+                assert_eq!(clauses.len(), 2);
+                assert!(matches!(clauses[1].1.kind, ExpressionKind::Do(_)));
+                assert_eq!(otherwise, Some(Box::new(variable("a"))));
+            }
+            kind => panic!("Expected cond, got {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn pop_cond_else_not_last_returns_err() {
+        let expected = Err("Expected else to be the last clause".into());
+        assert_eq!(parse("(cond (else 1) (a 2))"), expected);
+    }
+
+    #[test]
+    fn pop_for_returns_loop() {
+        let result = parse("(for move moves (set total (+ total 1)))").unwrap();
+        match result.kind {
+            ExpressionKind::For(item, items, _) => {
+                assert_eq!(item, "move".to_string());
+                assert_eq!(*items, variable("moves"));
+            }
+            kind => panic!("Expected for, got {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn pop_construct_returns_fields() {
+        let expected = ExpressionKind::Construct {
+            type_: Type::Identifier("Point".into()),
+            variant: None,
+            fields: vec![("x".into(), int(1)), ("y".into(), variable("y"))],
+        };
+        assert_eq!(parse("(Point (x 1) (y y))").map(|e| e.kind), Ok(expected));
+    }
+
+    #[test]
+    fn pop_construct_variant_returns_variant() {
+        let expected = ExpressionKind::Construct {
+            type_: Type::Identifier("combat/Mood".into()),
+            variant: Some("Happy".into()),
+            fields: vec![],
+        };
+        assert_eq!(parse("(combat/Mood Happy)").map(|e| e.kind), Ok(expected));
+    }
+
+    #[test]
+    fn pop_construct_duplicate_field_returns_err() {
+        let expected = Err("Duplicate field 'x'".into());
+        assert_eq!(parse("(Point (x 1) (x 2))"), expected);
+    }
+
+    #[test]
+    fn pop_call_returns_call() {
+        let expected = ExpressionKind::Call("collides?".into(), vec![variable("a"), variable("b")]);
+        assert_eq!(parse("(collides? a b)").map(|e| e.kind), Ok(expected));
+    }
+
+    #[test]
+    fn pop_empty_returns_err() {
+        assert_eq!(parse("()"), Err("Expected expression".into()));
+    }
+
+    #[test]
+    fn pop_extra_values_returns_err() {
+        assert_eq!(parse("(get a b c)"), Err("Expected end of list".into()));
+    }
+
+    #[test]
+    fn pop_match_returns_arms() {
+        let result =
+            parse("(match action ((Attack damage) damage) (Wait 0) (_ (let a 1) a))").unwrap();
+        match result.kind {
+            ExpressionKind::Match(value, arms) => {
+                assert_eq!(*value, variable("action"));
+                assert_eq!(
+                    arms.iter().map(|a| a.pattern.clone()).collect::<Vec<_>>(),
+                    vec![
+                        Pattern::Variant("Attack".into(), vec!["damage".into()]),
+                        Pattern::Variant("Wait".into(), vec![]),
+                        Pattern::Wildcard,
+                    ]
+                );
+                assert_eq!(arms[0].body, variable("damage"));
+                assert!(matches!(arms[2].body.kind, ExpressionKind::Do(_)));
+            }
+            kind => panic!("Expected match, got {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn pop_match_without_arms_returns_err() {
+        assert_eq!(parse("(match action)"), Err("Expected match arm".into()));
+    }
+
+    #[test]
+    fn pop_match_duplicate_binding_returns_err() {
+        let expected = Err("Duplicate binding 'a'".into());
+        assert_eq!(parse("(match action ((Move a a) a))"), expected);
+    }
+}
@@ -2,7 +2,15 @@ use std::collections::HashMap;
 
 use lisper::{Error, List};
 
-use super::type_::Type;
+use super::{
+    attribute::Attribute,
+    constraint::Constraint,
+    doc::Doc,
+    expression::{Expression, Operator},
+    literal::Literal,
+    type_::Type,
+    FromLisp,
+};
 
 /// A field. Can be properties, arguments, etc.
 #[derive(Debug, Clone)]
@@ -10,11 +18,24 @@ pub struct Field {
     pub location: lisper::Location,
     pub name: String,
     pub type_: Type,
+    pub default: Option<(lisper::Location, Literal)>,
+    /// The expression the default is computed from, if it is not a literal.
+    /// The default is filled in once the expression is evaluated.
+    pub default_expression: Option<Expression>,
+    pub constraints: Vec<Constraint>,
+    pub doc: Option<String>,
+    pub attributes: Vec<Attribute>,
 }
 
 impl PartialEq for Field {
     fn eq(&self, other: &Self) -> bool {
-        self.name == other.name && self.type_ == other.type_
+        self.name == other.name
+            && self.type_ == other.type_
+            && self.default.as_ref().map(|(_, d)| d) == other.default.as_ref().map(|(_, d)| d)
+            && self.default_expression == other.default_expression
+            && self.constraints == other.constraints
+            && self.doc == other.doc
+            && self.attributes == other.attributes
     }
 }
 impl Field {
@@ -28,6 +49,71 @@ impl Field {
         let ty = Type::try_parse(&ty_, loc)?;
 
         let (name, loc) = property_list.pop_identifier("name")?;
+
+        // Parse the default value. Identifiers are checked once the types they refer to are known.
+        let default = if Literal::can_pop(&property_list) {
+            let default = Literal::pop(&mut property_list, "default value")?;
+            if !ty.is_identifier() {
+                default.0.type_check(&ty, default.1.clone())?;
+            }
+            Some((default.1, default.0))
+        } else {
+            None
+        };
+
+        // A default can also be a constant or an operator expression, evaluated once every definition is known.
+        // Other lists are options, so unknown options are still reported as such.
+        let default_expression = if default.is_none() && Self::front_is_expression(&property_list) {
+            Some(Expression::pop(&mut property_list)?)
+        } else {
+            None
+        };
+
+        // Parse constraints, docs and attributes. Identifiers are checked once the types they refer to are known.
+        let mut constraints = vec![];
+        let mut doc = None;
+        let mut attributes = vec![];
+        while property_list.front_is_list() {
+            let constraint_list = property_list.pop_list("constraint")?;
+            if Attribute::can_try(&constraint_list) {
+                attributes.push(Attribute::from_lisp(constraint_list)?);
+                continue;
+            }
+
+            if Doc::can_try(&constraint_list) {
+                let d = Doc::from_lisp(constraint_list)?;
+                if doc.is_some() {
+                    return Err(Error {
+                        message: format!("Duplicate doc for {} '{}'", field_type, name),
+                        location: d.location,
+                    });
+                }
+                doc = Some(d.text);
+                continue;
+            }
+
+            if !Constraint::can_try(&constraint_list) {
+                return Err(Error {
+                    message: format!("Unknown option '{}'", constraint_list),
+                    location: constraint_list.location(),
+                });
+            }
+
+            let constraint = Constraint::parse(constraint_list)?;
+            if !ty.is_identifier() {
+                constraint.validate(&ty)?;
+            }
+            constraints.push(constraint);
+        }
+
+        if !property_list.is_empty() {
+            let n = property_list.pop_front("Unexpected values")?;
+            return Err(Error {
+                message: "Expected end of list".into(),
+                location: n.first_location(),
+            });
+        }
+
         match fields.get(&name) {
             Some(_) => {
                 return Err(Error {
@@ -42,6 +128,11 @@ impl Field {
                         location: loc,
                         name,
                         type_: ty,
+                        default,
+                        default_expression,
+                        constraints,
+                        doc,
+                        attributes,
                     },
                 );
             }
@@ -49,4 +140,20 @@ impl Field {
 
         Ok(())
     }
+
+    /// Returns whether the front of the list is a default expression, such as `MAX-LEVEL` or `(* MAX-LEVEL 10)`.
+    fn front_is_expression(list: &List) -> bool {
+        if list.front_is_identifier() {
+            return true;
+        }
+
+        let mut list = list.clone();
+        match list.pop_list("default value") {
+            Ok(inner) => match inner.peek_front().map(|n| n.as_identifier()) {
+                Some(Ok(head)) => Operator::try_parse(&head).is_some(),
+                _ => false,
+            },
+            Err(_) => false,
+        }
+    }
 }
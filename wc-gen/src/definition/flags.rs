@@ -0,0 +1,154 @@
+use super::{attribute::Attribute, parse_annotations, type_::Type, FromLisp};
+use lisper::{Error, Location};
+
+/// The most values a flag set can hold, one per bit of the largest integer.
+const MAX_VALUES: usize = 64;
+
+/// A set of flags stored as a bitmask, where each value is a single bit.
+#[derive(Debug, Clone)]
+pub struct Flags {
+    pub location: Location,
+    pub name: String,
+    pub values: Vec<String>,
+    pub doc: Option<String>,
+    pub attributes: Vec<Attribute>,
+}
+impl PartialEq for Flags {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.values == other.values
+            && self.doc == other.doc
+            && self.attributes == other.attributes
+    }
+}
+
+impl Flags {
+    /// Returns the smallest unsigned integer with a bit for every value.
+    pub fn underlying_type(&self) -> Type {
+        match self.values.len() {
+            0..=8 => Type::U8,
+            9..=16 => Type::U16,
+            17..=32 => Type::U32,
+            _ => Type::U64,
+        }
+    }
+}
+
+impl FromLisp for Flags {
+    fn identifier() -> &'static str {
+        "flags"
+    }
+
+    fn get_related_types(&self) -> Vec<(Location, Type)> {
+        vec![]
+    }
+
+    fn parse_values(list: &mut lisper::List) -> Result<Self, Error> {
+        let (name, location) = list.pop_identifier("flags name")?;
+        let (doc, attributes) = parse_annotations(list)?;
+
+        let mut values: Vec<String> = vec![];
+        while !list.is_empty() {
+            let (value, loc) = list.pop_identifier("flag")?;
+            if values.contains(&value) {
+                return Err(Error {
+                    message: format!("Duplicate flag '{}'", value),
+                    location: loc,
+                });
+            }
+
+            values.push(value);
+            if values.len() > MAX_VALUES {
+                return Err(Error {
+                    message: format!("Flags '{}' can have at most {} values", name, MAX_VALUES),
+                    location: loc,
+                });
+            }
+        }
+
+        Ok(Flags {
+            location,
+            name,
+            values,
+            doc,
+            attributes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> lisper::List {
+        lisper::parse_str(input).unwrap()[0].clone()
+    }
+
+    #[test]
+    fn can_try_not_identifier_returns_false() {
+        let input = parse("(enum Element Fire)");
+        assert!(!Flags::can_try(&input));
+    }
+
+    #[test]
+    fn can_try_identifier_returns_true() {
+        let input = parse("(flags Element Fire)");
+        assert!(Flags::can_try(&input));
+    }
+
+    #[test]
+    fn from_lisp_returns_flags() {
+        let input = parse("(flags Element (doc \"Elemental types\") Fire Water Earth Air)");
+        let expected = Flags {
+            location: Location::default(),
+            name: "Element".into(),
+            values: vec!["Fire".into(), "Water".into(), "Earth".into(), "Air".into()],
+            doc: Some("Elemental types".into()),
+            attributes: vec![],
+        };
+
+        assert_eq!(Flags::from_lisp(input), Ok(expected));
+    }
+
+    #[test]
+    fn from_lisp_duplicate_value_returns_err() {
+        let input = parse("(flags Element Fire Water Fire)");
+        let expected = Err("Duplicate flag 'Fire'".into());
+
+        assert_eq!(Flags::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn from_lisp_list_value_returns_err() {
+        let input = parse("(flags Element Fire (Water))");
+        let expected = Err("Expected flag".into());
+
+        assert_eq!(Flags::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn from_lisp_too_many_values_returns_err() {
+        let values = (0..65).map(|i| format!("F{}", i)).collect::<Vec<_>>();
+        let input = parse(&format!("(flags Big {})", values.join(" ")));
+        let expected = Err("Flags 'Big' can have at most 64 values".into());
+
+        assert_eq!(Flags::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn underlying_type_depends_on_count() {
+        let make = |count: usize| Flags {
+            location: Location::default(),
+            name: "Element".into(),
+            values: (0..count).map(|i| format!("F{}", i)).collect(),
+            doc: None,
+            attributes: vec![],
+        };
+
+        assert_eq!(make(4).underlying_type(), Type::U8);
+        assert_eq!(make(8).underlying_type(), Type::U8);
+        assert_eq!(make(9).underlying_type(), Type::U16);
+        assert_eq!(make(17).underlying_type(), Type::U32);
+        assert_eq!(make(64).underlying_type(), Type::U64);
+    }
+}
@@ -0,0 +1,83 @@
+use crate::definition::expression::Expression;
+use lisper::{Error, List};
+
+/// Whether a contract is checked before or after the function runs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContractKind {
+    /// Written as `(requires ...)`, checked against the arguments before the function runs.
+    Requires,
+    /// Written as `(ensures ...)`, checked after the function runs with its return value bound to `result`.
+    Ensures,
+}
+impl ContractKind {
+    /// Returns the kind of contract the identifier names, if any.
+    pub fn try_parse(identifier: &str) -> Option<ContractKind> {
+        match identifier {
+            "requires" => Some(ContractKind::Requires),
+            "ensures" => Some(ContractKind::Ensures),
+            _ => None,
+        }
+    }
+
+    /// Returns how a failed condition of this kind is reported.
+    pub fn describe(&self) -> &'static str {
+        match self {
+            ContractKind::Requires => "Precondition",
+            ContractKind::Ensures => "Postcondition",
+        }
+    }
+}
+
+/// A condition a function promises holds, such as `(> level 0)` in `(requires (> level 0))`.
+#[derive(Debug, Clone)]
+pub struct Contract {
+    pub location: lisper::Location,
+    /// The condition as it was written, used to report that it failed.
+    pub source: String,
+    pub condition: Expression,
+}
+impl PartialEq for Contract {
+    fn eq(&self, other: &Self) -> bool {
+        self.condition == other.condition
+    }
+}
+
+impl Contract {
+    /// Returns the kind of the contract list at the front of the list, if there is one.
+    pub fn front_kind(list: &List) -> Option<ContractKind> {
+        if !list.front_is_list() {
+            return None;
+        }
+
+        let mut list = list.clone();
+        let contract_list = list.pop_list("contract").ok()?;
+        match contract_list.peek_front().map(|n| n.as_identifier()) {
+            Some(Ok(id)) => ContractKind::try_parse(&id),
+            _ => None,
+        }
+    }
+
+    /// Parses the conditions of a `requires` or `ensures` list.
+    pub fn parse(mut list: List) -> Result<Vec<Contract>, Error> {
+        let (id, location) = list.pop_identifier("contract")?;
+        let mut contracts = vec![];
+        while let Some(node) = list.peek_front() {
+            let source = node.to_string();
+            let condition = Expression::pop(&mut list)?;
+            contracts.push(Contract {
+                location: condition.location.clone(),
+                source,
+                condition,
+            });
+        }
+
+        if contracts.is_empty() {
+            return Err(Error {
+                message: format!("Expected condition after '{}'", id),
+                location,
+            });
+        }
+
+        Ok(contracts)
+    }
+}
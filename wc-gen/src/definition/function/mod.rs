@@ -1,7 +1,11 @@
+mod contract;
 mod parameter;
+pub use contract::*;
 pub use parameter::*;
 
-use super::{type_::Type, FromLisp};
+use super::{
+    attribute::Attribute, expression::Expression, parse_annotations, type_::Type, FromLisp,
+};
 use lisper::Error;
 use std::collections::HashSet;
 
@@ -11,14 +15,132 @@ pub struct Function {
     pub name: String,
     pub parameters: Vec<Parameter>,
     pub return_type: (lisper::Location, Type),
+    /// Whether the function is declared with `(self const)` and does not modify its struct.
+    pub is_const: bool,
+    /// Whether the function is declared with `static-fn` and belongs to its struct rather than an instance.
+    pub is_static: bool,
+    /// The expressions the function evaluates. Empty if the function is implemented by hand.
+    pub body: Vec<Expression>,
+    /// The conditions checked against the arguments before the function runs.
+    pub requires: Vec<Contract>,
+    /// The conditions checked after the function runs, with its return value bound to `result`.
+    pub ensures: Vec<Contract>,
+    pub doc: Option<String>,
+    pub attributes: Vec<Attribute>,
 }
 impl PartialEq for Function {
     fn eq(&self, other: &Self) -> bool {
         self.name == other.name
             && self.parameters == other.parameters
             && self.return_type.1 == other.return_type.1
+            && self.is_const == other.is_const
+            && self.is_static == other.is_static
+            && self.body == other.body
+            && self.requires == other.requires
+            && self.ensures == other.ensures
+            && self.doc == other.doc
+            && self.attributes == other.attributes
     }
 }
+impl Function {
+    /// Replaces the types in the signature with the result of `f`.
+    pub fn map_types(&mut self, f: &dyn Fn(&Type) -> Type) {
+        for parameter in self.parameters.iter_mut() {
+            parameter.type_ = f(&parameter.type_);
+        }
+        self.return_type.1 = f(&self.return_type.1);
+        for expression in self.body.iter_mut() {
+            expression.map_types(f);
+        }
+        for contract in self.requires.iter_mut().chain(self.ensures.iter_mut()) {
+            contract.condition.map_types(f);
+        }
+    }
+
+    /// Returns the `requires` conditions followed by the `ensures` conditions.
+    pub fn contracts(&self) -> impl Iterator<Item = &Contract> {
+        self.requires.iter().chain(self.ensures.iter())
+    }
+
+    /// Returns whether both functions take the same parameter types and modes, return the same type
+    /// and agree on `(self const)` and `static-fn`. Parameter names are not part of the signature.
+    pub fn signature_matches(&self, other: &Function) -> bool {
+        self.return_type.1 == other.return_type.1
+            && self.is_const == other.is_const
+            && self.is_static == other.is_static
+            && self.parameters.len() == other.parameters.len()
+            && self
+                .parameters
+                .iter()
+                .zip(other.parameters.iter())
+                .all(|(a, b)| a.type_ == b.type_ && a.mode == b.mode)
+    }
+
+    /// Returns whether the list is a `static-fn`.
+    pub fn can_try_static(list: &lisper::List) -> bool {
+        match list.peek_front().map(|n| n.as_identifier()) {
+            Some(Ok(id)) => id == "static-fn",
+            _ => false,
+        }
+    }
+
+    /// Parses a `static-fn`, which is a function called on its struct rather than an instance.
+    pub fn from_lisp_static(mut list: lisper::List) -> Result<Function, Error> {
+        let (id, location) = list.pop_identifier("static-fn")?;
+        if id != "static-fn" {
+            return Err(Error {
+                message: "Expected identifier 'static-fn'".into(),
+                location,
+            });
+        }
+
+        let mut f = Function::parse_values(&mut list)?;
+        if f.is_const {
+            return Err(Error {
+                message: format!("Static function '{}' can not be const", f.name),
+                location: f.location,
+            });
+        }
+
+        f.is_static = true;
+        Ok(f)
+    }
+
+    /// Pops `(self const)` from the front of the list, returning whether it was there.
+    fn pop_self(list: &mut lisper::List) -> Result<bool, Error> {
+        if !list.front_is_list() {
+            return Ok(false);
+        }
+
+        let mut remaining = list.clone();
+        let mut self_list = remaining.pop_list("self")?;
+        match self_list.peek_front().map(|n| n.as_identifier()) {
+            Some(Ok(id)) if id == "self" => {}
+            _ => return Ok(false),
+        }
+
+        self_list.pop_identifier("self")?;
+        let (modifier, location) = self_list.pop_identifier("const after self")?;
+        if modifier != "const" {
+            return Err(Error {
+                message: format!("Expected const after self, got '{}'", modifier),
+                location,
+            });
+        }
+
+        if !self_list.is_empty() {
+            let n = self_list.pop_front("Unexpected values")?;
+            return Err(Error {
+                message: "Expected end of list".into(),
+                location: n.first_location(),
+            });
+        }
+
+        *list = remaining;
+        Ok(true)
+    }
+}
+
 impl FromLisp for Function {
     fn identifier() -> &'static str {
         "fn"
@@ -26,20 +148,38 @@ impl FromLisp for Function {
 
     fn parse_values(list: &mut lisper::List) -> Result<Self, Error> {
         let (name, location) = list.pop_identifier("function identifier")?;
+        let (doc, attributes) = parse_annotations(list)?;
+        let is_const = Function::pop_self(list)?;
 
         // Parse parameters
         let mut parameters = vec![];
         let mut parameter_def = list.pop_list("list for parameters")?;
         while parameter_def.is_empty() == false {
             let mut parameter_list = parameter_def.pop_list("parameter list")?;
-            let (param_type, loc) = parameter_list.pop_identifier("parameter type")?;
+            let (mut param_type, mut loc) = parameter_list.pop_identifier("parameter type")?;
+
+            // An optional mode comes before the type
+            let mode = ParameterMode::try_parse(&param_type);
+            if mode.is_some() {
+                (param_type, loc) = parameter_list.pop_identifier("parameter type")?;
+            }
+
             let (param_name, _) = parameter_list.pop_identifier("parameter name")?;
             let param_type: Type = Type::try_parse(&param_type, loc.clone())?;
 
+            if !parameter_list.is_empty() {
+                let n = parameter_list.pop_front("Unexpected values")?;
+                return Err(Error {
+                    message: "Expected end of list".into(),
+                    location: n.first_location(),
+                });
+            }
+
             let parameter = Parameter {
                 location: loc,
                 name: param_name,
                 type_: param_type,
+                mode,
             };
 
             parameters.push(parameter);
@@ -62,18 +202,34 @@ impl FromLisp for Function {
         let return_type = Type::try_parse(&return_type, loc.clone())?;
         let return_type = (loc, return_type);
 
-        if !list.is_empty() {
-            let n = list.pop_front("Unexpected values")?;
-            return Err(Error {
-                message: "Expected end of list".into(),
-                location: n.first_location(),
-            });
+        // Parse contracts, which come before the body
+        let mut requires = vec![];
+        let mut ensures = vec![];
+        while let Some(kind) = Contract::front_kind(list) {
+            let contracts = Contract::parse(list.pop_list("contract")?)?;
+            match kind {
+                ContractKind::Requires => requires.extend(contracts),
+                ContractKind::Ensures => ensures.extend(contracts),
+            }
+        }
+
+        // Parse body
+        let mut body = vec![];
+        while !list.is_empty() {
+            body.push(Expression::pop(list)?);
         }
         let function = Function {
             location,
             name,
             parameters,
             return_type,
+            is_const,
+            is_static: false,
+            body,
+            requires,
+            ensures,
+            doc,
+            attributes,
         };
 
         Ok(function)
@@ -170,6 +326,13 @@ mod tests {
             name: "life-meaning".into(),
             parameters: vec![],
             return_type: (Location::default(), Type::I64),
+            is_const: false,
+            is_static: false,
+            body: vec![],
+            requires: vec![],
+            ensures: vec![],
+            doc: None,
+            attributes: vec![],
         };
 
         assert_eq!(result, Ok(expected));
@@ -200,14 +363,23 @@ mod tests {
                     location: lisper::Location::default(),
                     name: "a".into(),
                     type_: Type::I64,
+                    mode: None,
                 },
                 Parameter {
                     location: lisper::Location::default(),
                     name: "b".into(),
                     type_: Type::I64,
+                    mode: None,
                 },
             ],
             return_type: (Location::default(), Type::I64),
+            is_const: false,
+            is_static: false,
+            body: vec![],
+            requires: vec![],
+            ensures: vec![],
+            doc: None,
+            attributes: vec![],
         };
 
         assert_eq!(result, Ok(expected));
@@ -284,4 +456,138 @@ mod tests {
         expected.sort();
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn parse_doc_returns_doc() {
+        let input = "(fn heal (doc \"Restores health\") ((i32 amount)) void)";
+        let result = parse_fn(input).unwrap();
+
+        assert_eq!(result.doc, Some("Restores health".to_string()));
+        assert_eq!(result.parameters.len(), 1);
+    }
+
+    #[test]
+    fn parse_self_const_returns_const() {
+        let input = "(fn collides? (doc \"Checks overlap\") (self const) ((Aabb other)) bool)";
+        let result = parse_fn(input).unwrap();
+
+        assert!(result.is_const);
+        assert_eq!(result.doc, Some("Checks overlap".to_string()));
+        assert_eq!(result.parameters.len(), 1);
+    }
+
+    #[test]
+    fn parse_self_not_const_returns_err() {
+        let input = "(fn collides? (self mutable) ((Aabb other)) bool)";
+        let expected = "Expected const after self, got 'mutable'".into();
+        assert_eq!(parse_fn(input), Err(expected));
+    }
+
+    #[test]
+    fn parse_parameter_modes_returns_modes() {
+        let input =
+            "(fn resolve ((in Aabb other) (mut Aabb target) (out i32 result) (i32 depth)) void)";
+        let result = parse_fn(input).unwrap();
+        let modes = result.parameters.iter().map(|p| p.mode).collect::<Vec<_>>();
+
+        assert_eq!(
+            modes,
+            vec![
+                Some(ParameterMode::In),
+                Some(ParameterMode::Mut),
+                Some(ParameterMode::Out),
+                None
+            ]
+        );
+        assert_eq!(result.parameters[0].type_, Type::Identifier("Aabb".into()));
+        assert_eq!(result.parameters[2].name, "result".to_string());
+    }
+
+    #[test]
+    fn parse_parameter_extra_values_returns_err() {
+        let input = "(fn resolve ((Aabb other extra)) void)";
+        let expected = "Expected end of list".into();
+        assert_eq!(parse_fn(input), Err(expected));
+    }
+
+    #[test]
+    fn signature_matches_compares_modes_and_const() {
+        let f = parse_fn("(fn hit (self const) ((in Aabb other)) bool)").unwrap();
+        let same = parse_fn("(fn hit (self const) ((in Aabb box)) bool)").unwrap();
+        let mutable = parse_fn("(fn hit ((in Aabb other)) bool)").unwrap();
+        let mode = parse_fn("(fn hit (self const) ((mut Aabb other)) bool)").unwrap();
+
+        assert!(f.signature_matches(&same));
+        assert!(!f.signature_matches(&mutable));
+        assert!(!f.signature_matches(&mode));
+    }
+
+    #[test]
+    fn from_lisp_static_returns_static() {
+        let list = parse("(static-fn make-default () Character)");
+        assert!(Function::can_try_static(&list));
+        assert!(!Function::can_try(&list));
+
+        let result = Function::from_lisp_static(list).unwrap();
+        assert!(result.is_static);
+        assert_eq!(result.name, "make-default".to_string());
+    }
+
+    #[test]
+    fn from_lisp_static_const_returns_err() {
+        let list = parse("(static-fn make-default (self const) () Character)");
+        let expected = "Static function 'make-default' can not be const".into();
+        assert_eq!(Function::from_lisp_static(list), Err(expected));
+    }
+
+    #[test]
+    fn from_lisp_with_body_returns_expressions() {
+        let input = "(fn double ((in i32 n)) i32 (let m (* n 2)) m)";
+        let result = parse_fn(input).unwrap();
+
+        assert_eq!(result.body.len(), 2);
+        assert_eq!(
+            result.body[1].kind,
+            crate::definition::expression::ExpressionKind::Variable("m".into())
+        );
+    }
+
+    #[test]
+    fn from_lisp_with_contracts_returns_conditions() {
+        let input = "(fn level-up ((in i32 level)) i32
+            (requires (> level 0) (< level 100))
+            (ensures (> result level))
+            (+ level 1))";
+        let result = parse_fn(input).unwrap();
+
+        assert_eq!(result.requires.len(), 2);
+        assert_eq!(result.requires[0].source, "(> level 0)");
+        assert_eq!(result.ensures.len(), 1);
+        assert_eq!(result.ensures[0].source, "(> result level)");
+        assert_eq!(result.body.len(), 1);
+    }
+
+    #[test]
+    fn from_lisp_contracts_without_body_returns_function() {
+        let input = "(fn heal ((mut i32 health)) void (ensures (> health 0)))";
+        let result = parse_fn(input).unwrap();
+
+        assert_eq!(result.requires.len(), 0);
+        assert_eq!(result.ensures.len(), 1);
+        assert_eq!(result.body.len(), 0);
+    }
+
+    #[test]
+    fn from_lisp_empty_contract_returns_err() {
+        let input = "(fn heal ((mut i32 health)) void (requires))";
+        let expected = "Expected condition after 'requires'".into();
+        assert_eq!(parse_fn(input), Err(expected));
+    }
+
+    #[test]
+    fn from_lisp_invalid_body_returns_err() {
+        let input = "(fn double ((in i32 n)) i32 (< n))";
+        let expected = "Operator '<' can not take 1 operands".into();
+        assert_eq!(parse_fn(input), Err(expected));
+    }
 }
@@ -1,13 +1,37 @@
 use crate::definition::type_::Type;
 
+/// How a parameter is passed, written before the type as `(in Aabb other)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParameterMode {
+    /// Read only.
+    In,
+    /// Read and modified by the function.
+    Mut,
+    /// Written by the function to return an extra result.
+    Out,
+}
+impl ParameterMode {
+    /// Returns the mode the identifier names, if any.
+    pub fn try_parse(identifier: &str) -> Option<ParameterMode> {
+        match identifier {
+            "in" => Some(ParameterMode::In),
+            "mut" => Some(ParameterMode::Mut),
+            "out" => Some(ParameterMode::Out),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Parameter {
     pub location: lisper::Location,
     pub name: String,
     pub type_: Type,
+    /// The declared passing mode. `None` keeps the target's default.
+    pub mode: Option<ParameterMode>,
 }
 impl PartialEq for Parameter {
     fn eq(&self, other: &Self) -> bool {
-        self.name == other.name && self.type_ == other.type_
+        self.name == other.name && self.type_ == other.type_ && self.mode == other.mode
     }
 }
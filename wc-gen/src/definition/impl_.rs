@@ -0,0 +1,81 @@
+use super::{type_::Type, FromLisp};
+use lisper::{Error, Location};
+
+/// Declares that a struct implements a trait, written as `(impl Trait Struct)`.
+#[derive(Debug, Clone)]
+pub struct Impl {
+    pub location: Location,
+    pub trait_name: String,
+    pub struct_name: String,
+}
+impl PartialEq for Impl {
+    fn eq(&self, other: &Self) -> bool {
+        self.trait_name == other.trait_name && self.struct_name == other.struct_name
+    }
+}
+
+impl FromLisp for Impl {
+    fn identifier() -> &'static str {
+        "impl"
+    }
+
+    fn get_related_types(&self) -> Vec<(Location, Type)> {
+        vec![]
+    }
+
+    fn parse_values(list: &mut lisper::List) -> Result<Self, Error> {
+        let (trait_name, location) = list.pop_identifier("trait name")?;
+        let (struct_name, _) = list.pop_identifier("struct name")?;
+
+        if !list.is_empty() {
+            let n = list.pop_front("Unexpected values")?;
+            return Err(Error {
+                message: "Expected end of list".into(),
+                location: n.first_location(),
+            });
+        }
+
+        Ok(Impl {
+            location,
+            trait_name,
+            struct_name,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> lisper::List {
+        lisper::parse_str(input).unwrap()[0].clone()
+    }
+
+    #[test]
+    fn from_lisp_returns_impl() {
+        let input = parse("(impl Collidable Aabb)");
+        let expected = Impl {
+            location: Location::default(),
+            trait_name: "Collidable".into(),
+            struct_name: "Aabb".into(),
+        };
+
+        assert_eq!(Impl::from_lisp(input), Ok(expected));
+    }
+
+    #[test]
+    fn from_lisp_no_struct_returns_err() {
+        let input = parse("(impl Collidable)");
+        let expected = Err("Expected struct name".into());
+
+        assert_eq!(Impl::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn from_lisp_extra_values_returns_err() {
+        let input = parse("(impl Collidable Aabb Circle)");
+        let expected = Err("Expected end of list".into());
+
+        assert_eq!(Impl::from_lisp(input), expected);
+    }
+}
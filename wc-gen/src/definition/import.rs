@@ -0,0 +1,98 @@
+use super::{literal::Literal, type_::Type, FromLisp};
+use lisper::{Error, Location};
+use std::path::PathBuf;
+
+/// An import of another definition file. The path is relative to the importing file.
+#[derive(Debug, Clone)]
+pub struct Import {
+    pub location: Location,
+    pub path: PathBuf,
+}
+impl PartialEq for Import {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path
+    }
+}
+
+impl FromLisp for Import {
+    fn identifier() -> &'static str {
+        "import"
+    }
+
+    fn get_related_types(&self) -> Vec<(Location, Type)> {
+        vec![]
+    }
+
+    fn parse_values(list: &mut lisper::List) -> Result<Self, Error> {
+        let (path, location) = Literal::pop(list, "import path")?;
+        let path = match path {
+            Literal::String(path) if !path.is_empty() => PathBuf::from(path),
+            path => {
+                return Err(Error {
+                    message: format!("Expected import path, got '{}'", path),
+                    location,
+                })
+            }
+        };
+
+        if !list.is_empty() {
+            let n = list.pop_front("Unexpected values")?;
+            return Err(Error {
+                message: "Expected end of list".into(),
+                location: n.first_location(),
+            });
+        }
+
+        Ok(Import { location, path })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> lisper::List {
+        lisper::parse_str(input).unwrap()[0].clone()
+    }
+
+    #[test]
+    fn can_try_identifier_returns_true() {
+        let input = parse("(import \"common/math.scm\")");
+        assert!(Import::can_try(&input));
+    }
+
+    #[test]
+    fn from_lisp_returns_path() {
+        let input = parse("(import \"common/math.scm\")");
+        let expected = Import {
+            location: Location::default(),
+            path: PathBuf::from("common/math.scm"),
+        };
+
+        assert_eq!(Import::from_lisp(input), Ok(expected));
+    }
+
+    #[test]
+    fn from_lisp_no_path_returns_err() {
+        let input = parse("(import)");
+        let expected = Err("Expected import path".into());
+
+        assert_eq!(Import::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn from_lisp_number_returns_err() {
+        let input = parse("(import 42)");
+        let expected = Err("Expected import path, got '42'".into());
+
+        assert_eq!(Import::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn from_lisp_extra_values_returns_err() {
+        let input = parse("(import \"a.scm\" \"b.scm\")");
+        let expected = Err("Expected end of list".into());
+
+        assert_eq!(Import::from_lisp(input), expected);
+    }
+}
@@ -0,0 +1,301 @@
+use super::type_::Type;
+use lisper::{Error, List, Location};
+
+/// A literal value written in a definition.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Bool(bool),
+    Int(i128),
+    Float(f64),
+    String(String),
+}
+
+impl std::fmt::Display for Literal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Literal::Bool(value) => write!(f, "{}", value),
+            Literal::Int(value) => write!(f, "{}", value),
+            Literal::Float(value) => write!(f, "{:?}", value),
+            Literal::String(value) => write!(f, "{:?}", value),
+        }
+    }
+}
+
+impl Literal {
+    /// Returns whether the front of the list could be a literal.
+    pub fn can_pop(list: &List) -> bool {
+        !list.is_empty() && !list.front_is_list() && !list.front_is_identifier()
+    }
+
+    /// Pops a literal from the front of the list.
+    pub fn pop(list: &mut List, expected: &str) -> Result<(Self, Location), Error> {
+        let can_pop = Self::can_pop(list);
+        let node = list.pop_front(expected)?;
+        let location = node.first_location();
+        if !can_pop {
+            return Err(Error {
+                message: format!("Expected {}", expected),
+                location,
+            });
+        }
+
+        let literal = Self::try_parse(&node.to_string(), location.clone())?;
+        Ok((literal, location))
+    }
+
+    /// Tries to parse a literal from its source representation.
+    pub fn try_parse(value: &str, location: Location) -> Result<Self, Error> {
+        let value = value.trim();
+        match value {
+            "true" => return Ok(Literal::Bool(true)),
+            "false" => return Ok(Literal::Bool(false)),
+            _ => {}
+        }
+
+        if let Ok(value) = value.parse::<i128>() {
+            return Ok(Literal::Int(value));
+        }
+
+        if let Ok(value) = value.parse::<f64>() {
+            if value.is_finite() {
+                return Ok(Literal::Float(value));
+            }
+        }
+
+        if Self::is_numeric(value) {
+            return Err(Error {
+                message: format!("Invalid number '{}'", value),
+                location,
+            });
+        }
+
+        if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+            return Ok(Literal::String(value[1..value.len() - 1].to_string()));
+        }
+
+        if value.is_empty() {
+            return Err(Error {
+                message: "Expected literal".into(),
+                location,
+            });
+        }
+
+        Ok(Literal::String(value.to_string()))
+    }
+
+    /// Ensures the literal can be stored in a value of the given type.
+    pub fn type_check(&self, ty: &Type, location: Location) -> Result<(), Error> {
+        let fits = match (self, ty) {
+            (Literal::Bool(_), Type::Bool) => true,
+            (Literal::String(_), Type::String) => true,
+            (Literal::Float(value), Type::Float) => {
+                self.fits_float(*value, ty, location.clone())?
+            }
+            (Literal::Int(value), Type::Float) => {
+                self.fits_float(*value as f64, ty, location.clone())?
+            }
+            (Literal::Int(value), ty) => match Self::int_range(ty) {
+                Some((min, max)) => {
+                    if *value < min || *value > max {
+                        return Err(Error {
+                            message: format!("Value {} does not fit in {}", value, ty),
+                            location,
+                        });
+                    }
+                    true
+                }
+                None => false,
+            },
+            _ => false,
+        };
+
+        if !fits {
+            return Err(Error {
+                message: format!("Expected {} value, got '{}'", ty, self),
+                location,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Returns whether the token starts like a number, such as `12`, `-3` or `.5`.
+    fn is_numeric(value: &str) -> bool {
+        let digits = value.trim_start_matches(['-', '+']).trim_start_matches('.');
+        digits.starts_with(|c: char| c.is_ascii_digit())
+    }
+
+    /// Ensures the value is within the range of a float.
+    fn fits_float(&self, value: f64, ty: &Type, location: Location) -> Result<bool, Error> {
+        if value.abs() > f32::MAX as f64 {
+            return Err(Error {
+                message: format!("Value {} does not fit in {}", self, ty),
+                location,
+            });
+        }
+        Ok(true)
+    }
+
+    /// Returns the inclusive range of values an integer type can hold.
+    pub fn int_range(ty: &Type) -> Option<(i128, i128)> {
+        match ty {
+            Type::I8 => Some((i8::MIN as i128, i8::MAX as i128)),
+            Type::I16 => Some((i16::MIN as i128, i16::MAX as i128)),
+            Type::I32 => Some((i32::MIN as i128, i32::MAX as i128)),
+            Type::I64 => Some((i64::MIN as i128, i64::MAX as i128)),
+            Type::U8 => Some((0, u8::MAX as i128)),
+            Type::U16 => Some((0, u16::MAX as i128)),
+            Type::U32 => Some((0, u32::MAX as i128)),
+            Type::U64 => Some((0, u64::MAX as i128)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> lisper::List {
+        lisper::parse_str(input).unwrap()[0].clone()
+    }
+
+    #[test]
+    fn try_parse_bool() {
+        let location = Location::default();
+        assert_eq!(
+            Literal::try_parse("true", location.clone()),
+            Ok(Literal::Bool(true))
+        );
+        assert_eq!(
+            Literal::try_parse("false", location),
+            Ok(Literal::Bool(false))
+        );
+    }
+
+    #[test]
+    fn try_parse_numbers() {
+        let location = Location::default();
+        assert_eq!(
+            Literal::try_parse("100", location.clone()),
+            Ok(Literal::Int(100))
+        );
+        assert_eq!(
+            Literal::try_parse("-3", location.clone()),
+            Ok(Literal::Int(-3))
+        );
+        assert_eq!(Literal::try_parse("1.5", location), Ok(Literal::Float(1.5)));
+    }
+
+    #[test]
+    fn try_parse_malformed_number_returns_err() {
+        let location = Location::default();
+        assert_eq!(
+            Literal::try_parse("1.2.3", location.clone()),
+            Err("Invalid number '1.2.3'".into())
+        );
+        assert_eq!(
+            Literal::try_parse("-12abc", location.clone()),
+            Err("Invalid number '-12abc'".into())
+        );
+        assert_eq!(
+            Literal::try_parse("1e999", location),
+            Err("Invalid number '1e999'".into())
+        );
+    }
+
+    #[test]
+    fn try_parse_string() {
+        let location = Location::default();
+        assert_eq!(
+            Literal::try_parse("\"Unnamed\"", location),
+            Ok(Literal::String("Unnamed".into()))
+        );
+    }
+
+    #[test]
+    fn pop_returns_literal() {
+        let mut list = parse("(100 \"Unnamed\" true)");
+
+        assert_eq!(
+            Literal::pop(&mut list, "value").map(|(l, _)| l),
+            Ok(Literal::Int(100))
+        );
+        assert_eq!(
+            Literal::pop(&mut list, "value").map(|(l, _)| l),
+            Ok(Literal::String("Unnamed".into()))
+        );
+        assert_eq!(
+            Literal::pop(&mut list, "value").map(|(l, _)| l),
+            Ok(Literal::Bool(true))
+        );
+    }
+
+    #[test]
+    fn pop_identifier_returns_err() {
+        let mut list = parse("(health)");
+        let expected = Err("Expected value".into());
+        assert_eq!(Literal::pop(&mut list, "value"), expected);
+    }
+
+    #[test]
+    fn type_check_matching_types_returns_ok() {
+        let location = Location::default();
+        assert_eq!(
+            Literal::Int(100).type_check(&Type::I32, location.clone()),
+            Ok(())
+        );
+        assert_eq!(
+            Literal::Int(1).type_check(&Type::Float, location.clone()),
+            Ok(())
+        );
+        assert_eq!(
+            Literal::Float(1.5).type_check(&Type::Float, location.clone()),
+            Ok(())
+        );
+        assert_eq!(
+            Literal::Bool(true).type_check(&Type::Bool, location.clone()),
+            Ok(())
+        );
+        assert_eq!(
+            Literal::String("a".into()).type_check(&Type::String, location),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn type_check_out_of_range_returns_err() {
+        let expected = Err("Value 256 does not fit in u8".into());
+        assert_eq!(
+            Literal::Int(256).type_check(&Type::U8, Location::default()),
+            expected
+        );
+
+        let expected = Err("Value -1 does not fit in u32".into());
+        assert_eq!(
+            Literal::Int(-1).type_check(&Type::U32, Location::default()),
+            expected
+        );
+
+        let expected = Err("Value 1e300 does not fit in f32".into());
+        assert_eq!(
+            Literal::Float(1e300).type_check(&Type::Float, Location::default()),
+            expected
+        );
+    }
+
+    #[test]
+    fn type_check_mismatch_returns_err() {
+        let expected = Err("Expected i32 value, got '\"a\"'".into());
+        assert_eq!(
+            Literal::String("a".into()).type_check(&Type::I32, Location::default()),
+            expected
+        );
+
+        let expected = Err("Expected bool value, got '1'".into());
+        assert_eq!(
+            Literal::Int(1).type_check(&Type::Bool, Location::default()),
+            expected
+        );
+    }
+}
@@ -1,11 +1,27 @@
-use lisper::{Error, Location};
+use attribute::Attribute;
+use doc::Doc;
+use lisper::{Error, List, Location};
 use type_::Type;
 
+pub mod alias;
+pub mod attribute;
+pub mod constant;
+pub mod constraint;
+pub mod doc;
+pub mod embed;
 pub mod enum_;
+pub mod expression;
 pub mod field;
+pub mod flags;
 pub mod function;
+pub mod impl_;
+pub mod import;
+pub mod literal;
+pub mod module;
+pub mod newtype;
 pub mod output;
 pub mod struct_;
+pub mod trait_;
 pub mod type_;
 
 pub trait FromLisp: Sized {
@@ -46,4 +62,91 @@ pub trait FromLisp: Sized {
         }
         false
     }
+
+    /// Pops the type from the front of the list if the front is a list starting with its identifier.
+    fn try_pop(list: &mut lisper::List) -> Result<Option<Self>, Error> {
+        if !list.front_is_list() {
+            return Ok(None);
+        }
+
+        let mut remaining = list.clone();
+        let front = remaining.pop_list(Self::identifier())?;
+        if !Self::can_try(&front) {
+            return Ok(None);
+        }
+
+        *list = remaining;
+        Ok(Some(Self::from_lisp(front)?))
+    }
+}
+
+/// Parses the name of a definition, which is either `Name` or `(Name A B)` for generic definitions.
+/// Returns the name, its location and the type parameters.
+pub fn parse_definition_name(
+    list: &mut lisper::List,
+    expected: &str,
+) -> Result<(String, Location, Vec<String>), Error> {
+    if !list.front_is_list() {
+        let (name, location) = list.pop_identifier(expected)?;
+        return Ok((name, location, vec![]));
+    }
+
+    let mut name_list = list.pop_list(expected)?;
+    let (name, location) = name_list.pop_identifier(expected)?;
+
+    let mut parameters: Vec<String> = vec![];
+    while !name_list.is_empty() {
+        let (parameter, loc) = name_list.pop_identifier("type parameter")?;
+        if Type::try_parse(&parameter, loc.clone())? != Type::Identifier(parameter.clone()) {
+            return Err(Error {
+                message: format!("Invalid type parameter '{}'", parameter),
+                location: loc,
+            });
+        }
+
+        if parameters.contains(&parameter) {
+            return Err(Error {
+                message: format!("Duplicate type parameter '{}'", parameter),
+                location: loc,
+            });
+        }
+        parameters.push(parameter);
+    }
+
+    if parameters.is_empty() {
+        return Err(Error {
+            message: "Expected type parameter".into(),
+            location,
+        });
+    }
+
+    Ok((name, location, parameters))
+}
+
+/// Pops the docs and attributes that follow the name of a definition, in any order.
+pub fn parse_annotations(list: &mut List) -> Result<(Option<String>, Vec<Attribute>), Error> {
+    let mut doc = None;
+    let mut attributes = vec![];
+    loop {
+        if let Some(attribute) = Attribute::try_pop(list)? {
+            attributes.push(attribute);
+            continue;
+        }
+
+        let location = list.location();
+        match Doc::pop(list)? {
+            Some(text) => {
+                if doc.is_some() {
+                    return Err(Error {
+                        message: "Duplicate doc".into(),
+                        location,
+                    });
+                }
+                doc = Some(text);
+            }
+            None => break,
+        }
+    }
+
+    Ok((doc, attributes))
 }
@@ -0,0 +1,108 @@
+use super::{type_::Type, FromLisp};
+use lisper::{Error, List, Location};
+
+/// A module that scopes the definitions inside it. Definitions are referenced as `module/Name` outside of it.
+#[derive(Clone)]
+pub struct Module {
+    pub name: String,
+    pub definitions: Vec<List>,
+}
+
+impl Module {
+    /// Returns the name of a definition declared in the given modules, such as `combat/Move`.
+    pub fn qualify(modules: &[String], name: &str) -> String {
+        let mut parts = modules.to_vec();
+        parts.push(name.to_string());
+        parts.join("/")
+    }
+}
+
+impl FromLisp for Module {
+    fn identifier() -> &'static str {
+        "module"
+    }
+
+    fn get_related_types(&self) -> Vec<(Location, Type)> {
+        vec![]
+    }
+
+    fn parse_values(list: &mut List) -> Result<Self, Error> {
+        let (name, location) = list.pop_identifier("module name")?;
+        if name.contains('/')
+            || Type::try_parse(&name, location.clone())? != Type::Identifier(name.clone())
+        {
+            return Err(Error {
+                message: format!("Invalid module name '{}'", name),
+                location,
+            });
+        }
+
+        let mut definitions = vec![];
+        while !list.is_empty() {
+            definitions.push(list.pop_list("definition")?);
+        }
+
+        Ok(Module { name, definitions })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> List {
+        lisper::parse_str(input).unwrap()[0].clone()
+    }
+
+    #[test]
+    fn can_try_not_identifier_returns_false() {
+        let input = parse("(struct combat)");
+        assert!(!Module::can_try(&input));
+    }
+
+    #[test]
+    fn can_try_identifier_returns_true() {
+        let input = parse("(module combat)");
+        assert!(Module::can_try(&input));
+    }
+
+    #[test]
+    fn from_lisp_returns_definitions() {
+        let input = parse("(module combat (struct Move) (enum Kind Melee Ranged))");
+        let module = Module::from_lisp(input).ok().unwrap();
+
+        assert_eq!(module.name, "combat".to_string());
+        assert_eq!(module.definitions.len(), 2);
+    }
+
+    #[test]
+    fn from_lisp_no_name_returns_err() {
+        let input = parse("(module)");
+        let expected = Some("Expected module name".into());
+
+        assert_eq!(Module::from_lisp(input).err(), expected);
+    }
+
+    #[test]
+    fn from_lisp_qualified_name_returns_err() {
+        let input = parse("(module combat/moves)");
+        let expected = Some("Invalid module name 'combat/moves'".into());
+
+        assert_eq!(Module::from_lisp(input).err(), expected);
+    }
+
+    #[test]
+    fn from_lisp_not_list_returns_err() {
+        let input = parse("(module combat Move)");
+        let expected = Some("Expected definition".into());
+
+        assert_eq!(Module::from_lisp(input).err(), expected);
+    }
+
+    #[test]
+    fn qualify_joins_modules() {
+        let modules = vec!["combat".to_string(), "moves".to_string()];
+        assert_eq!(Module::qualify(&modules, "Move"), "combat/moves/Move");
+        assert_eq!(Module::qualify(&[], "Move"), "Move");
+    }
+}
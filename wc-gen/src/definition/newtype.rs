@@ -0,0 +1,126 @@
+use super::{type_::Type, FromLisp};
+use lisper::{Error, Location};
+
+/// A distinct type that wraps another type. Unlike an alias, it can not be used in place of the wrapped type.
+#[derive(Debug, Clone)]
+pub struct Newtype {
+    pub location: Location,
+    pub name: String,
+    pub type_: (Location, Type),
+}
+impl PartialEq for Newtype {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.type_.1 == other.type_.1
+    }
+}
+
+impl FromLisp for Newtype {
+    fn identifier() -> &'static str {
+        "newtype"
+    }
+
+    fn get_related_types(&self) -> Vec<(Location, Type)> {
+        vec![self.type_.clone()]
+    }
+
+    fn parse_values(list: &mut lisper::List) -> Result<Self, Error> {
+        let (name, location) = list.pop_identifier("newtype name")?;
+
+        let (ty, loc) = list.pop_identifier("wrapped type")?;
+        let ty = Type::try_parse(&ty, loc.clone())?;
+        match ty {
+            Type::Void => {
+                return Err(Error {
+                    message: "void can not be wrapped".into(),
+                    location: loc,
+                })
+            }
+            Type::Optional(_) => {
+                return Err(Error {
+                    message: "Optionals can not be wrapped".into(),
+                    location: loc,
+                })
+            }
+            _ => {}
+        }
+
+        if !list.is_empty() {
+            let n = list.pop_front("Unexpected values")?;
+            return Err(Error {
+                message: "Expected end of list".into(),
+                location: n.first_location(),
+            });
+        }
+
+        Ok(Newtype {
+            location,
+            name,
+            type_: (loc, ty),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> lisper::List {
+        lisper::parse_str(input).unwrap()[0].clone()
+    }
+
+    #[test]
+    fn can_try_not_identifier_returns_false() {
+        let input = parse("(alias Gold i32)");
+        assert!(!Newtype::can_try(&input));
+    }
+
+    #[test]
+    fn can_try_identifier_returns_true() {
+        let input = parse("(newtype Gold i32)");
+        assert!(Newtype::can_try(&input));
+    }
+
+    #[test]
+    fn from_lisp_returns_newtype() {
+        let input = parse("(newtype Gold i32)");
+        let expected = Newtype {
+            location: Location::default(),
+            name: "Gold".to_string(),
+            type_: (Location::default(), Type::I32),
+        };
+
+        assert_eq!(Newtype::from_lisp(input), Ok(expected));
+    }
+
+    #[test]
+    fn from_lisp_no_type_returns_err() {
+        let input = parse("(newtype Gold)");
+        let expected = Err("Expected wrapped type".into());
+
+        assert_eq!(Newtype::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn from_lisp_void_returns_err() {
+        let input = parse("(newtype Nothing void)");
+        let expected = Err("void can not be wrapped".into());
+
+        assert_eq!(Newtype::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn from_lisp_optional_returns_err() {
+        let input = parse("(newtype MaybeGold i32?)");
+        let expected = Err("Optionals can not be wrapped".into());
+
+        assert_eq!(Newtype::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn from_lisp_extra_values_returns_err() {
+        let input = parse("(newtype Gold i32 i64)");
+        let expected = Err("Expected end of list".into());
+
+        assert_eq!(Newtype::from_lisp(input), expected);
+    }
+}
@@ -1,4 +1,4 @@
-use super::FromLisp;
+use super::{attribute::Attribute, FromLisp};
 use lisper::Location;
 use std::path::PathBuf;
 
@@ -13,10 +13,13 @@ pub struct Output {
     pub location: Location,
     pub folder: PathBuf,
     pub language: TargetLanguage,
+    pub attributes: Vec<Attribute>,
 }
 impl PartialEq for Output {
     fn eq(&self, other: &Self) -> bool {
-        self.folder == other.folder && self.language == other.language
+        self.folder == other.folder
+            && self.language == other.language
+            && self.attributes == other.attributes
     }
 }
 
@@ -44,10 +47,17 @@ impl FromLisp for Output {
         let (folder, location) = list.pop_identifier("folder")?;
         let folder = PathBuf::from(folder);
 
+        // Attributes may follow the folder
+        let mut attributes = vec![];
+        while let Some(attribute) = Attribute::try_pop(list)? {
+            attributes.push(attribute);
+        }
+
         Ok(Output {
             location,
             folder,
             language,
+            attributes,
         })
     }
 }
@@ -101,9 +111,20 @@ mod tests {
             location: Location::default(),
             folder: PathBuf::from("../output"),
             language: TargetLanguage::Cpp,
+            attributes: vec![],
         };
 
         assert_eq!(value, expected);
         assert_eq!(value.get_related_types(), vec![]);
     }
+
+    #[test]
+    fn outputs_attributes() {
+        let input = "(output cpp ../output (@ cpp-namespace game))";
+        let list = parse(input);
+        let value = Output::from_lisp(list).unwrap();
+
+        assert_eq!(value.attributes.len(), 1);
+        assert_eq!(value.attributes[0].name, "cpp-namespace".to_string());
+    }
 }
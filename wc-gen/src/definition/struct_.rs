@@ -1,4 +1,7 @@
-use super::{field::Field, function::Function, type_::Type, FromLisp};
+use super::{
+    attribute::Attribute, embed::Embed, field::Field, function::Function, parse_annotations,
+    parse_definition_name, type_::Type, FromLisp,
+};
 use lisper::{Error, Location};
 use std::collections::HashMap;
 
@@ -7,26 +10,57 @@ use std::collections::HashMap;
 pub struct Struct {
     pub location: lisper::Location,
     pub name: String,
+    /// The type parameters of a generic struct. Empty if the struct is not generic.
+    pub type_parameters: Vec<String>,
+    /// The structs whose fields are flattened into this struct.
+    pub embeds: Vec<Embed>,
     pub fields: HashMap<String, Field>,
     pub functions: HashMap<String, Function>,
+    pub doc: Option<String>,
+    pub attributes: Vec<Attribute>,
 }
 impl PartialEq for Struct {
     fn eq(&self, other: &Self) -> bool {
-        self.name == other.name && self.fields == other.fields
+        self.name == other.name
+            && self.type_parameters == other.type_parameters
+            && self.embeds == other.embeds
+            && self.fields == other.fields
+            && self.doc == other.doc
+            && self.attributes == other.attributes
     }
 }
 
 impl Struct {
-    /// Returns a list of all the other structs referenced by the struct.
-    pub fn get_referenced_structs(&self) -> Vec<String> {
+    /// Returns the types that are stored directly in the struct and must be constructed with it.
+    /// Lists and optionals are excluded as they are allocated on demand.
+    pub fn get_direct_referenced_types(&self) -> Vec<(Location, Type)> {
         self.fields
             .values()
-            .filter_map(|field| match field.type_.inner_type() {
-                Type::Identifier(name) => Some(name),
+            .filter_map(|field| match &field.type_ {
+                Type::Identifier(_) | Type::Generic(_, _) | Type::Parameter(_) => {
+                    Some((field.location.clone(), field.type_.clone()))
+                }
                 _ => None,
             })
             .collect()
     }
+
+    pub fn is_generic(&self) -> bool {
+        !self.type_parameters.is_empty()
+    }
+
+    /// Replaces the types of the fields and functions with the result of `f`.
+    pub fn map_types(&mut self, f: &dyn Fn(&Type) -> Type) {
+        for embed in self.embeds.iter_mut() {
+            embed.type_ = f(&embed.type_);
+        }
+        for field in self.fields.values_mut() {
+            field.type_ = f(&field.type_);
+        }
+        for function in self.functions.values_mut() {
+            function.map_types(f);
+        }
+    }
 }
 
 impl FromLisp for Struct {
@@ -35,10 +69,23 @@ impl FromLisp for Struct {
     }
 
     fn parse_values(list: &mut lisper::List) -> Result<Self, Error> {
-        let (name, location) = list.pop_identifier("Expected name")?;
+        let (name, location, type_parameters) = parse_definition_name(list, "Expected name")?;
+        let (doc, attributes) = parse_annotations(list)?;
         let mut fields = HashMap::new();
         let mut functions = HashMap::new();
 
+        // Parse embedded structs
+        let mut embeds: Vec<Embed> = vec![];
+        while let Some(embed) = Embed::try_pop(list)? {
+            if embeds.contains(&embed) {
+                return Err(Error {
+                    message: format!("Duplicate embed '{}'", embed.type_),
+                    location: embed.location,
+                });
+            }
+            embeds.push(embed);
+        }
+
         // Parse fields
         if !list.is_empty() {
             let mut list = list.pop_list("fields")?;
@@ -58,8 +105,12 @@ impl FromLisp for Struct {
         // Parse functions
         while !list.is_empty() {
             let function_list = list.pop_list("functions")?;
-            if Function::can_try(&function_list) {
-                let f = Function::from_lisp(function_list)?;
+            if Function::can_try(&function_list) || Function::can_try_static(&function_list) {
+                let f = if Function::can_try_static(&function_list) {
+                    Function::from_lisp_static(function_list)?
+                } else {
+                    Function::from_lisp(function_list)?
+                };
                 let location = f.location.clone();
                 let name = f.name.clone();
                 match functions.insert(f.name.clone(), f) {
@@ -92,13 +143,20 @@ impl FromLisp for Struct {
         Ok(Struct {
             location,
             name,
+            type_parameters,
+            embeds,
             fields,
             functions,
+            doc,
+            attributes,
         })
     }
 
     fn get_related_types(&self) -> Vec<(Location, Type)> {
         let mut types = vec![];
+        self.embeds
+            .iter()
+            .for_each(|embed| types.append(&mut embed.get_related_types()));
         self.fields
             .values()
             .for_each(|field| types.push((field.location.clone(), field.type_.clone())));
@@ -121,7 +179,9 @@ mod tests {
 
     use lisper::Location;
 
-    use crate::definition::function::Parameter;
+    use crate::definition::{
+        constraint::ConstraintKind, expression::Expression, function::Parameter, literal::Literal,
+    };
 
     use super::*;
 
@@ -161,8 +221,12 @@ mod tests {
         let expected = Struct {
             location: Location::default(),
             name: "foo".to_string(),
+            type_parameters: vec![],
+            embeds: vec![],
             fields: HashMap::new(),
             functions: HashMap::new(),
+            doc: None,
+            attributes: vec![],
         };
 
         assert_eq!(Struct::from_lisp(input), Ok(expected));
@@ -185,6 +249,8 @@ mod tests {
         let expected = Struct {
             location: Location::default(),
             name: "Aabb".to_string(),
+            type_parameters: vec![],
+            embeds: vec![],
             fields: vec![
                 (
                     "x-min".to_string(),
@@ -192,6 +258,11 @@ mod tests {
                         location: Location::default(),
                         name: "x-min".to_string(),
                         type_: Type::I32,
+                        default: None,
+                        default_expression: None,
+                        constraints: vec![],
+                        doc: None,
+                        attributes: vec![],
                     },
                 ),
                 (
@@ -200,6 +271,11 @@ mod tests {
                         location: Location::default(),
                         name: "y-min".to_string(),
                         type_: Type::I32,
+                        default: None,
+                        default_expression: None,
+                        constraints: vec![],
+                        doc: None,
+                        attributes: vec![],
                     },
                 ),
                 (
@@ -208,6 +284,11 @@ mod tests {
                         location: Location::default(),
                         name: "x-max".to_string(),
                         type_: Type::I32,
+                        default: None,
+                        default_expression: None,
+                        constraints: vec![],
+                        doc: None,
+                        attributes: vec![],
                     },
                 ),
                 (
@@ -216,6 +297,11 @@ mod tests {
                         location: Location::default(),
                         name: "y-max".to_string(),
                         type_: Type::I32,
+                        default: None,
+                        default_expression: None,
+                        constraints: vec![],
+                        doc: None,
+                        attributes: vec![],
                     },
                 ),
             ]
@@ -231,13 +317,23 @@ mod tests {
                         location: Location::default(),
                         name: "other".to_string(),
                         type_: Type::Identifier("Aabb".to_string()),
+                        mode: None,
                     }],
                     return_type: (Location::default(), Type::Bool),
+                    is_const: false,
+                    is_static: false,
+                    body: vec![],
+                    requires: vec![],
+                    ensures: vec![],
+                    doc: None,
+                    attributes: vec![],
                 },
             )]
             .iter()
             .cloned()
             .collect(),
+            doc: None,
+            attributes: vec![],
         };
 
         assert_eq!(actual, expected);
@@ -261,6 +357,8 @@ mod tests {
         let expected = Struct {
             location: Location::default(),
             name: "Aabb".to_string(),
+            type_parameters: vec![],
+            embeds: vec![],
             fields: vec![
                 (
                     "x-min".to_string(),
@@ -268,6 +366,11 @@ mod tests {
                         location: Location::default(),
                         name: "x-min".to_string(),
                         type_: Type::I32,
+                        default: None,
+                        default_expression: None,
+                        constraints: vec![],
+                        doc: None,
+                        attributes: vec![],
                     },
                 ),
                 (
@@ -276,6 +379,11 @@ mod tests {
                         location: Location::default(),
                         name: "y-min".to_string(),
                         type_: Type::I32,
+                        default: None,
+                        default_expression: None,
+                        constraints: vec![],
+                        doc: None,
+                        attributes: vec![],
                     },
                 ),
                 (
@@ -284,6 +392,11 @@ mod tests {
                         location: Location::default(),
                         name: "x-max".to_string(),
                         type_: Type::I32,
+                        default: None,
+                        default_expression: None,
+                        constraints: vec![],
+                        doc: None,
+                        attributes: vec![],
                     },
                 ),
                 (
@@ -292,6 +405,11 @@ mod tests {
                         location: Location::default(),
                         name: "y-max".to_string(),
                         type_: Type::I32,
+                        default: None,
+                        default_expression: None,
+                        constraints: vec![],
+                        doc: None,
+                        attributes: vec![],
                     },
                 ),
             ]
@@ -306,6 +424,13 @@ mod tests {
                         name: "print".to_string(),
                         parameters: vec![],
                         return_type: (Location::default(), Type::Void),
+                        is_const: false,
+                        is_static: false,
+                        body: vec![],
+                        requires: vec![],
+                        ensures: vec![],
+                        doc: None,
+                        attributes: vec![],
                     },
                 ),
                 (
@@ -317,14 +442,24 @@ mod tests {
                             location: Location::default(),
                             name: "other".to_string(),
                             type_: Type::Identifier("Aabb".to_string()),
+                            mode: None,
                         }],
                         return_type: (Location::default(), Type::Bool),
+                        is_const: false,
+                        is_static: false,
+                        body: vec![],
+                        requires: vec![],
+                        ensures: vec![],
+                        doc: None,
+                        attributes: vec![],
                     },
                 ),
             ]
             .iter()
             .cloned()
             .collect(),
+            doc: None,
+            attributes: vec![],
         };
 
         assert_eq!(actual, expected);
@@ -386,13 +521,22 @@ mod tests {
                 location: Location::default(),
                 name: "bar".to_string(),
                 type_: Type::I64,
+                default: None,
+                default_expression: None,
+                constraints: vec![],
+                doc: None,
+                attributes: vec![],
             },
         );
         let expected = Struct {
             location: Location::default(),
             name: "foo".to_string(),
+            type_parameters: vec![],
+            embeds: vec![],
             fields,
             functions: HashMap::new(),
+            doc: None,
+            attributes: vec![],
         };
 
         assert_eq!(Struct::from_lisp(input), Ok(expected));
@@ -416,6 +560,11 @@ mod tests {
                 location: Location::default(),
                 name: "bar".to_string(),
                 type_: Type::I64,
+                default: None,
+                default_expression: None,
+                constraints: vec![],
+                doc: None,
+                attributes: vec![],
             },
         );
         fields.insert(
@@ -424,6 +573,11 @@ mod tests {
                 location: Location::default(),
                 name: "baz".to_string(),
                 type_: Type::Bool,
+                default: None,
+                default_expression: None,
+                constraints: vec![],
+                doc: None,
+                attributes: vec![],
             },
         );
         fields.insert(
@@ -432,13 +586,22 @@ mod tests {
                 location: Location::default(),
                 name: "qux".to_string(),
                 type_: Type::String,
+                default: None,
+                default_expression: None,
+                constraints: vec![],
+                doc: None,
+                attributes: vec![],
             },
         );
         let expected = Struct {
             location: Location::default(),
             name: "foo".to_string(),
+            type_parameters: vec![],
+            embeds: vec![],
             fields,
             functions: HashMap::new(),
+            doc: None,
+            attributes: vec![],
         };
 
         assert_eq!(Struct::from_lisp(input), Ok(expected));
@@ -530,4 +693,364 @@ mod tests {
 
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn get_direct_referenced_types_skips_lists_and_optionals() {
+        let input = "(struct Skill 
+            (fields 
+                (i64 x) 
+                (Point point) 
+                (Point[] points)
+                (Skill? parent)
+                (Skill[] prerequisites))
+            )";
+        let list = parse(input);
+        let value = Struct::from_lisp(list).unwrap();
+
+        let result = value
+            .get_direct_referenced_types()
+            .iter()
+            .map(|(_, name)| name.clone())
+            .collect::<Vec<_>>();
+
+        assert_eq!(result, vec![Type::Identifier("Point".to_string())]);
+    }
+
+    #[test]
+    fn from_list_parses_default_values() {
+        let input = parse(
+            "(struct Character 
+        (fields 
+            (i32 health 100)
+            (string name \"Unnamed\")
+            (i32 level)))",
+        );
+        let value = Struct::from_lisp(input).unwrap();
+
+        assert_eq!(
+            value.fields["health"]
+                .default
+                .as_ref()
+                .map(|(_, d)| d.clone()),
+            Some(Literal::Int(100))
+        );
+        assert_eq!(
+            value.fields["name"]
+                .default
+                .as_ref()
+                .map(|(_, d)| d.clone()),
+            Some(Literal::String("Unnamed".into()))
+        );
+        assert_eq!(value.fields["level"].default, None);
+    }
+
+    #[test]
+    fn from_list_parses_default_expressions() {
+        let input = parse(
+            "(struct Character 
+        (fields 
+            (i32 health (* MAX-HEALTH 2) (range 1 1000))
+            (i32 max-health MAX-HEALTH)))",
+        );
+        let value = Struct::from_lisp(input).unwrap();
+        let expression = |input: &str| Expression::pop(&mut parse(input)).unwrap();
+
+        assert_eq!(
+            value.fields["health"].default_expression,
+            Some(expression("((* MAX-HEALTH 2))"))
+        );
+        assert_eq!(value.fields["health"].constraints.len(), 1);
+        assert_eq!(
+            value.fields["max-health"].default_expression,
+            Some(expression("(MAX-HEALTH)"))
+        );
+    }
+
+    #[test]
+    fn from_list_default_wrong_type_returns_err() {
+        let input = parse(
+            "(struct Character 
+        (fields (bool alive 100)))",
+        );
+        let expected = Err("Expected bool value, got '100'".into());
+
+        assert_eq!(Struct::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn from_list_default_out_of_range_returns_err() {
+        let input = parse(
+            "(struct Character 
+        (fields (u8 level 300)))",
+        );
+        let expected = Err("Value 300 does not fit in u8".into());
+
+        assert_eq!(Struct::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn from_list_default_on_list_returns_err() {
+        let input = parse(
+            "(struct Character 
+        (fields (i32[] levels 1)))",
+        );
+        let expected = Err("Expected i32[] value, got '1'".into());
+
+        assert_eq!(Struct::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn from_list_field_extra_values_returns_err() {
+        let input = parse(
+            "(struct Character 
+        (fields (i32 health 100 200)))",
+        );
+        let expected = Err("Expected end of list".into());
+
+        assert_eq!(Struct::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn from_list_parses_constraints() {
+        let input = parse(
+            "(struct Character 
+        (fields 
+            (i32 level 1 (range 1 100))
+            (string name (max-len 16))
+            (i32[] moves (max-count 4))))",
+        );
+        let value = Struct::from_lisp(input).unwrap();
+
+        let kinds = |name: &str| {
+            value.fields[name]
+                .constraints
+                .iter()
+                .map(|c| c.kind.clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            kinds("level"),
+            vec![ConstraintKind::Range {
+                min: Literal::Int(1),
+                max: Literal::Int(100)
+            }]
+        );
+        assert_eq!(kinds("name"), vec![ConstraintKind::MaxLen(16)]);
+        assert_eq!(kinds("moves"), vec![ConstraintKind::MaxCount(4)]);
+    }
+
+    #[test]
+    fn from_list_constraint_wrong_type_returns_err() {
+        let input = parse(
+            "(struct Character 
+        (fields (i32 level (max-count 4))))",
+        );
+        let expected = Err("max-count constraint requires a list, got 'i32'".into());
+
+        assert_eq!(Struct::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn from_list_unknown_field_option_returns_err() {
+        let input = parse(
+            "(struct Character 
+        (fields (i32 level (between 1 4))))",
+        );
+        let expected = Err("Unknown option '(between 1 4)'".into());
+
+        assert_eq!(Struct::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn from_lisp_generic_returns_type_parameters() {
+        let input = parse("(struct (Pair A B) (fields (A first) (B second)))");
+        let expected = Struct {
+            location: Location::default(),
+            name: "Pair".to_string(),
+            type_parameters: vec!["A".to_string(), "B".to_string()],
+            embeds: vec![],
+            fields: vec![
+                (
+                    "first".to_string(),
+                    Field {
+                        location: Location::default(),
+                        name: "first".to_string(),
+                        type_: Type::Identifier("A".to_string()),
+                        default: None,
+                        default_expression: None,
+                        constraints: vec![],
+                        doc: None,
+                        attributes: vec![],
+                    },
+                ),
+                (
+                    "second".to_string(),
+                    Field {
+                        location: Location::default(),
+                        name: "second".to_string(),
+                        type_: Type::Identifier("B".to_string()),
+                        default: None,
+                        default_expression: None,
+                        constraints: vec![],
+                        doc: None,
+                        attributes: vec![],
+                    },
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            functions: HashMap::new(),
+            doc: None,
+            attributes: vec![],
+        };
+
+        assert_eq!(Struct::from_lisp(input), Ok(expected));
+    }
+
+    #[test]
+    fn from_lisp_generic_duplicate_parameter_returns_err() {
+        let input = parse("(struct (Pair A A) (fields (A first)))");
+        let expected = Err("Duplicate type parameter 'A'".into());
+
+        assert_eq!(Struct::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn from_lisp_generic_without_parameters_returns_err() {
+        let input = parse("(struct (Pair) (fields (i32 first)))");
+        let expected = Err("Expected type parameter".into());
+
+        assert_eq!(Struct::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn from_lisp_generic_invalid_parameter_returns_err() {
+        let input = parse("(struct (Pair A[]) (fields (i32 first)))");
+        let expected = Err("Invalid type parameter 'A[]'".into());
+
+        assert_eq!(Struct::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn from_list_parses_docs() {
+        let input = parse(
+            "(struct Character (doc \"A playable character\")
+        (fields
+            (i32 exp 0 (doc \"Experience towards the next level\") (range 0 1000))
+            (i32 level))
+        (fn level-up (doc \"Raises the level\") () void))",
+        );
+        let value = Struct::from_lisp(input).unwrap();
+
+        assert_eq!(value.doc, Some("A playable character".to_string()));
+        assert_eq!(
+            value.fields["exp"].doc,
+            Some("Experience towards the next level".to_string())
+        );
+        assert_eq!(value.fields["exp"].constraints.len(), 1);
+        assert_eq!(value.fields["level"].doc, None);
+        assert_eq!(
+            value.functions["level-up"].doc,
+            Some("Raises the level".to_string())
+        );
+    }
+
+    #[test]
+    fn from_list_field_duplicate_doc_returns_err() {
+        let input = parse("(struct Character (fields (i32 exp (doc \"a\") (doc \"b\"))))");
+        let expected = Err("Duplicate doc for property 'exp'".into());
+
+        assert_eq!(Struct::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn from_list_parses_attributes() {
+        let input = parse(
+            "(struct Character (@ cpp-name \"Hero\") (doc \"A playable character\")
+        (fields
+            (i32 hp (@ cpp-name \"Hp\") (range 0 100)))
+        (fn level-up (@ cpp-name \"LevelUp\") () void))",
+        );
+        let value = Struct::from_lisp(input).unwrap();
+
+        assert_eq!(value.doc, Some("A playable character".to_string()));
+        assert_eq!(value.attributes[0].string_value(), Ok("Hero".to_string()));
+        assert_eq!(
+            value.fields["hp"].attributes[0].string_value(),
+            Ok("Hp".to_string())
+        );
+        assert_eq!(value.fields["hp"].constraints.len(), 1);
+        assert_eq!(
+            value.functions["level-up"].attributes[0].string_value(),
+            Ok("LevelUp".to_string())
+        );
+    }
+
+    #[test]
+    fn from_list_duplicate_doc_returns_err() {
+        let input = parse("(struct Character (doc \"a\") (@ cpp-name \"Hero\") (doc \"b\"))");
+        let expected = Err("Duplicate doc".into());
+
+        assert_eq!(Struct::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn from_list_parses_embeds() {
+        let input = parse(
+            "(struct Character (doc \"A playable character\") (embed Stats) (embed Buffs)
+        (fields (string name)))",
+        );
+        let value = Struct::from_lisp(input).unwrap();
+
+        assert_eq!(
+            value
+                .embeds
+                .iter()
+                .map(|e| e.type_.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                Type::Identifier("Stats".to_string()),
+                Type::Identifier("Buffs".to_string())
+            ]
+        );
+        assert_eq!(value.fields.len(), 1);
+        assert!(value
+            .get_related_types()
+            .iter()
+            .any(|(_, ty)| *ty == Type::Identifier("Buffs".to_string())));
+    }
+
+    #[test]
+    fn from_list_duplicate_embed_returns_err() {
+        let input = parse("(struct Character (embed Stats) (embed Stats))");
+        let expected = Err("Duplicate embed 'Stats'".into());
+
+        assert_eq!(Struct::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn from_list_parses_static_functions() {
+        let input = parse(
+            "(struct Character (fields (string name))
+        (static-fn make-default () Character)
+        (fn rename ((string name)) void))",
+        );
+        let value = Struct::from_lisp(input).unwrap();
+
+        assert!(value.functions["make-default"].is_static);
+        assert!(!value.functions["rename"].is_static);
+    }
+
+    #[test]
+    fn from_list_static_function_same_name_returns_err() {
+        let input = parse(
+            "(struct Character (fields (string name))
+        (static-fn find () Character)
+        (fn find () Character))",
+        );
+        let expected = Err("Duplicate function 'find'".into());
+
+        assert_eq!(Struct::from_lisp(input), expected);
+    }
 }
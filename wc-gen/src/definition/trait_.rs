@@ -0,0 +1,137 @@
+use super::{attribute::Attribute, function::Function, parse_annotations, type_::Type, FromLisp};
+use lisper::{Error, Location};
+use std::collections::HashMap;
+
+/// A set of functions that structs can implement with `(impl Trait Struct)`.
+#[derive(Debug, Clone)]
+pub struct Trait {
+    pub location: Location,
+    pub name: String,
+    pub functions: HashMap<String, Function>,
+    pub doc: Option<String>,
+    pub attributes: Vec<Attribute>,
+}
+impl PartialEq for Trait {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.functions == other.functions
+            && self.doc == other.doc
+            && self.attributes == other.attributes
+    }
+}
+
+impl Trait {
+    /// Replaces the types of the function signatures with the result of `f`.
+    pub fn map_types(&mut self, f: &dyn Fn(&Type) -> Type) {
+        for function in self.functions.values_mut() {
+            function.map_types(f);
+        }
+    }
+}
+
+impl FromLisp for Trait {
+    fn identifier() -> &'static str {
+        "trait"
+    }
+
+    fn get_related_types(&self) -> Vec<(Location, Type)> {
+        self.functions
+            .values()
+            .flat_map(|f| f.get_related_types())
+            .collect()
+    }
+
+    fn parse_values(list: &mut lisper::List) -> Result<Self, Error> {
+        let (name, location) = list.pop_identifier("trait name")?;
+        let (doc, attributes) = parse_annotations(list)?;
+
+        let mut functions = HashMap::new();
+        while !list.is_empty() {
+            let function_list = list.pop_list("trait function")?;
+            if !Function::can_try(&function_list) {
+                return Err(Error {
+                    message: format!("Expected fn in trait '{}'", name),
+                    location: function_list.location(),
+                });
+            }
+
+            let f = Function::from_lisp(function_list)?;
+            if !f.body.is_empty() {
+                return Err(Error {
+                    message: format!("Trait function '{}' can not have a body", f.name),
+                    location: f.location,
+                });
+            }
+
+            if functions.contains_key(&f.name) {
+                return Err(Error {
+                    message: format!("Duplicate function '{}'", f.name),
+                    location: f.location,
+                });
+            }
+            functions.insert(f.name.clone(), f);
+        }
+
+        Ok(Trait {
+            location,
+            name,
+            functions,
+            doc,
+            attributes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> lisper::List {
+        lisper::parse_str(input).unwrap()[0].clone()
+    }
+
+    #[test]
+    fn can_try_identifier_returns_true() {
+        let input = parse("(trait Collidable)");
+        assert!(Trait::can_try(&input));
+    }
+
+    #[test]
+    fn from_lisp_returns_functions() {
+        let input = parse(
+            "(trait Collidable (doc \"Can hit things\")
+                (fn collides? ((Aabb other)) bool)
+                (fn bounds () Aabb))",
+        );
+        let value = Trait::from_lisp(input).unwrap();
+
+        assert_eq!(value.name, "Collidable".to_string());
+        assert_eq!(value.doc, Some("Can hit things".to_string()));
+        assert_eq!(value.functions.len(), 2);
+        assert_eq!(value.functions["collides?"].return_type.1, Type::Bool);
+    }
+
+    #[test]
+    fn from_lisp_not_function_returns_err() {
+        let input = parse("(trait Collidable (fields (i32 x)))");
+        let expected = Err("Expected fn in trait 'Collidable'".into());
+
+        assert_eq!(Trait::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn from_lisp_function_body_returns_err() {
+        let input = parse("(trait Collidable (fn bounds () i32 0))");
+        let expected = Err("Trait function 'bounds' can not have a body".into());
+
+        assert_eq!(Trait::from_lisp(input), expected);
+    }
+
+    #[test]
+    fn from_lisp_duplicate_function_returns_err() {
+        let input = parse("(trait Collidable (fn bounds () i32) (fn bounds () i32))");
+        let expected = Err("Duplicate function 'bounds'".into());
+
+        assert_eq!(Trait::from_lisp(input), expected);
+    }
+}
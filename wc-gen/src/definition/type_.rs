@@ -1,5 +1,6 @@
 use super::Error;
 use lisper::Location;
+use std::collections::HashMap;
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Type {
@@ -17,6 +18,11 @@ pub enum Type {
     Void,
     Identifier(String),
     List(Box<Type>),
+    Optional(Box<Type>),
+    /// An instantiation of a generic type, such as `Pair<i32,string>`.
+    Generic(String, Vec<Type>),
+    /// A type parameter of a generic definition.
+    Parameter(String),
 }
 
 impl std::fmt::Display for Type {
@@ -36,6 +42,18 @@ impl std::fmt::Display for Type {
             Type::Void => write!(f, "void"),
             Type::Identifier(name) => write!(f, "{}", name),
             Type::List(inner) => write!(f, "{}[]", inner),
+            Type::Optional(inner) => write!(f, "{}?", inner),
+            Type::Generic(name, arguments) => write!(
+                f,
+                "{}<{}>",
+                name,
+                arguments
+                    .iter()
+                    .map(|a| a.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+            Type::Parameter(name) => write!(f, "{}", name),
         }
     }
 }
@@ -55,15 +73,104 @@ impl Type {
         }
     }
 
+    pub fn is_optional(&self) -> bool {
+        matches!(self, Type::Optional(_))
+    }
+
     /// Returns the inner type of the type.
-    /// For example, `int[]` would return `int`.
+    /// For example, `int[]` would return `int` and `Point?` would return `Point`.
     pub fn inner_type(&self) -> Type {
         match self {
             Type::List(inner) => inner.inner_type(),
+            Type::Optional(inner) => inner.inner_type(),
+            ty => ty.clone(),
+        }
+    }
+
+    /// Returns the type with every identifier replaced by the result of `f`.
+    pub fn map_identifiers(&self, f: &dyn Fn(&str) -> Type) -> Type {
+        match self {
+            Type::Identifier(name) => f(name),
+            Type::List(inner) => Type::List(Box::new(inner.map_identifiers(f))),
+            Type::Optional(inner) => Type::Optional(Box::new(inner.map_identifiers(f))),
+            Type::Generic(name, arguments) => Type::Generic(
+                name.clone(),
+                arguments.iter().map(|a| a.map_identifiers(f)).collect(),
+            ),
+            ty => ty.clone(),
+        }
+    }
+
+    /// Returns the type with the names of identifiers and generic instantiations replaced by the result of `f`.
+    pub fn map_names(&self, f: &dyn Fn(&str) -> String) -> Type {
+        match self {
+            Type::Identifier(name) => Type::Identifier(f(name)),
+            Type::List(inner) => Type::List(Box::new(inner.map_names(f))),
+            Type::Optional(inner) => Type::Optional(Box::new(inner.map_names(f))),
+            Type::Generic(name, arguments) => {
+                Type::Generic(f(name), arguments.iter().map(|a| a.map_names(f)).collect())
+            }
             ty => ty.clone(),
         }
     }
 
+    /// Returns the type with every type parameter replaced by its argument.
+    /// Parameters without an argument are left as is.
+    pub fn substitute(&self, arguments: &HashMap<String, Type>) -> Type {
+        match self {
+            Type::Parameter(name) => match arguments.get(name) {
+                Some(ty) => ty.clone(),
+                None => self.clone(),
+            },
+            Type::List(inner) => Type::List(Box::new(inner.substitute(arguments))),
+            Type::Optional(inner) => Type::Optional(Box::new(inner.substitute(arguments))),
+            Type::Generic(name, type_arguments) => Type::Generic(
+                name.clone(),
+                type_arguments
+                    .iter()
+                    .map(|a| a.substitute(arguments))
+                    .collect(),
+            ),
+            ty => ty.clone(),
+        }
+    }
+
+    /// Returns the type with every generic instantiation replaced by the result of `f`.
+    /// Type arguments are mapped before the instantiation that contains them.
+    pub fn map_generics(&self, f: &dyn Fn(&str, Vec<Type>) -> Type) -> Type {
+        match self {
+            Type::List(inner) => Type::List(Box::new(inner.map_generics(f))),
+            Type::Optional(inner) => Type::Optional(Box::new(inner.map_generics(f))),
+            Type::Generic(name, arguments) => {
+                f(name, arguments.iter().map(|a| a.map_generics(f)).collect())
+            }
+            ty => ty.clone(),
+        }
+    }
+
+    /// Splits type arguments on the commas that are not nested in other type arguments.
+    fn split_arguments(arguments: &str) -> Vec<String> {
+        let mut split = vec![];
+        let mut current = String::new();
+        let mut depth = 0;
+        for c in arguments.chars() {
+            match c {
+                '<' => depth += 1,
+                '>' => depth -= 1,
+                ',' if depth == 0 => {
+                    split.push(current.trim().to_string());
+                    current.clear();
+                    continue;
+                }
+                _ => {}
+            }
+            current.push(c);
+        }
+        split.push(current.trim().to_string());
+
+        split
+    }
+
     /// Tries to parse a type from a string.
     pub fn try_parse(value: &str, location: Location) -> Result<Self, Error> {
         match value {
@@ -89,6 +196,28 @@ impl Type {
                     });
                 }
 
+                if let Some(inner) = identifier.strip_suffix('?') {
+                    let inner = Type::try_parse(inner, location.clone())?;
+
+                    match inner {
+                        Type::Void => {
+                            return Err(Error {
+                                message: "void can not be optional".into(),
+                                location,
+                            });
+                        }
+                        Type::Optional(_) => {
+                            return Err(Error {
+                                message: "Optionals can not be nested".into(),
+                                location,
+                            });
+                        }
+                        _ => {}
+                    }
+
+                    return Ok(Type::Optional(Box::new(inner)));
+                }
+
                 if identifier.ends_with("[]") {
                     let inner = &identifier[..identifier.len() - 2];
                     let inner = Type::try_parse(inner, location.clone())?;
@@ -100,6 +229,13 @@ impl Type {
                         });
                     }
 
+                    if inner.is_optional() {
+                        return Err(Error {
+                            message: "Optionals can not be stored in a list".into(),
+                            location,
+                        });
+                    }
+
                     return Ok(Type::List(Box::new(inner)));
                 }
                 if identifier.ends_with("[") {
@@ -108,6 +244,41 @@ impl Type {
                         location: location,
                     });
                 }
+
+                if let Some(start) = identifier.find('<') {
+                    if !identifier.ends_with(">") {
+                        return Err(Error {
+                            message: "Unclosed type arguments".into(),
+                            location,
+                        });
+                    }
+
+                    let name = &identifier[..start];
+                    let arguments =
+                        Self::split_arguments(&identifier[start + 1..identifier.len() - 1])
+                            .iter()
+                            .map(|argument| {
+                                if argument.is_empty() {
+                                    return Err(Error {
+                                        message: "Expected type argument".into(),
+                                        location: location.clone(),
+                                    });
+                                }
+
+                                let argument = Type::try_parse(argument, location.clone())?;
+                                if argument == Type::Void {
+                                    return Err(Error {
+                                        message: "void can not be a type argument".into(),
+                                        location: location.clone(),
+                                    });
+                                }
+                                Ok(argument)
+                            })
+                            .collect::<Result<Vec<_>, _>>()?;
+
+                    return Ok(Type::Generic(name.into(), arguments));
+                }
+
                 Ok(Type::Identifier(identifier.into()))
             }
         }
@@ -128,6 +299,21 @@ mod tests {
 
         let ty = Type::List(Box::new(Type::List(Box::new(Type::I64))));
         assert_eq!(ty.inner_type(), Type::I64);
+
+        let ty = Type::Optional(Box::new(Type::List(Box::new(Type::I64))));
+        assert_eq!(ty.inner_type(), Type::I64);
+    }
+
+    #[test]
+    fn test_map_identifiers() {
+        let ty = Type::Optional(Box::new(Type::List(Box::new(Type::Identifier(
+            "EntityId".into(),
+        )))));
+        let expected = Type::Optional(Box::new(Type::List(Box::new(Type::U32))));
+        assert_eq!(ty.map_identifiers(&|_| Type::U32), expected);
+
+        let ty = Type::I8;
+        assert_eq!(ty.map_identifiers(&|_| Type::U32), Type::I8);
     }
 
     #[test]
@@ -251,4 +437,134 @@ mod tests {
             Err(expected.into())
         );
     }
+
+    #[test]
+    fn parse_optional() {
+        let input = "Skill?";
+        let expected = Type::Optional(Box::new(Type::Identifier("Skill".into())));
+        assert_eq!(Type::try_parse(input, Location::default()), Ok(expected));
+    }
+
+    #[test]
+    fn parse_optional_list() {
+        let input = "i32[]?";
+        let expected = Type::Optional(Box::new(Type::List(Box::new(Type::I32))));
+        assert_eq!(Type::try_parse(input, Location::default()), Ok(expected));
+    }
+
+    #[test]
+    fn parse_list_of_optionals_returns_err() {
+        let input = "i32?[]";
+        let expected = "Optionals can not be stored in a list".to_string();
+        assert_eq!(
+            Type::try_parse(input, Location::default()),
+            Err(expected.into())
+        );
+    }
+
+    #[test]
+    fn parse_nested_optional_returns_err() {
+        let input = "i32??";
+        let expected = "Optionals can not be nested".to_string();
+        assert_eq!(
+            Type::try_parse(input, Location::default()),
+            Err(expected.into())
+        );
+    }
+
+    #[test]
+    fn parse_void_optional_returns_err() {
+        let input = "void?";
+        let expected = "void can not be optional".to_string();
+        assert_eq!(
+            Type::try_parse(input, Location::default()),
+            Err(expected.into())
+        );
+    }
+
+    #[test]
+    fn parse_generic() {
+        let input = "Pair<i32,string>";
+        let expected = Type::Generic("Pair".into(), vec![Type::I32, Type::String]);
+        assert_eq!(Type::try_parse(input, Location::default()), Ok(expected));
+    }
+
+    #[test]
+    fn parse_nested_generic_list() {
+        let input = "Pair<Ranged<u8>,Move[]>[]";
+        let expected = Type::List(Box::new(Type::Generic(
+            "Pair".into(),
+            vec![
+                Type::Generic("Ranged".into(), vec![Type::U8]),
+                Type::List(Box::new(Type::Identifier("Move".into()))),
+            ],
+        )));
+        assert_eq!(Type::try_parse(input, Location::default()), Ok(expected));
+    }
+
+    #[test]
+    fn parse_generic_unclosed_returns_err() {
+        let input = "Pair<i32,string";
+        let expected = "Unclosed type arguments".to_string();
+        assert_eq!(
+            Type::try_parse(input, Location::default()),
+            Err(expected.into())
+        );
+    }
+
+    #[test]
+    fn parse_generic_empty_argument_returns_err() {
+        let input = "Pair<i32,>";
+        let expected = "Expected type argument".to_string();
+        assert_eq!(
+            Type::try_parse(input, Location::default()),
+            Err(expected.into())
+        );
+    }
+
+    #[test]
+    fn display_generic() {
+        let ty = Type::Generic("Pair".into(), vec![Type::I32, Type::String]);
+        assert_eq!(ty.to_string(), "Pair<i32,string>");
+    }
+
+    #[test]
+    fn substitute_replaces_parameters() {
+        let ty = Type::List(Box::new(Type::Generic(
+            "Pair".into(),
+            vec![Type::Parameter("A".into()), Type::Parameter("B".into())],
+        )));
+        let mut arguments = HashMap::new();
+        arguments.insert("A".to_string(), Type::I32);
+
+        let expected = Type::List(Box::new(Type::Generic(
+            "Pair".into(),
+            vec![Type::I32, Type::Parameter("B".into())],
+        )));
+        assert_eq!(ty.substitute(&arguments), expected);
+    }
+
+    #[test]
+    fn map_generics_maps_arguments_first() {
+        let ty = Type::try_parse("Pair<Ranged<u8>,i32>?", Location::default()).unwrap();
+        let mapped = ty.map_generics(&|name, arguments| {
+            let arguments = arguments.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+            Type::Identifier(format!("{}_{}", name, arguments.join("_")))
+        });
+
+        let expected = Type::Optional(Box::new(Type::Identifier("Pair_Ranged_u8_i32".into())));
+        assert_eq!(mapped, expected);
+    }
+
+    #[test]
+    fn map_names_renames_generics_and_arguments() {
+        let ty = Type::try_parse("Pair<Move,i32>[]", Location::default()).unwrap();
+        let mapped = ty.map_names(&|name| format!("combat/{}", name));
+
+        let expected = Type::List(Box::new(Type::Generic(
+            "combat/Pair".into(),
+            vec![Type::Identifier("combat/Move".into()), Type::I32],
+        )));
+        assert_eq!(mapped, expected);
+    }
 }
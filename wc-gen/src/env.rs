@@ -1,57 +1,293 @@
-use crate::definition::{
-    enum_::Enum, function::Function, output::Output, struct_::Struct, type_::Type, FromLisp,
+use crate::{
+    definition::{
+        alias::Alias,
+        attribute::Attribute,
+        constant::Constant,
+        enum_::Enum,
+        expression::{Expression, ExpressionKind},
+        field::Field,
+        flags::Flags,
+        function::Function,
+        impl_::Impl,
+        literal::Literal,
+        module::Module,
+        newtype::Newtype,
+        output::Output,
+        struct_::Struct,
+        trait_::Trait,
+        type_::Type,
+        FromLisp,
+    },
+    interpreter::Interpreter,
+    loader::{self, Source},
+    type_checker,
 };
-use lisper::{Error, List};
-use std::collections::HashMap;
+use lisper::{Error, List, Location};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+/// A kind of definition as it is written in errors, along with the names and locations of its definitions.
+type NamedDefinitions<'a> = (&'a str, &'a str, Vec<(&'a String, &'a Location)>);
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Environment {
     pub structs: HashMap<String, Struct>,
     pub enums: HashMap<String, Enum>,
     pub functions: HashMap<String, Function>,
+    pub aliases: HashMap<String, Alias>,
+    pub newtypes: HashMap<String, Newtype>,
+    pub flags: HashMap<String, Flags>,
+    pub constants: HashMap<String, Constant>,
+    pub traits: HashMap<String, Trait>,
+    pub impls: Vec<Impl>,
     pub outputs: Vec<Output>,
 }
 impl Environment {
+    /// Returns whether the name refers to a type.
+    pub fn is_known_type(&self, name: &str) -> bool {
+        self.structs.contains_key(name)
+            || self.enums.contains_key(name)
+            || self.aliases.contains_key(name)
+            || self.newtypes.contains_key(name)
+            || self.flags.contains_key(name)
+    }
+
+    /// Returns the type parameters of the named type. Empty if the type is not generic.
+    fn get_type_parameters(&self, name: &str) -> &[String] {
+        if let Some(s) = self.structs.get(name) {
+            return &s.type_parameters;
+        }
+
+        if let Some(e) = self.enums.get(name) {
+            return &e.type_parameters;
+        }
+
+        &[]
+    }
+
+    fn validate_type(
+        &self,
+        location: &Location,
+        ty: &Type,
+        parameters: &[String],
+    ) -> Result<(), Error> {
+        let (name, arguments) = match ty {
+            Type::List(inner) | Type::Optional(inner) => {
+                return self.validate_type(location, inner, parameters);
+            }
+            Type::Identifier(name) if parameters.contains(name) => return Ok(()),
+            Type::Identifier(name) => (name, vec![]),
+            Type::Generic(name, _) if parameters.contains(name) => {
+                return Err(Error {
+                    message: format!("Type parameter '{}' can not take type arguments", name),
+                    location: location.clone(),
+                });
+            }
+            Type::Generic(name, arguments) => (name, arguments.clone()),
+            _ => return Ok(()),
+        };
+
+        if !self.is_known_type(name) {
+            return Err(Error {
+                message: format!("Unknown type '{}'", name),
+                location: location.clone(),
+            });
+        }
+
+        let expected = self.get_type_parameters(name).len();
+        if expected != arguments.len() {
+            return Err(Error {
+                message: format!(
+                    "Type '{}' expects {} type arguments, got {}",
+                    name,
+                    expected,
+                    arguments.len()
+                ),
+                location: location.clone(),
+            });
+        }
+
+        for argument in arguments.iter() {
+            self.validate_type(location, argument, parameters)?;
+        }
+
+        Ok(())
+    }
+
+    fn validate_types_exist(
+        &self,
+        types: Vec<(Location, Type)>,
+        parameters: &[String],
+    ) -> Result<(), Error> {
+        for (location, ty) in types {
+            self.validate_type(&location, &ty, parameters)?;
+        }
+
+        Ok(())
+    }
+
+    /// Ensures type parameters do not shadow other types.
+    fn validate_type_parameters(
+        &self,
+        location: &Location,
+        parameters: &[String],
+    ) -> Result<(), Error> {
+        for parameter in parameters.iter() {
+            if self.is_known_type(parameter) {
+                return Err(Error {
+                    message: format!("Type parameter '{}' has the same name as a type", parameter),
+                    location: location.clone(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
     fn validate_identifier_types(&self) -> Result<(), Error> {
         for (_, value) in self.structs.iter() {
-            for (location, ty) in value.get_related_types() {
-                if let Type::Identifier(ty) = ty.inner_type() {
-                    if self.structs.contains_key(&ty.to_string()) == false
-                        && self.enums.contains_key(&ty.to_string()) == false
-                    {
-                        return Err(Error {
-                            message: format!("Unknown type '{}'", ty),
-                            location,
-                        });
-                    }
-                }
-            }
+            self.validate_type_parameters(&value.location, &value.type_parameters)?;
+            self.validate_types_exist(value.get_related_types(), &value.type_parameters)?;
         }
 
         for (_, value) in self.enums.iter() {
-            for (location, ty) in value.get_related_types() {
-                if let Type::Identifier(ty) = ty.inner_type() {
-                    if self.structs.contains_key(&ty.to_string()) == false
-                        && self.enums.contains_key(&ty.to_string()) == false
-                    {
+            self.validate_type_parameters(&value.location, &value.type_parameters)?;
+            self.validate_types_exist(value.get_related_types(), &value.type_parameters)?;
+        }
+
+        for (_, value) in self.functions.iter() {
+            self.validate_types_exist(value.get_related_types(), &[])?;
+        }
+
+        for (_, value) in self.aliases.iter() {
+            self.validate_types_exist(value.get_related_types(), &[])?;
+        }
+
+        for (_, value) in self.newtypes.iter() {
+            self.validate_types_exist(value.get_related_types(), &[])?;
+        }
+
+        for (_, value) in self.constants.iter() {
+            self.validate_types_exist(value.get_related_types(), &[])?;
+        }
+
+        for (_, value) in self.traits.iter() {
+            self.validate_types_exist(value.get_related_types(), &[])?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the traits the struct implements, sorted by name.
+    pub fn get_struct_traits(&self, name: &str) -> Vec<&Trait> {
+        let mut traits = self
+            .impls
+            .iter()
+            .filter(|i| i.struct_name == name)
+            .filter_map(|i| self.traits.get(&i.trait_name))
+            .collect::<Vec<_>>();
+        traits.sort_by(|a, b| a.name.cmp(&b.name));
+        traits
+    }
+
+    /// Returns the function a call from the named definition refers to.
+    /// Functions are looked up from the innermost module of the caller outwards.
+    pub fn resolve_function(&self, caller: &str, name: &str) -> Option<&Function> {
+        let modules = caller.split('/').map(|m| m.to_string()).collect::<Vec<_>>();
+        let modules = &modules[..modules.len() - 1];
+        for depth in (1..=modules.len()).rev() {
+            let qualified = Module::qualify(&modules[..depth], name);
+            if let Some(f) = self.functions.get(&qualified) {
+                return Some(f);
+            }
+        }
+
+        self.functions.get(name)
+    }
+
+    /// Returns the constant a reference from the named definition refers to.
+    /// Constants are looked up from the innermost module of the caller outwards.
+    pub fn resolve_constant(&self, caller: &str, name: &str) -> Option<&Constant> {
+        let modules = caller.split('/').map(|m| m.to_string()).collect::<Vec<_>>();
+        let modules = &modules[..modules.len() - 1];
+        for depth in (1..=modules.len()).rev() {
+            let qualified = Module::qualify(&modules[..depth], name);
+            if let Some(c) = self.constants.get(&qualified) {
+                return Some(c);
+            }
+        }
+
+        self.constants.get(name)
+    }
+
+    /// Ensures impls refer to a trait and a struct, and that the functions the struct declares match the trait.
+    fn validate_impls(&self) -> Result<(), Error> {
+        let mut seen = HashSet::new();
+        for i in self.impls.iter() {
+            let t = match self.traits.get(&i.trait_name) {
+                Some(t) => t,
+                None => {
+                    return Err(Error {
+                        message: format!("Unknown trait '{}'", i.trait_name),
+                        location: i.location.clone(),
+                    })
+                }
+            };
+
+            let s = match self.structs.get(&i.struct_name) {
+                Some(s) => s,
+                None => {
+                    return Err(Error {
+                        message: format!("Unknown struct '{}'", i.struct_name),
+                        location: i.location.clone(),
+                    })
+                }
+            };
+
+            if s.is_generic() {
+                return Err(Error {
+                    message: format!(
+                        "Generic struct '{}' can not implement trait '{}'",
+                        s.name, t.name
+                    ),
+                    location: i.location.clone(),
+                });
+            }
+
+            if !seen.insert((&i.trait_name, &i.struct_name)) {
+                return Err(Error {
+                    message: format!("Duplicate impl of '{}' for '{}'", t.name, s.name),
+                    location: i.location.clone(),
+                });
+            }
+
+            let mut names = t.functions.keys().collect::<Vec<_>>();
+            names.sort();
+            for name in names {
+                if let Some(declared) = s.functions.get(name) {
+                    if !declared.signature_matches(&t.functions[name]) {
                         return Err(Error {
-                            message: format!("Unknown type '{}'", ty),
-                            location,
+                            message: format!(
+                                "Function '{}' of '{}' does not match trait '{}'",
+                                name, s.name, t.name
+                            ),
+                            location: declared.location.clone(),
                         });
                     }
-                }
-            }
-        }
 
-        for (_, value) in self.functions.iter() {
-            for (location, ty) in value.get_related_types() {
-                if let Type::Identifier(ty) = ty.inner_type() {
-                    if self.structs.contains_key(&ty.to_string()) == false
-                        && self.enums.contains_key(&ty.to_string()) == false
-                    {
+                    // The struct overrides the trait function, so both must be named alike
+                    let cpp_name = Attribute::find(&declared.attributes, "cpp-name");
+                    let trait_cpp_name = Attribute::find(&t.functions[name].attributes, "cpp-name");
+                    if cpp_name.is_some() && cpp_name != trait_cpp_name {
                         return Err(Error {
-                            message: format!("Unknown type '{}'", ty),
-                            location,
+                            message: format!(
+                                "Function '{}' of '{}' must have the same cpp-name as in trait '{}'",
+                                name, s.name, t.name
+                            ),
+                            location: declared.location.clone(),
                         });
                     }
                 }
@@ -61,142 +297,1184 @@ impl Environment {
         Ok(())
     }
 
-    fn validate_no_duplicate_names(&self) -> Result<(), Error> {
-        // Validate structs against other things
-        for (name, value) in self.structs.iter() {
-            if self.enums.contains_key(name) {
+    /// Ensures `(self const)` is only used on functions with a struct.
+    fn validate_self_const(&self) -> Result<(), Error> {
+        let mut names = self.functions.keys().collect::<Vec<_>>();
+        names.sort();
+        for name in names {
+            let f = &self.functions[name];
+            if f.is_const {
                 return Err(Error {
-                    message: format!("Struct '{}' has the same name as a enum", name),
-                    location: value.location.clone(),
+                    message: format!("Function '{}' has no self to be const", f.name),
+                    location: f.location.clone(),
                 });
             }
+        }
 
-            if self.functions.contains_key(name) {
-                return Err(Error {
-                    message: format!("Struct '{}' has the same name as a function", name),
-                    location: value.location.clone(),
-                });
+        Ok(())
+    }
+
+    /// Adds the trait functions a struct does not declare itself to the struct, so it inherits them.
+    /// Functions the struct declares take the `cpp-name` of the trait function they override.
+    fn inherit_trait_functions(&mut self) {
+        for i in self.impls.iter() {
+            let (t, s) = match (
+                self.traits.get(&i.trait_name),
+                self.structs.get_mut(&i.struct_name),
+            ) {
+                (Some(t), Some(s)) => (t, s),
+                _ => continue,
+            };
+
+            for (name, f) in t.functions.iter() {
+                let declared = s.functions.entry(name.clone()).or_insert_with(|| f.clone());
+                let cpp_name = Attribute::find(&f.attributes, "cpp-name");
+                if let Some(cpp_name) = cpp_name {
+                    if Attribute::find(&declared.attributes, "cpp-name").is_none() {
+                        declared.attributes.push(cpp_name.clone());
+                    }
+                }
             }
         }
+    }
 
-        // Validate enums against other things
-        for (name, value) in self.enums.iter() {
-            if self.structs.contains_key(name) {
-                return Err(Error {
-                    message: format!("Enum '{}' has the same name as a struct", name),
-                    location: value.location.clone(),
-                });
+    /// Replaces identifiers that refer to a type parameter of their generic definition with a `Type::Parameter`.
+    fn replace_type_parameters(&mut self) {
+        fn to_parameter(parameters: Vec<String>) -> impl Fn(&Type) -> Type {
+            move |ty: &Type| {
+                ty.map_identifiers(&|name: &str| {
+                    if parameters.iter().any(|p| p == name) {
+                        Type::Parameter(name.to_string())
+                    } else {
+                        Type::Identifier(name.to_string())
+                    }
+                })
             }
+        }
 
-            if self.functions.contains_key(name) {
-                return Err(Error {
-                    message: format!("Enum '{}' has the same name as a function", name),
-                    location: value.location.clone(),
-                });
+        for s in self.structs.values_mut().filter(|s| s.is_generic()) {
+            s.map_types(&to_parameter(s.type_parameters.clone()));
+        }
+
+        for e in self.enums.values_mut().filter(|e| e.is_generic()) {
+            e.map_types(&to_parameter(e.type_parameters.clone()));
+        }
+    }
+
+    /// Ensures aliases do not refer back to themselves.
+    fn validate_no_alias_cycles(&self) -> Result<(), Error> {
+        let mut names = self.aliases.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+
+        for name in names.iter() {
+            let mut path = vec![name.clone()];
+            let mut current = name.clone();
+            while let Some(alias) = self.aliases.get(&current) {
+                let next = match alias.type_.1.inner_type() {
+                    Type::Identifier(next) if self.aliases.contains_key(&next) => next,
+                    _ => break,
+                };
+
+                path.push(next.clone());
+                if next == *name {
+                    return Err(Error {
+                        message: format!(
+                            "Alias '{}' refers to itself: {}",
+                            name,
+                            path.join(" -> ")
+                        ),
+                        location: self.aliases[name].location.clone(),
+                    });
+                }
+                if path[..path.len() - 1].contains(&next) {
+                    // Cycle that does not include this alias, it is reported for its own members.
+                    break;
+                }
+
+                current = next;
             }
         }
 
         Ok(())
     }
 
-    pub fn parse(lists: Vec<List>) -> Result<Self, Error> {
-        parse(lists)
+    /// Returns the type with all aliases replaced by the types they refer to.
+    pub fn resolve_aliases(&self, ty: &Type) -> Type {
+        ty.map_identifiers(&|name: &str| match self.aliases.get(name) {
+            Some(alias) => self.resolve_aliases(&alias.type_.1),
+            None => Type::Identifier(name.to_string()),
+        })
     }
-}
 
-fn parse(lists: Vec<List>) -> Result<Environment, Error> {
-    let mut structs = HashMap::new();
-    let mut enums = HashMap::new();
-    let mut functions = HashMap::new();
-    let mut outputs = vec![];
+    /// Replaces every use of an alias with the type it refers to, so later stages only see concrete types.
+    fn replace_aliases(&mut self) {
+        let resolved = self
+            .aliases
+            .iter()
+            .map(|(name, alias)| (name.clone(), self.resolve_aliases(&alias.type_.1)))
+            .collect::<HashMap<_, _>>();
 
-    for list in lists {
-        let definition = parse_list(list)?;
-        match definition {
-            Definition::Empty => {}
-            Definition::Output(output) => outputs.push(output),
-            Definition::Enum(enum_) => match enums.insert(enum_.name.clone(), enum_.clone()) {
-                Some(_) => {
-                    return Err(lisper::Error {
-                        message: format!("Duplicate enum '{}'", enum_.name),
-                        location: enum_.location,
-                    })
-                }
-                None => {}
+        let resolve = |name: &str| match resolved.get(name) {
+            Some(ty) => ty.clone(),
+            None => Type::Identifier(name.to_string()),
+        };
+
+        self.map_types(&|ty: &Type| ty.map_identifiers(&resolve));
+    }
+
+    /// Replaces every type used by a definition with the result of `f`.
+    fn map_types(&mut self, f: &dyn Fn(&Type) -> Type) {
+        for s in self.structs.values_mut() {
+            s.map_types(f);
+        }
+
+        for e in self.enums.values_mut() {
+            e.map_types(f);
+        }
+
+        for fun in self.functions.values_mut() {
+            fun.map_types(f);
+        }
+
+        for a in self.aliases.values_mut() {
+            a.type_.1 = f(&a.type_.1);
+        }
+
+        for n in self.newtypes.values_mut() {
+            n.type_.1 = f(&n.type_.1);
+        }
+
+        for c in self.constants.values_mut() {
+            c.type_.1 = f(&c.type_.1);
+        }
+
+        for t in self.traits.values_mut() {
+            t.map_types(f);
+        }
+    }
+
+    /// Returns the type with aliases and newtypes unwrapped to the type they store.
+    pub fn underlying_type(&self, ty: &Type) -> Type {
+        match self.resolve_aliases(ty) {
+            Type::Identifier(name) => match self.newtypes.get(&name) {
+                Some(n) => self.underlying_type(&n.type_.1),
+                None => Type::Identifier(name),
             },
-            Definition::Struct(struct_) => {
-                match structs.insert(struct_.name.clone(), struct_.clone()) {
-                    Some(_) => {
-                        return Err(lisper::Error {
-                            message: format!("Duplicate struct '{}'", struct_.name),
-                            location: struct_.location,
-                        })
-                    }
-                    None => {}
+            ty => ty,
+        }
+    }
+
+    /// Replaces the fields of each struct with its own fields plus the fields of the structs it embeds.
+    fn flatten_embeds(&mut self) -> Result<(), Error> {
+        let mut names = self.structs.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+
+        let mut flattened = HashMap::new();
+        for name in names.iter() {
+            self.get_flattened_fields(name, &mut vec![], &mut flattened)?;
+        }
+
+        for (name, fields) in flattened {
+            if let Some(s) = self.structs.get_mut(&name) {
+                s.fields = fields;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the fields of the struct including the fields of the structs it embeds, recursively.
+    /// The path holds the structs being flattened to detect structs that embed themselves.
+    fn get_flattened_fields(
+        &self,
+        name: &str,
+        path: &mut Vec<String>,
+        flattened: &mut HashMap<String, HashMap<String, Field>>,
+    ) -> Result<HashMap<String, Field>, Error> {
+        if let Some(fields) = flattened.get(name) {
+            return Ok(fields.clone());
+        }
+
+        let s = &self.structs[name];
+        let mut fields = s.fields.clone();
+        path.push(name.to_string());
+        for embed in s.embeds.iter() {
+            let embedded = match &embed.type_ {
+                Type::Identifier(embedded) if self.structs.contains_key(embedded) => embedded,
+                ty => {
+                    return Err(Error {
+                        message: format!("Only structs can be embedded, got '{}'", ty),
+                        location: embed.location.clone(),
+                    })
                 }
+            };
+
+            if path.contains(embedded) {
+                return Err(Error {
+                    message: format!("Struct '{}' embeds itself", embedded),
+                    location: embed.location.clone(),
+                });
             }
-            Definition::Function(function) => {
-                match functions.insert(function.name.clone(), function.clone()) {
-                    Some(_) => {
-                        return Err(lisper::Error {
-                            message: format!("Duplicate function '{}'", function.name),
-                            location: function.location,
-                        })
-                    }
-                    None => {}
+
+            let mut embedded_fields = self
+                .get_flattened_fields(embedded, path, flattened)?
+                .into_iter()
+                .collect::<Vec<_>>();
+            embedded_fields.sort_by(|a, b| a.0.cmp(&b.0));
+            for (field_name, field) in embedded_fields {
+                if fields.contains_key(&field_name) {
+                    return Err(Error {
+                        message: format!(
+                            "Field '{}' of embedded struct '{}' collides with a field of '{}'",
+                            field_name, embedded, s.name
+                        ),
+                        location: embed.location.clone(),
+                    });
                 }
+                fields.insert(field_name, field);
             }
         }
+        path.pop();
+
+        flattened.insert(name.to_string(), fields.clone());
+        Ok(fields)
     }
 
-    let env = Environment {
-        structs,
-        enums,
-        outputs,
-        functions,
-    };
+    /// Returns the fields of all structs and enum variants.
+    fn get_all_fields(&self) -> Vec<&Field> {
+        self.structs
+            .values()
+            .flat_map(|s| s.fields.values())
+            .chain(
+                self.enums
+                    .values()
+                    .flat_map(|e| e.variants.values())
+                    .flat_map(|v| v.values.values()),
+            )
+            .collect()
+    }
 
-    env.validate_no_duplicate_names()?;
-    env.validate_identifier_types()?;
+    /// Ensures field constraints apply to the field's underlying type.
+    fn validate_field_constraints(&self) -> Result<(), Error> {
+        for field in self.get_all_fields() {
+            let ty = self.underlying_type(&field.type_);
+            for constraint in field.constraints.iter() {
+                constraint.validate(&ty)?;
+            }
+        }
 
-    Ok(env)
-}
+        Ok(())
+    }
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum Definition {
-    Empty,
-    Enum(Enum),
-    Struct(Struct),
-    Function(Function),
-    Output(Output),
-}
+    /// Ensures field defaults hold a value of the field's underlying type.
+    /// Default expressions are checked when they are evaluated.
+    fn validate_field_defaults(&self) -> Result<(), Error> {
+        for field in self.get_all_fields() {
+            let location = match (&field.default, &field.default_expression) {
+                (Some((location, _)), _) => location,
+                (None, Some(expression)) => &expression.location,
+                (None, None) => continue,
+            };
+
+            let ty = self.underlying_type(&field.type_);
+            match ty {
+                Type::Identifier(_)
+                | Type::List(_)
+                | Type::Optional(_)
+                | Type::Generic(_, _)
+                | Type::Parameter(_) => {
+                    return Err(Error {
+                        message: format!(
+                            "Default values are not supported for type '{}'",
+                            field.type_
+                        ),
+                        location: location.clone(),
+                    });
+                }
+                _ => {}
+            }
 
-fn parse_list(list: List) -> Result<Definition, Error> {
-    if list.is_empty() {
-        return Ok(Definition::Empty);
-    }
+            if let Some((location, default)) = &field.default {
+                default.type_check(&ty, location.clone())?;
+            }
+        }
 
-    if Struct::can_try(&list) {
-        let s = Struct::from_lisp(list)?;
-        return Ok(Definition::Struct(s));
+        Ok(())
     }
 
-    if Enum::can_try(&list) {
-        let e = Enum::from_lisp(list)?;
-        return Ok(Definition::Enum(e));
+    /// Ensures constants hold a value of their underlying type.
+    /// Constant expressions are checked when they are evaluated.
+    fn validate_constants(&self) -> Result<(), Error> {
+        for constant in self.constants.values() {
+            let ty = self.underlying_type(&constant.type_.1);
+            match ty {
+                Type::Identifier(_)
+                | Type::List(_)
+                | Type::Optional(_)
+                | Type::Generic(_, _)
+                | Type::Parameter(_) => {
+                    return Err(Error {
+                        message: format!("Constants can not be of type '{}'", constant.type_.1),
+                        location: constant.type_.0.clone(),
+                    });
+                }
+                _ => {}
+            }
+
+            if constant.expression.is_none() {
+                constant.value.1.type_check(&ty, constant.value.0.clone())?;
+            }
+        }
+
+        Ok(())
     }
 
-    if Function::can_try(&list) {
-        let f = Function::from_lisp(list)?;
-        return Ok(Definition::Function(f));
+    /// Evaluates the constants and field defaults written as expressions, storing their values as literals.
+    /// A constant is evaluated once every constant it refers to has a value.
+    fn evaluate_constant_expressions(&mut self) -> Result<(), Error> {
+        let mut pending = self
+            .constants
+            .values()
+            .filter(|c| c.expression.is_some())
+            .map(|c| c.name.clone())
+            .collect::<BTreeSet<_>>();
+
+        while !pending.is_empty() {
+            let ready = pending.iter().find(|name| {
+                let expression = self.constants[*name].expression.as_ref().unwrap();
+                self.get_referenced_constants(name, expression)
+                    .is_disjoint(&pending)
+            });
+            let name = match ready {
+                Some(name) => name.clone(),
+                None => {
+                    let name = pending.first().unwrap();
+                    return Err(Error {
+                        message: format!("Constant '{}' depends on its own value", name),
+                        location: self.constants[name].location.clone(),
+                    });
+                }
+            };
+
+            let constant = &self.constants[&name];
+            let expression = constant.expression.as_ref().unwrap();
+            let value = self
+                .evaluate_constant_expression(&name, expression, &constant.type_.1)
+                .map_err(|e| Error {
+                    message: format!("{} in constant '{}'", e.message, name),
+                    location: e.location,
+                })?;
+            let constant = self.constants.get_mut(&name).unwrap();
+            constant.value.1 = value;
+            constant.expression = None;
+            pending.remove(&name);
+        }
+
+        let mut defaults = vec![];
+        for field in self.get_all_fields() {
+            if let Some(expression) = &field.default_expression {
+                let value = self
+                    .evaluate_constant_expression("", expression, &field.type_)
+                    .map_err(|e| Error {
+                        message: format!("{} in default of '{}'", e.message, field.name),
+                        location: e.location,
+                    })?;
+                defaults.push((expression.location.clone(), value));
+            }
+        }
+
+        // The fields are visited in the same order to fill in the defaults
+        let mut defaults = defaults.into_iter();
+        let fields = self
+            .structs
+            .values_mut()
+            .flat_map(|s| s.fields.values_mut())
+            .chain(
+                self.enums
+                    .values_mut()
+                    .flat_map(|e| e.variants.values_mut())
+                    .flat_map(|v| v.values.values_mut()),
+            );
+        for field in fields {
+            if field.default_expression.is_some() {
+                field.default = defaults.next();
+            }
+        }
+
+        Ok(())
     }
 
-    if Output::can_try(&list) {
-        let o = Output::from_lisp(list)?;
-        return Ok(Definition::Output(o));
+    /// Evaluates an expression to a literal of the given type, checking it first.
+    /// Names are resolved from the caller.
+    fn evaluate_constant_expression(
+        &self,
+        caller: &str,
+        expression: &Expression,
+        ty: &Type,
+    ) -> Result<Literal, Error> {
+        let ty = self.underlying_type(ty);
+        type_checker::check_expression(self, caller, expression, Some(&ty), &mut HashMap::new())?;
+
+        let value =
+            Interpreter::new(self).evaluate(caller, expression, Some(&ty), &mut HashMap::new())?;
+        let literal = match value.to_literal() {
+            Some(literal) => literal,
+            None => {
+                return Err(Error {
+                    message: format!("Expected {} value, got '{}'", ty, value),
+                    location: expression.location.clone(),
+                })
+            }
+        };
+        literal.type_check(&ty, expression.location.clone())?;
+
+        Ok(literal)
     }
 
-    let msg = format!("Expected enum, fn or struct, got '{}'", list);
+    /// Returns the constants the expression refers to, including through the functions it calls.
+    /// Names are resolved from the caller, and from the function itself within the functions it calls.
+    fn get_referenced_constants(&self, caller: &str, expression: &Expression) -> BTreeSet<String> {
+        let mut constants = BTreeSet::new();
+        let mut visited_functions = HashSet::new();
+        let mut pending = vec![(caller, expression)];
+        while let Some((caller, e)) = pending.pop() {
+            match &e.kind {
+                ExpressionKind::Variable(name) => {
+                    if let Some(c) = self.resolve_constant(caller, name) {
+                        constants.insert(c.name.clone());
+                    }
+                }
+                ExpressionKind::Call(name, _) => {
+                    if let Some(f) = self.resolve_function(caller, name) {
+                        if visited_functions.insert(f.name.clone()) {
+                            pending.extend(f.body.iter().map(|b| (f.name.as_str(), b)));
+                        }
+                    }
+                }
+                _ => {}
+            }
+            pending.extend(e.children().into_iter().map(|c| (caller, c)));
+        }
+
+        constants
+    }
+
+    /// Returns the types stored directly in the given type.
+    /// Type parameters are replaced by the type arguments of a generic instantiation.
+    fn get_direct_referenced_types(&self, ty: &Type) -> Vec<(Location, Type)> {
+        let (name, arguments) = match ty {
+            Type::Identifier(name) => (name, vec![]),
+            Type::Generic(name, arguments) => (name, arguments.clone()),
+            _ => return vec![],
+        };
+
+        let references = if let Some(s) = self.structs.get(name) {
+            s.get_direct_referenced_types()
+        } else if let Some(e) = self.enums.get(name) {
+            e.get_direct_referenced_types()
+        } else if let Some(n) = self.newtypes.get(name) {
+            vec![n.type_.clone()]
+        } else {
+            vec![]
+        };
+
+        let arguments = self
+            .get_type_parameters(name)
+            .iter()
+            .cloned()
+            .zip(arguments)
+            .collect::<HashMap<_, _>>();
+
+        references
+            .into_iter()
+            .map(|(location, ty)| (location, ty.substitute(&arguments)))
+            .filter(|(_, ty)| matches!(ty, Type::Identifier(_) | Type::Generic(_, _)))
+            .collect()
+    }
+
+    /// Ensures no type contains itself without indirection, as it would have an infinite size.
+    /// Cycles through optionals or lists are allowed.
+    fn validate_no_infinite_cycles(&self) -> Result<(), Error> {
+        let mut names = self
+            .structs
+            .keys()
+            .chain(self.enums.keys())
+            .chain(self.newtypes.keys())
+            .cloned()
+            .collect::<Vec<_>>();
+        names.sort();
+
+        let mut visited = HashSet::new();
+        for name in names.iter() {
+            let mut path = vec![];
+            self.find_infinite_cycle(&Type::Identifier(name.clone()), &mut path, &mut visited)?;
+        }
+
+        Ok(())
+    }
+
+    /// Walks the types stored directly in the given type.
+    /// The path holds type names rather than instantiations, as a generic type that directly contains
+    /// any instantiation of itself has an infinite size.
+    fn find_infinite_cycle(
+        &self,
+        ty: &Type,
+        path: &mut Vec<String>,
+        visited: &mut HashSet<String>,
+    ) -> Result<(), Error> {
+        let name = match ty {
+            Type::Identifier(name) | Type::Generic(name, _) => name.clone(),
+            _ => return Ok(()),
+        };
+
+        if visited.contains(&ty.to_string()) {
+            return Ok(());
+        }
+
+        let mut references = self.get_direct_referenced_types(ty);
+        references.sort_by(|a, b| a.1.cmp(&b.1));
+
+        path.push(name);
+        for (location, reference) in references {
+            let reference_name = match &reference {
+                Type::Identifier(name) | Type::Generic(name, _) => name.clone(),
+                _ => continue,
+            };
+
+            if let Some(start) = path.iter().position(|n| *n == reference_name) {
+                let mut cycle = path[start..].to_vec();
+                cycle.push(reference_name.clone());
+
+                return Err(Error {
+                    message: format!(
+                        "Recursive type '{}' has infinite size: {}. Use an optional ('{}?') or list ('{}[]') to break the cycle",
+                        reference_name,
+                        cycle.join(" -> "),
+                        reference_name,
+                        reference_name
+                    ),
+                    location,
+                });
+            }
+
+            self.find_infinite_cycle(&reference, path, visited)?;
+        }
+        path.pop();
+
+        visited.insert(ty.to_string());
+        Ok(())
+    }
+
+    fn validate_no_duplicate_names(&self) -> Result<(), Error> {
+        let kinds: Vec<NamedDefinitions> = vec![
+            (
+                "Struct",
+                "struct",
+                self.structs.iter().map(|(n, v)| (n, &v.location)).collect(),
+            ),
+            (
+                "Enum",
+                "enum",
+                self.enums.iter().map(|(n, v)| (n, &v.location)).collect(),
+            ),
+            (
+                "Function",
+                "function",
+                self.functions
+                    .iter()
+                    .map(|(n, v)| (n, &v.location))
+                    .collect(),
+            ),
+            (
+                "Alias",
+                "alias",
+                self.aliases.iter().map(|(n, v)| (n, &v.location)).collect(),
+            ),
+            (
+                "Newtype",
+                "newtype",
+                self.newtypes
+                    .iter()
+                    .map(|(n, v)| (n, &v.location))
+                    .collect(),
+            ),
+            (
+                "Flags",
+                "flag set",
+                self.flags.iter().map(|(n, v)| (n, &v.location)).collect(),
+            ),
+            (
+                "Trait",
+                "trait",
+                self.traits.iter().map(|(n, v)| (n, &v.location)).collect(),
+            ),
+            (
+                "Constant",
+                "constant",
+                self.constants
+                    .iter()
+                    .map(|(n, v)| (n, &v.location))
+                    .collect(),
+            ),
+        ];
+
+        // Validate each kind against the other kinds
+        for (index, (kind, _, names)) in kinds.iter().enumerate() {
+            for (name, location) in names.iter() {
+                for (other_index, (_, other_kind, other_names)) in kinds.iter().enumerate() {
+                    if index == other_index {
+                        continue;
+                    }
+
+                    if other_names.iter().any(|(other, _)| other == name) {
+                        return Err(Error {
+                            message: format!(
+                                "{} '{}' has the same name as a {}",
+                                kind, name, other_kind
+                            ),
+                            location: (*location).clone(),
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the environment with every instantiation of a generic struct or enum replaced by a concrete
+    /// definition named after its type arguments, such as `Pair_3i32_6string` for `Pair<i32,string>`.
+    /// Used by backends that do not support generics.
+    pub fn monomorphize(&self) -> Result<Environment, Error> {
+        const MAX_DEPTH: usize = 32;
+
+        let mut env = self.clone();
+        env.structs.retain(|_, s| !s.is_generic());
+        env.enums.retain(|_, e| !e.is_generic());
+
+        // Instance name -> (generic name, type arguments, depth of nested instantiations)
+        let instances = RefCell::new(BTreeMap::<String, (String, Vec<Type>, usize)>::new());
+        let collision = RefCell::new(None);
+        let instantiate = |depth: usize| {
+            let instances = &instances;
+            let collision = &collision;
+            move |ty: &Type| {
+                ty.map_generics(&|name: &str, arguments: Vec<Type>| {
+                    let instance = instance_name(name, &arguments);
+                    if self.is_known_type(&instance) {
+                        collision.borrow_mut().get_or_insert(Error {
+                            message: format!(
+                                "Instance '{}' of generic type '{}' has the same name as a type",
+                                instance, name
+                            ),
+                            location: self.get_generic_location(name),
+                        });
+                    }
+
+                    let mut instances = instances.borrow_mut();
+                    let (other, other_arguments, _) = instances
+                        .entry(instance.clone())
+                        .or_insert((name.to_string(), arguments.clone(), depth));
+                    if *other != name || *other_arguments != arguments {
+                        collision.borrow_mut().get_or_insert(Error {
+                            message: format!(
+                                "Instances of generic types '{}' and '{}' have the same name '{}'",
+                                other, name, instance
+                            ),
+                            location: self.get_generic_location(name),
+                        });
+                    }
+
+                    Type::Identifier(instance)
+                })
+            }
+        };
+
+        env.map_types(&instantiate(0));
+        if let Some(error) = collision.take() {
+            return Err(error);
+        }
+
+        // Instances may use other generic types, so keep going until no new instances are found
+        loop {
+            let pending = instances
+                .borrow()
+                .iter()
+                .filter(|(instance, _)| {
+                    !env.structs.contains_key(*instance) && !env.enums.contains_key(*instance)
+                })
+                .map(|(instance, value)| (instance.clone(), value.clone()))
+                .collect::<Vec<_>>();
+
+            if pending.is_empty() {
+                break;
+            }
+
+            for (instance, (name, arguments, depth)) in pending {
+                let parameters = self.get_type_parameters(&name);
+                if depth > MAX_DEPTH {
+                    return Err(Error {
+                        message: format!("Generic type '{}' is instantiated without end", name),
+                        location: self.get_generic_location(&name),
+                    });
+                }
+
+                let arguments = parameters
+                    .iter()
+                    .cloned()
+                    .zip(arguments)
+                    .collect::<HashMap<_, _>>();
+                let instantiate = instantiate(depth + 1);
+                let substitute = |ty: &Type| instantiate(&ty.substitute(&arguments));
+
+                if let Some(s) = self.structs.get(&name) {
+                    let mut s = s.clone();
+                    s.name = instance.clone();
+                    s.type_parameters = vec![];
+                    s.map_types(&substitute);
+                    env.structs.insert(instance, s);
+                } else if let Some(e) = self.enums.get(&name) {
+                    let mut e = e.clone();
+                    e.name = instance.clone();
+                    e.type_parameters = vec![];
+                    e.map_types(&substitute);
+                    env.enums.insert(instance, e);
+                }
+            }
+
+            if let Some(error) = collision.take() {
+                return Err(error);
+            }
+        }
+
+        Ok(env)
+    }
+
+    /// Returns the location of the named generic struct or enum.
+    fn get_generic_location(&self, name: &str) -> Location {
+        match self.structs.get(name) {
+            Some(s) => s.location.clone(),
+            None => self.enums[name].location.clone(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn parse(lists: Vec<List>) -> Result<Self, Error> {
+        parse(lists)
+    }
+
+    /// Parses the definitions loaded from files. Errors in a definition name the file it is written in.
+    pub fn parse_sources(sources: Vec<Source>) -> Result<Self, Error> {
+        let mut definitions = vec![];
+        for source in sources {
+            collect_definitions(Some(&source.path), source.lists, &[], &mut definitions)?;
+        }
+        parse_definitions(definitions)
+    }
+}
+
+/// A definition along with the file and modules it is declared in.
+type CollectedDefinition = (Option<PathBuf>, Vec<String>, Definition);
+
+/// Returns the name of a generic instantiation, such as `Pair_3i32_6string` for `Pair<i32,string>`.
+/// Each argument is prefixed with its length so arguments containing underscores stay unambiguous.
+fn instance_name(name: &str, arguments: &[Type]) -> String {
+    fn mangle(ty: &Type) -> String {
+        let mangled = match ty {
+            Type::List(inner) => format!("list{}", mangle(inner)),
+            Type::Optional(inner) => format!("opt{}", mangle(inner)),
+            ty => ty.to_string().replace('/', "_"),
+        };
+
+        format!("{}{}", mangled.len(), mangled)
+    }
+
+    let mut instance = name.to_string();
+    for argument in arguments.iter() {
+        instance.push('_');
+        instance.push_str(&mangle(argument));
+    }
+
+    instance
+}
+
+#[cfg(test)]
+fn parse(lists: Vec<List>) -> Result<Environment, Error> {
+    let mut definitions = vec![];
+    collect_definitions(None, lists, &[], &mut definitions)?;
+    parse_definitions(definitions)
+}
+
+fn parse_definitions(definitions: Vec<CollectedDefinition>) -> Result<Environment, Error> {
+    let mut structs = HashMap::new();
+    let mut enums = HashMap::new();
+    let mut functions = HashMap::new();
+    let mut aliases = HashMap::new();
+    let mut newtypes = HashMap::new();
+    let mut flags = HashMap::new();
+    let mut constants = HashMap::new();
+    let mut traits = HashMap::new();
+    let mut impls = vec![];
+    let mut outputs = vec![];
+
+    let type_names = definitions
+        .iter()
+        .filter_map(|(_, modules, definition)| match definition {
+            Definition::Struct(s) => Some(Module::qualify(modules, &s.name)),
+            Definition::Enum(e) => Some(Module::qualify(modules, &e.name)),
+            Definition::Alias(a) => Some(Module::qualify(modules, &a.name)),
+            Definition::Newtype(n) => Some(Module::qualify(modules, &n.name)),
+            Definition::Flags(f) => Some(Module::qualify(modules, &f.name)),
+            Definition::Trait(t) => Some(Module::qualify(modules, &t.name)),
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
+
+    for (file, modules, definition) in definitions {
+        let definition = qualify_definition(definition, &modules, &type_names)
+            .map_err(|e| in_source(file.as_deref(), e))?;
+        match definition {
+            Definition::Empty => {}
+            Definition::Output(output) => outputs.push(output),
+            Definition::Impl(i) => impls.push(i),
+            Definition::Trait(t) => {
+                if traits.insert(t.name.clone(), t.clone()).is_some() {
+                    return Err(lisper::Error {
+                        message: format!("Duplicate trait '{}'", t.name),
+                        location: t.location,
+                    });
+                }
+            }
+            Definition::Enum(enum_) => match enums.insert(enum_.name.clone(), enum_.clone()) {
+                Some(_) => {
+                    return Err(lisper::Error {
+                        message: format!("Duplicate enum '{}'", enum_.name),
+                        location: enum_.location,
+                    })
+                }
+                None => {}
+            },
+            Definition::Struct(struct_) => {
+                match structs.insert(struct_.name.clone(), struct_.clone()) {
+                    Some(_) => {
+                        return Err(lisper::Error {
+                            message: format!("Duplicate struct '{}'", struct_.name),
+                            location: struct_.location,
+                        })
+                    }
+                    None => {}
+                }
+            }
+            Definition::Function(function) => {
+                match functions.insert(function.name.clone(), function.clone()) {
+                    Some(_) => {
+                        return Err(lisper::Error {
+                            message: format!("Duplicate function '{}'", function.name),
+                            location: function.location,
+                        })
+                    }
+                    None => {}
+                }
+            }
+            Definition::Alias(alias) => {
+                if aliases.insert(alias.name.clone(), alias.clone()).is_some() {
+                    return Err(lisper::Error {
+                        message: format!("Duplicate alias '{}'", alias.name),
+                        location: alias.location,
+                    });
+                }
+            }
+            Definition::Newtype(newtype) => {
+                if newtypes
+                    .insert(newtype.name.clone(), newtype.clone())
+                    .is_some()
+                {
+                    return Err(lisper::Error {
+                        message: format!("Duplicate newtype '{}'", newtype.name),
+                        location: newtype.location,
+                    });
+                }
+            }
+            Definition::Flags(f) => {
+                if flags.insert(f.name.clone(), f.clone()).is_some() {
+                    return Err(lisper::Error {
+                        message: format!("Duplicate flags '{}'", f.name),
+                        location: f.location,
+                    });
+                }
+            }
+            Definition::Constant(constant) => {
+                if constants
+                    .insert(constant.name.clone(), constant.clone())
+                    .is_some()
+                {
+                    return Err(lisper::Error {
+                        message: format!("Duplicate constant '{}'", constant.name),
+                        location: constant.location,
+                    });
+                }
+            }
+        }
+    }
+
+    let mut env = Environment {
+        structs,
+        enums,
+        outputs,
+        functions,
+        aliases,
+        newtypes,
+        flags,
+        constants,
+        traits,
+        impls,
+    };
+
+    env.validate_no_duplicate_names()?;
+    env.validate_identifier_types()?;
+    env.replace_type_parameters();
+    env.validate_no_alias_cycles()?;
+    env.replace_aliases();
+    env.flatten_embeds()?;
+    env.validate_no_infinite_cycles()?;
+    env.validate_constants()?;
+    env.validate_field_defaults()?;
+    env.validate_field_constraints()?;
+    env.validate_self_const()?;
+    env.validate_impls()?;
+    env.inherit_trait_functions();
+    type_checker::check_environment(&env)?;
+    env.evaluate_constant_expressions()?;
+
+    Ok(env)
+}
+
+/// Collects the definitions in the lists along with the file and modules they are declared in.
+fn collect_definitions(
+    file: Option<&Path>,
+    lists: Vec<List>,
+    modules: &[String],
+    definitions: &mut Vec<CollectedDefinition>,
+) -> Result<(), Error> {
+    for list in lists {
+        if Module::can_try(&list) {
+            let module = Module::from_lisp(list).map_err(|e| in_source(file, e))?;
+            let mut inner = modules.to_vec();
+            inner.push(module.name);
+            collect_definitions(file, module.definitions, &inner, definitions)?;
+            continue;
+        }
+
+        let definition = parse_list(list).map_err(|e| in_source(file, e))?;
+        if let Definition::Output(output) = &definition {
+            if !modules.is_empty() {
+                return Err(in_source(
+                    file,
+                    Error {
+                        message: "Outputs can not be declared in a module".into(),
+                        location: output.location.clone(),
+                    },
+                ));
+            }
+        }
+        definitions.push((file.map(Path::to_path_buf), modules.to_vec(), definition));
+    }
+
+    Ok(())
+}
+
+/// Prefixes the error with the file it occurred in, if the definition was loaded from one.
+fn in_source(file: Option<&Path>, error: Error) -> Error {
+    match file {
+        Some(file) => loader::in_file(file, error),
+        None => error,
+    }
+}
+
+/// Returns the qualified name a type refers to from the given modules.
+/// Types are looked up from the innermost module outwards, falling back to the name as written.
+fn resolve_name(modules: &[String], name: &str, type_names: &HashSet<String>) -> String {
+    for depth in (1..=modules.len()).rev() {
+        let qualified = Module::qualify(&modules[..depth], name);
+        if type_names.contains(&qualified) {
+            return qualified;
+        }
+    }
+
+    name.to_string()
+}
+
+fn resolve_types<'a>(
+    modules: &'a [String],
+    type_names: &'a HashSet<String>,
+    parameters: Vec<String>,
+) -> impl Fn(&Type) -> Type + 'a {
+    move |ty: &Type| {
+        ty.map_names(&|name: &str| {
+            if parameters.iter().any(|p| p == name) {
+                name.to_string()
+            } else {
+                resolve_name(modules, name, type_names)
+            }
+        })
+    }
+}
+
+/// Qualifies the name of a definition with the modules it is declared in and resolves the types it references.
+fn qualify_definition(
+    definition: Definition,
+    modules: &[String],
+    type_names: &HashSet<String>,
+) -> Result<Definition, Error> {
+    let validate_name = |name: &str, location: &Location| {
+        if name.contains('/') {
+            return Err(Error {
+                message: format!("Invalid name '{}', declare it in a module instead", name),
+                location: location.clone(),
+            });
+        }
+
+        Ok(())
+    };
+
+    let definition = match definition {
+        Definition::Empty | Definition::Output(_) => definition,
+        Definition::Struct(mut s) => {
+            validate_name(&s.name, &s.location)?;
+            s.map_types(&resolve_types(
+                modules,
+                type_names,
+                s.type_parameters.clone(),
+            ));
+            s.name = Module::qualify(modules, &s.name);
+            Definition::Struct(s)
+        }
+        Definition::Enum(mut e) => {
+            validate_name(&e.name, &e.location)?;
+            e.map_types(&resolve_types(
+                modules,
+                type_names,
+                e.type_parameters.clone(),
+            ));
+            e.name = Module::qualify(modules, &e.name);
+            Definition::Enum(e)
+        }
+        Definition::Function(mut f) => {
+            validate_name(&f.name, &f.location)?;
+            f.map_types(&resolve_types(modules, type_names, vec![]));
+            f.name = Module::qualify(modules, &f.name);
+            Definition::Function(f)
+        }
+        Definition::Alias(mut a) => {
+            validate_name(&a.name, &a.location)?;
+            a.type_.1 = resolve_types(modules, type_names, vec![])(&a.type_.1);
+            a.name = Module::qualify(modules, &a.name);
+            Definition::Alias(a)
+        }
+        Definition::Newtype(mut n) => {
+            validate_name(&n.name, &n.location)?;
+            n.type_.1 = resolve_types(modules, type_names, vec![])(&n.type_.1);
+            n.name = Module::qualify(modules, &n.name);
+            Definition::Newtype(n)
+        }
+        Definition::Trait(mut t) => {
+            validate_name(&t.name, &t.location)?;
+            t.map_types(&resolve_types(modules, type_names, vec![]));
+            t.name = Module::qualify(modules, &t.name);
+            Definition::Trait(t)
+        }
+        Definition::Impl(mut i) => {
+            i.trait_name = resolve_name(modules, &i.trait_name, type_names);
+            i.struct_name = resolve_name(modules, &i.struct_name, type_names);
+            Definition::Impl(i)
+        }
+        Definition::Flags(mut f) => {
+            validate_name(&f.name, &f.location)?;
+            f.name = Module::qualify(modules, &f.name);
+            Definition::Flags(f)
+        }
+        Definition::Constant(mut c) => {
+            validate_name(&c.name, &c.location)?;
+            c.type_.1 = resolve_types(modules, type_names, vec![])(&c.type_.1);
+            c.name = Module::qualify(modules, &c.name);
+            Definition::Constant(c)
+        }
+    };
+
+    Ok(definition)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Definition {
+    Empty,
+    Enum(Enum),
+    Struct(Struct),
+    Function(Function),
+    Alias(Alias),
+    Newtype(Newtype),
+    Flags(Flags),
+    Trait(Trait),
+    Impl(Impl),
+    Constant(Constant),
+    Output(Output),
+}
+
+fn parse_list(list: List) -> Result<Definition, Error> {
+    if list.is_empty() {
+        return Ok(Definition::Empty);
+    }
+
+    if Struct::can_try(&list) {
+        let s = Struct::from_lisp(list)?;
+        return Ok(Definition::Struct(s));
+    }
+
+    if Enum::can_try(&list) {
+        let e = Enum::from_lisp(list)?;
+        return Ok(Definition::Enum(e));
+    }
+
+    if Function::can_try(&list) {
+        let f = Function::from_lisp(list)?;
+        return Ok(Definition::Function(f));
+    }
+
+    if Alias::can_try(&list) {
+        let a = Alias::from_lisp(list)?;
+        return Ok(Definition::Alias(a));
+    }
+
+    if Newtype::can_try(&list) {
+        let n = Newtype::from_lisp(list)?;
+        return Ok(Definition::Newtype(n));
+    }
+
+    if Flags::can_try(&list) {
+        let f = Flags::from_lisp(list)?;
+        return Ok(Definition::Flags(f));
+    }
+
+    if Trait::can_try(&list) {
+        let t = Trait::from_lisp(list)?;
+        return Ok(Definition::Trait(t));
+    }
+
+    if Impl::can_try(&list) {
+        let i = Impl::from_lisp(list)?;
+        return Ok(Definition::Impl(i));
+    }
+
+    if Constant::can_try(&list) {
+        let c = Constant::from_lisp(list)?;
+        return Ok(Definition::Constant(c));
+    }
+
+    if Output::can_try(&list) {
+        let o = Output::from_lisp(list)?;
+        return Ok(Definition::Output(o));
+    }
+
+    let msg = format!("Expected enum, fn or struct, got '{}'", list);
 
     Err(Error {
         message: msg,
@@ -204,264 +1482,1402 @@ fn parse_list(list: List) -> Result<Definition, Error> {
     })
 }
 
-#[cfg(test)]
-mod tests {
-    use std::collections::HashMap;
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use lisper::Location;
+
+    fn make(contents: &str) -> List {
+        lisper::parse_str(contents)
+            .unwrap()
+            .first()
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn parse_returns_environment() {
+        let lists = lisper::parse_str("(struct foo)").unwrap();
+        let expected = Ok(Environment {
+            enums: HashMap::new(),
+            functions: HashMap::new(),
+            aliases: HashMap::new(),
+            newtypes: HashMap::new(),
+            flags: HashMap::new(),
+            traits: HashMap::new(),
+            impls: vec![],
+            constants: HashMap::new(),
+            outputs: vec![],
+            structs: vec![(
+                "foo".to_string(),
+                Struct {
+                    location: Location::default(),
+                    name: "foo".to_string(),
+                    type_parameters: vec![],
+                    embeds: vec![],
+                    fields: Default::default(),
+                    functions: Default::default(),
+                    doc: None,
+                    attributes: vec![],
+                },
+            )]
+            .into_iter()
+            .collect(),
+        });
+        let result = parse(lists);
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn parse_duplicate_struct_returns_err() {
+        let list = make("(struct foo)");
+
+        let lists = vec![list.clone(), list];
+
+        let expected = Err(Error {
+            message: "Duplicate struct 'foo'".to_string(),
+            location: Location::default(),
+        });
+        let result = parse(lists);
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn parse_list_empty_returns_empty() {
+        let list = make("()");
+        let expected = Ok(Definition::Empty);
+        assert_eq!(parse_list(list), expected);
+    }
+
+    #[test]
+    fn parse_list_returns_output() {
+        let list = make("(output cpp ../output output.hpp)");
+        let expected = Ok(Definition::Output(Output {
+            location: Location::default(),
+            folder: std::path::PathBuf::from("../output"),
+            language: crate::definition::output::TargetLanguage::Cpp,
+            attributes: vec![],
+        }));
+        assert_eq!(parse_list(list), expected);
+    }
+
+    #[test]
+    fn parse_list_struct_returns_struct() {
+        let list = make("(struct foo)");
+        let expected = Ok(Definition::Struct(Struct {
+            location: Location::default(),
+            name: "foo".to_string(),
+            type_parameters: vec![],
+            embeds: vec![],
+            fields: Default::default(),
+            functions: Default::default(),
+            doc: None,
+            attributes: vec![],
+        }));
+        assert_eq!(parse_list(list), expected);
+    }
+
+    #[test]
+    fn parse_list_enum_returns_enum() {
+        let list = make("(enum foo)");
+        let expected = Ok(Definition::Enum(Enum {
+            location: Location::default(),
+            name: "foo".to_string(),
+            type_parameters: vec![],
+            variants: Default::default(),
+            doc: None,
+            attributes: vec![],
+        }));
+        assert_eq!(parse_list(list), expected);
+    }
+
+    #[test]
+    fn parse_function_returns_expected() {
+        let list = make("(fn life () i64)");
+        let expected = Ok(Definition::Function(Function {
+            location: Location::default(),
+            name: "life".to_string(),
+            parameters: vec![],
+            return_type: (Location::default(), Type::I64),
+            is_const: false,
+            is_static: false,
+            body: vec![],
+            requires: vec![],
+            ensures: vec![],
+            doc: None,
+            attributes: vec![],
+        }));
+        assert_eq!(parse_list(list), expected);
+    }
+
+    #[test]
+    fn parse_list_unknown_returns_err() {
+        let list = make("(foo)");
+        let expected = Err(Error {
+            message: "Expected enum, fn or struct, got '(foo)'".to_string(),
+            location: Location::default(),
+        });
+        assert_eq!(parse_list(list), expected);
+    }
+
+    #[test]
+    fn struct_has_unknown_type_returns_err() {
+        let lists = lisper::parse_str("(struct foo (fields (jaja bar)))").unwrap();
+        let expected = Err(Error {
+            message: "Unknown type 'jaja'".to_string(),
+            location: Location::default(),
+        });
+        let result = parse(lists);
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn enum_has_unknown_type_returns_err() {
+        let lists = lisper::parse_str("(enum foo Point (Pointz Point))").unwrap();
+        let expected = Err(Error {
+            message: "Unknown type 'Point'".to_string(),
+            location: Location::default(),
+        });
+        let result = parse(lists);
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    #[ignore]
+    fn function_has_unknown_type_returns_err() {
+        let lists = lisper::parse_str("(fn life () Point point)").unwrap();
+        let expected = Err(Error {
+            message: "Unknown type 'Point'".to_string(),
+            location: Location::default(),
+        });
+        let result = parse(lists);
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn duplicate_function_returns_err() {
+        let lists = lisper::parse_str("(fn life () i64)\n(fn life () i64)").unwrap();
+        let expected = Err(Error {
+            message: "Duplicate function 'life'".to_string(),
+            location: Location::default(),
+        });
+        let result = parse(lists);
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn function_references_struct_returns_ok() {
+        let input = "
+        (struct Point (fields (i64 x) (i64 y)))
+        (fn life ((Point a)) i64)";
+
+        let lists = lisper::parse_str(input).unwrap();
+
+        let result = parse(lists);
+
+        assert_eq!(result.is_ok(), true);
+    }
+
+    #[test]
+    fn function_references_enum_returns_ok() {
+        let input = "
+        (enum Point)
+        (fn life ((Point a)) i64)";
+
+        let lists = lisper::parse_str(input).unwrap();
+
+        let result = parse(lists);
+
+        assert_eq!(result.is_ok(), true);
+    }
+
+    #[test]
+    fn struct_references_struct_returns_ok() {
+        let input = "
+        (struct Baz (fields (foo bar)))
+        (struct foo 
+            (fields 
+                (foo? bar)))";
+
+        let lists = lisper::parse_str(input).unwrap();
+
+        let result = parse(lists);
+
+        assert_eq!(result.is_ok(), true);
+    }
+
+    #[test]
+    fn struct_references_enum_returns_ok() {
+        let input = "
+        (enum value)
+        (struct foo 
+            (fields 
+                (value bar)))";
+
+        let lists = lisper::parse_str(input).unwrap();
+
+        let result = parse(lists);
+
+        assert_eq!(result.is_ok(), true);
+    }
+
+    #[test]
+    fn enum_references_enum_returns_ok() {
+        let input = "
+        (enum Testy (value value))
+        (enum value)";
+
+        let lists = lisper::parse_str(input).unwrap();
+
+        let result = parse(lists);
+
+        assert_eq!(result.is_ok(), true);
+    }
+
+    #[test]
+    fn enum_references_struct_returns_ok() {
+        let input = "
+        (enum Testy (value value))
+        (struct value)";
+
+        let lists = lisper::parse_str(input).unwrap();
+
+        let result = parse(lists);
+
+        assert_eq!(result.is_ok(), true);
+    }
+
+    #[test]
+    fn struct_has_same_name_as_enum_returns_err() {
+        let lists = lisper::parse_str("(struct foo)\n(enum foo)").unwrap();
+        let expected = Err(Error {
+            message: "Struct 'foo' has the same name as a enum".to_string(),
+            location: Location::default(),
+        });
+        let result = parse(lists);
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn struct_contains_itself_returns_err() {
+        let lists = lisper::parse_str("(struct Skill (fields (Skill parent)))").unwrap();
+        let expected = Err(Error {
+            message: "Recursive type 'Skill' has infinite size: Skill -> Skill. Use an optional ('Skill?') or list ('Skill[]') to break the cycle".to_string(),
+            location: Location::default(),
+        });
+        let result = parse(lists);
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn struct_contains_itself_through_other_struct_returns_err() {
+        let input = "
+        (struct A (fields (B b)))
+        (struct B (fields (C c)))
+        (struct C (fields (A a)))";
+        let lists = lisper::parse_str(input).unwrap();
+        let expected = Err(Error {
+            message: "Recursive type 'A' has infinite size: A -> B -> C -> A. Use an optional ('A?') or list ('A[]') to break the cycle".to_string(),
+            location: Location::default(),
+        });
+        let result = parse(lists);
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn struct_contains_itself_through_enum_returns_err() {
+        let input = "
+        (enum Node (Branch Tree))
+        (struct Tree (fields (Node root)))";
+        let lists = lisper::parse_str(input).unwrap();
+        let expected = Err(Error {
+            message: "Recursive type 'Node' has infinite size: Node -> Tree -> Node. Use an optional ('Node?') or list ('Node[]') to break the cycle".to_string(),
+            location: Location::default(),
+        });
+        let result = parse(lists);
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn struct_contains_itself_through_optional_returns_ok() {
+        let input = "
+        (struct Skill (fields (Skill? parent)))";
+        let lists = lisper::parse_str(input).unwrap();
+
+        let result = parse(lists);
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn struct_contains_itself_through_list_returns_ok() {
+        let input = "
+        (struct Skill (fields (string name) (Skill[] prerequisites)))
+        (struct Tree (fields (Skill root)))";
+        let lists = lisper::parse_str(input).unwrap();
+
+        let result = parse(lists);
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn parse_list_alias_returns_alias() {
+        let list = make("(alias EntityId u32)");
+        let expected = Ok(Definition::Alias(Alias {
+            location: Location::default(),
+            name: "EntityId".to_string(),
+            type_: (Location::default(), Type::U32),
+        }));
+        assert_eq!(parse_list(list), expected);
+    }
+
+    #[test]
+    fn parse_list_newtype_returns_newtype() {
+        let list = make("(newtype Gold i32)");
+        let expected = Ok(Definition::Newtype(Newtype {
+            location: Location::default(),
+            name: "Gold".to_string(),
+            type_: (Location::default(), Type::I32),
+        }));
+        assert_eq!(parse_list(list), expected);
+    }
+
+    #[test]
+    fn alias_is_replaced_in_struct_fields() {
+        let input = "
+        (alias EntityId u32)
+        (alias EntityIds EntityId[])
+        (struct Unit (fields (EntityId id) (EntityIds targets) (EntityId? owner)))";
+        let lists = lisper::parse_str(input).unwrap();
+
+        let env = parse(lists).unwrap();
+        let fields = &env.structs["Unit"].fields;
+
+        assert_eq!(fields["id"].type_, Type::U32);
+        assert_eq!(fields["targets"].type_, Type::List(Box::new(Type::U32)));
+        assert_eq!(fields["owner"].type_, Type::Optional(Box::new(Type::U32)));
+    }
+
+    #[test]
+    fn alias_is_replaced_in_functions() {
+        let input = "
+        (alias EntityId u32)
+        (fn find ((EntityId id)) EntityId)";
+        let lists = lisper::parse_str(input).unwrap();
+
+        let env = parse(lists).unwrap();
+        let function = &env.functions["find"];
+
+        assert_eq!(function.parameters[0].type_, Type::U32);
+        assert_eq!(function.return_type.1, Type::U32);
+    }
+
+    #[test]
+    fn alias_to_unknown_type_returns_err() {
+        let lists = lisper::parse_str("(alias EntityId Id)").unwrap();
+        let expected = Err(Error {
+            message: "Unknown type 'Id'".to_string(),
+            location: Location::default(),
+        });
+
+        assert_eq!(parse(lists), expected);
+    }
+
+    #[test]
+    fn alias_cycle_returns_err() {
+        let lists = lisper::parse_str("(alias A B[])\n(alias B A)").unwrap();
+        let expected = Err(Error {
+            message: "Alias 'A' refers to itself: A -> B -> A".to_string(),
+            location: Location::default(),
+        });
+
+        assert_eq!(parse(lists), expected);
+    }
+
+    #[test]
+    fn alias_to_struct_cycle_returns_err() {
+        let input = "
+        (alias Parent Skill)
+        (struct Skill (fields (Parent parent)))";
+        let lists = lisper::parse_str(input).unwrap();
+        let expected = Err(Error {
+            message: "Recursive type 'Skill' has infinite size: Skill -> Skill. Use an optional ('Skill?') or list ('Skill[]') to break the cycle".to_string(),
+            location: Location::default(),
+        });
+
+        assert_eq!(parse(lists), expected);
+    }
+
+    #[test]
+    fn newtype_is_known_type() {
+        let input = "
+        (newtype Gold i32)
+        (struct Character (fields (Gold gold)))";
+        let lists = lisper::parse_str(input).unwrap();
+
+        let env = parse(lists).unwrap();
+
+        assert_eq!(
+            env.structs["Character"].fields["gold"].type_,
+            Type::Identifier("Gold".into())
+        );
+    }
+
+    #[test]
+    fn newtype_has_same_name_as_struct_returns_err() {
+        let lists = lisper::parse_str("(newtype Gold i32)\n(struct Gold)").unwrap();
+        let expected = Err(Error {
+            message: "Struct 'Gold' has the same name as a newtype".to_string(),
+            location: Location::default(),
+        });
+
+        assert_eq!(parse(lists), expected);
+    }
+
+    #[test]
+    fn flags_is_known_type() {
+        let input = "
+        (module combat (flags Element Fire Water))
+        (struct Character (fields (combat/Element types)))";
+        let lists = lisper::parse_str(input).unwrap();
+
+        let env = parse(lists).unwrap();
+
+        assert_eq!(env.flags["combat/Element"].values.len(), 2);
+        assert_eq!(
+            env.structs["Character"].fields["types"].type_,
+            Type::Identifier("combat/Element".into())
+        );
+    }
+
+    #[test]
+    fn flags_has_same_name_as_enum_returns_err() {
+        let lists = lisper::parse_str("(flags Element Fire)\n(enum Element Fire)").unwrap();
+        let expected = Err(Error {
+            message: "Enum 'Element' has the same name as a flag set".to_string(),
+            location: Location::default(),
+        });
+
+        assert_eq!(parse(lists), expected);
+    }
+
+    #[test]
+    fn duplicate_flags_returns_err() {
+        let lists = lisper::parse_str("(flags Element Fire)\n(flags Element Water)").unwrap();
+        let expected = Err(Error {
+            message: "Duplicate flags 'Element'".to_string(),
+            location: Location::default(),
+        });
+
+        assert_eq!(parse(lists), expected);
+    }
+
+    #[test]
+    fn impl_inherits_trait_functions() {
+        let input = "
+        (trait Collidable (fn collides? ((Aabb other)) bool) (fn area () f32))
+        (struct Aabb (fields (f32 width)) (fn area () f32))
+        (impl Collidable Aabb)";
+        let lists = lisper::parse_str(input).unwrap();
+
+        let env = parse(lists).unwrap();
+        let functions = &env.structs["Aabb"].functions;
+
+        assert_eq!(functions.len(), 2);
+        assert_eq!(functions["collides?"].return_type.1, Type::Bool);
+        assert_eq!(
+            env.get_struct_traits("Aabb")[0].name,
+            "Collidable".to_string()
+        );
+    }
+
+    #[test]
+    fn impl_in_module_resolves_names() {
+        let input = "
+        (module physics
+            (trait Collidable (fn collides? ((Aabb other)) bool))
+            (struct Aabb)
+            (impl Collidable Aabb))";
+        let lists = lisper::parse_str(input).unwrap();
+
+        let env = parse(lists).unwrap();
+
+        assert_eq!(env.impls[0].trait_name, "physics/Collidable".to_string());
+        assert_eq!(env.impls[0].struct_name, "physics/Aabb".to_string());
+        assert_eq!(
+            env.traits["physics/Collidable"].functions["collides?"].parameters[0].type_,
+            Type::Identifier("physics/Aabb".into())
+        );
+    }
+
+    #[test]
+    fn impl_mismatched_function_returns_err() {
+        let input = "
+        (trait Collidable (fn collides? ((Aabb other)) bool))
+        (struct Aabb (fields) (fn collides? ((Aabb other)) i32))
+        (impl Collidable Aabb)";
+        let lists = lisper::parse_str(input).unwrap();
+        let expected = Err(Error {
+            message: "Function 'collides?' of 'Aabb' does not match trait 'Collidable'".to_string(),
+            location: Location::default(),
+        });
+
+        assert_eq!(parse(lists), expected);
+    }
+
+    #[test]
+    fn impl_unknown_trait_returns_err() {
+        let lists = lisper::parse_str("(struct Aabb)\n(impl Collidable Aabb)").unwrap();
+        let expected = Err(Error {
+            message: "Unknown trait 'Collidable'".to_string(),
+            location: Location::default(),
+        });
+
+        assert_eq!(parse(lists), expected);
+    }
+
+    #[test]
+    fn impl_unknown_struct_returns_err() {
+        let lists = lisper::parse_str("(trait Collidable)\n(impl Collidable Aabb)").unwrap();
+        let expected = Err(Error {
+            message: "Unknown struct 'Aabb'".to_string(),
+            location: Location::default(),
+        });
+
+        assert_eq!(parse(lists), expected);
+    }
+
+    #[test]
+    fn duplicate_impl_returns_err() {
+        let input = "
+        (trait Collidable)
+        (struct Aabb)
+        (impl Collidable Aabb)
+        (impl Collidable Aabb)";
+        let lists = lisper::parse_str(input).unwrap();
+        let expected = Err(Error {
+            message: "Duplicate impl of 'Collidable' for 'Aabb'".to_string(),
+            location: Location::default(),
+        });
+
+        assert_eq!(parse(lists), expected);
+    }
+
+    #[test]
+    fn embed_flattens_fields() {
+        let input = "
+        (struct Stats (fields (i32 health) (i32 attack)))
+        (struct Named (fields (string name)))
+        (struct Character (embed Stats) (embed Named) (fields (f32 speed)))";
+        let lists = lisper::parse_str(input).unwrap();
+
+        let env = parse(lists).unwrap();
+        let mut fields = env.structs["Character"]
+            .fields
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        fields.sort();
+
+        assert_eq!(fields, vec!["attack", "health", "name", "speed"]);
+        assert_eq!(env.structs["Stats"].fields.len(), 2);
+    }
+
+    #[test]
+    fn embed_flattens_nested_embeds() {
+        let input = "
+        (struct Health (fields (i32 health)))
+        (struct Stats (embed Health) (fields (i32 attack)))
+        (struct Character (embed Stats))";
+        let lists = lisper::parse_str(input).unwrap();
+
+        let env = parse(lists).unwrap();
+
+        assert_eq!(env.structs["Character"].fields.len(), 2);
+        assert_eq!(env.structs["Character"].fields["health"].type_, Type::I32);
+    }
+
+    #[test]
+    fn embed_in_module_resolves_names() {
+        let input = "
+        (module game
+            (struct Stats (fields (i32 health)))
+            (struct Character (embed Stats)))";
+        let lists = lisper::parse_str(input).unwrap();
+
+        let env = parse(lists).unwrap();
+
+        assert_eq!(
+            env.structs["game/Character"].embeds[0].type_,
+            Type::Identifier("game/Stats".to_string())
+        );
+        assert!(env.structs["game/Character"].fields.contains_key("health"));
+    }
+
+    #[test]
+    fn embed_field_collision_returns_err() {
+        let input = "
+        (struct Stats (fields (i32 health)))
+        (struct Character (embed Stats) (fields (f32 health)))";
+        let lists = lisper::parse_str(input).unwrap();
+        let expected = Err(Error {
+            message:
+                "Field 'health' of embedded struct 'Stats' collides with a field of 'Character'"
+                    .to_string(),
+            location: Location::default(),
+        });
+
+        assert_eq!(parse(lists), expected);
+    }
+
+    #[test]
+    fn embed_collision_between_embeds_returns_err() {
+        let input = "
+        (struct Stats (fields (i32 health)))
+        (struct Buffs (fields (i32 health)))
+        (struct Character (embed Stats) (embed Buffs))";
+        let lists = lisper::parse_str(input).unwrap();
+        let expected = Err(Error {
+            message:
+                "Field 'health' of embedded struct 'Buffs' collides with a field of 'Character'"
+                    .to_string(),
+            location: Location::default(),
+        });
+
+        assert_eq!(parse(lists), expected);
+    }
+
+    #[test]
+    fn embed_itself_returns_err() {
+        let input = "
+        (struct Stats (embed Character))
+        (struct Character (embed Stats))";
+        let lists = lisper::parse_str(input).unwrap();
+        let expected = Err(Error {
+            message: "Struct 'Character' embeds itself".to_string(),
+            location: Location::default(),
+        });
+
+        assert_eq!(parse(lists), expected);
+    }
+
+    #[test]
+    fn embed_enum_returns_err() {
+        let input = "
+        (enum Mood Happy Sad)
+        (struct Character (embed Mood))";
+        let lists = lisper::parse_str(input).unwrap();
+        let expected = Err(Error {
+            message: "Only structs can be embedded, got 'Mood'".to_string(),
+            location: Location::default(),
+        });
+
+        assert_eq!(parse(lists), expected);
+    }
+
+    #[test]
+    fn embed_unknown_type_returns_err() {
+        let lists = lisper::parse_str("(struct Character (embed Stats))").unwrap();
+        let expected = Err(Error {
+            message: "Unknown type 'Stats'".to_string(),
+            location: Location::default(),
+        });
+
+        assert_eq!(parse(lists), expected);
+    }
+
+    #[test]
+    fn free_function_self_const_returns_err() {
+        let lists = lisper::parse_str("(fn life (self const) () i64)").unwrap();
+        let expected = Err(Error {
+            message: "Function 'life' has no self to be const".to_string(),
+            location: Location::default(),
+        });
+
+        assert_eq!(parse(lists), expected);
+    }
+
+    #[test]
+    fn impl_mismatched_cpp_name_returns_err() {
+        let input = "
+        (trait Named (fn label (@ cpp-name \"Label\") (self const) () string))
+        (struct Hero (fields) (fn label (@ cpp-name \"GetLabel\") (self const) () string))
+        (impl Named Hero)";
+        let lists = lisper::parse_str(input).unwrap();
+        let expected = Err(Error {
+            message: "Function 'label' of 'Hero' must have the same cpp-name as in trait 'Named'"
+                .to_string(),
+            location: Location::default(),
+        });
+
+        assert_eq!(parse(lists), expected);
+    }
+
+    #[test]
+    fn impl_function_takes_trait_cpp_name() {
+        let input = "
+        (trait Named (fn label (@ cpp-name \"Label\") (self const) () string))
+        (struct Hero (fields) (fn label (self const) () string))
+        (impl Named Hero)";
+        let env = parse(lisper::parse_str(input).unwrap()).unwrap();
+
+        let label = &env.structs["Hero"].functions["label"];
+        let cpp_name = Attribute::find(&label.attributes, "cpp-name").unwrap();
+        assert_eq!(cpp_name.string_value(), Ok("Label".to_string()));
+    }
+
+    #[test]
+    fn impl_mismatched_const_returns_err() {
+        let input = "
+        (trait Collidable (fn collides? (self const) ((in Aabb other)) bool))
+        (struct Aabb (fields) (fn collides? ((in Aabb other)) bool))
+        (impl Collidable Aabb)";
+        let lists = lisper::parse_str(input).unwrap();
+        let expected = Err(Error {
+            message: "Function 'collides?' of 'Aabb' does not match trait 'Collidable'".to_string(),
+            location: Location::default(),
+        });
+
+        assert_eq!(parse(lists), expected);
+    }
+
+    #[test]
+    fn duplicate_alias_returns_err() {
+        let lists = lisper::parse_str("(alias Id u32)\n(alias Id u64)").unwrap();
+        let expected = Err(Error {
+            message: "Duplicate alias 'Id'".to_string(),
+            location: Location::default(),
+        });
+
+        assert_eq!(parse(lists), expected);
+    }
+
+    #[test]
+    fn parse_list_constant_returns_constant() {
+        let list = make("(const MAX-LEVEL i32 100)");
+        let expected = Ok(Definition::Constant(Constant {
+            location: Location::default(),
+            name: "MAX-LEVEL".to_string(),
+            type_: (Location::default(), Type::I32),
+            value: (Location::default(), Literal::Int(100)),
+            expression: None,
+        }));
+        assert_eq!(parse_list(list), expected);
+    }
+
+    #[test]
+    fn constant_of_newtype_returns_ok() {
+        let input = "
+        (newtype Gold i32)
+        (alias Amount Gold)
+        (const STARTING-GOLD Amount 100)";
+        let lists = lisper::parse_str(input).unwrap();
+
+        let env = parse(lists).unwrap();
+
+        assert_eq!(
+            env.constants["STARTING-GOLD"].type_.1,
+            Type::Identifier("Gold".into())
+        );
+    }
+
+    #[test]
+    fn constant_of_newtype_wrong_value_returns_err() {
+        let input = "
+        (newtype Gold u8)
+        (const STARTING-GOLD Gold 1000)";
+        let lists = lisper::parse_str(input).unwrap();
+        let expected = Err(Error {
+            message: "Value 1000 does not fit in u8".to_string(),
+            location: Location::default(),
+        });
+
+        assert_eq!(parse(lists), expected);
+    }
+
+    #[test]
+    fn constant_of_struct_returns_err() {
+        let input = "
+        (struct Point)
+        (const ORIGIN Point 0)";
+        let lists = lisper::parse_str(input).unwrap();
+        let expected = Err(Error {
+            message: "Constants can not be of type 'Point'".to_string(),
+            location: Location::default(),
+        });
+
+        assert_eq!(parse(lists), expected);
+    }
+
+    #[test]
+    fn duplicate_constant_returns_err() {
+        let lists = lisper::parse_str("(const A i32 1)\n(const A i32 2)").unwrap();
+        let expected = Err(Error {
+            message: "Duplicate constant 'A'".to_string(),
+            location: Location::default(),
+        });
+
+        assert_eq!(parse(lists), expected);
+    }
+
+    #[test]
+    fn constant_expression_returns_value() {
+        let input = "
+        (const MAX-LEVEL i32 50)
+        (const MAX-EXP i32 (* MAX-LEVEL 1000))
+        (const HALF-EXP i32 (/ MAX-EXP 2))";
+        let lists = lisper::parse_str(input).unwrap();
+
+        let env = parse(lists).unwrap();
+
+        assert_eq!(env.constants["MAX-EXP"].value.1, Literal::Int(50000));
+        assert_eq!(env.constants["HALF-EXP"].value.1, Literal::Int(25000));
+    }
+
+    #[test]
+    fn constant_expression_of_newtype_returns_value() {
+        let input = "
+        (newtype Gold i64)
+        (const STARTING-GOLD Gold (* 3 1000000000))";
+        let lists = lisper::parse_str(input).unwrap();
+
+        let env = parse(lists).unwrap();
+
+        assert_eq!(
+            env.constants["STARTING-GOLD"].value.1,
+            Literal::Int(3000000000)
+        );
+    }
+
+    #[test]
+    fn constant_expression_in_module_waits_for_sibling() {
+        let input = "
+        (const BASE i32 1)
+        (module combat
+            (const ARMOR i32 (double-base))
+            (fn double-base () i32 (* BASE 2))
+            (const BASE i32 (+ 5 5)))";
+        let lists = lisper::parse_str(input).unwrap();
+
+        let env = parse(lists).unwrap();
+
+        assert_eq!(env.constants["combat/ARMOR"].value.1, Literal::Int(20));
+    }
+
+    #[test]
+    fn constant_expression_calls_function() {
+        let input = "
+        (const BASE i32 10)
+        (fn double ((i32 value)) i32 (* value 2))
+        (const LIMIT i32 (double BASE))";
+        let lists = lisper::parse_str(input).unwrap();
+
+        let env = parse(lists).unwrap();
+
+        assert_eq!(env.constants["LIMIT"].value.1, Literal::Int(20));
+    }
+
+    #[test]
+    fn constant_expression_overflow_returns_err() {
+        let input = "
+        (const MAX-LEVEL u8 50)
+        (const MAX-EXP u8 (* MAX-LEVEL 10))";
+        let lists = lisper::parse_str(input).unwrap();
+        let expected = Err(Error {
+            message: "Overflow of u8 value in constant 'MAX-EXP'".to_string(),
+            location: Location::default(),
+        });
+
+        assert_eq!(parse(lists), expected);
+    }
 
-    use super::*;
-    use lisper::Location;
+    #[test]
+    fn constant_expression_wrong_type_returns_err() {
+        let input = "
+        (const MAX-LEVEL u8 50)
+        (const MAX-EXP i32 (* MAX-LEVEL 10))";
+        let lists = lisper::parse_str(input).unwrap();
+        let expected = Err(Error {
+            message: "Expected i32, got u8 in constant 'MAX-EXP'".to_string(),
+            location: Location::default(),
+        });
 
-    fn make(contents: &str) -> List {
-        lisper::parse_str(contents)
-            .unwrap()
-            .first()
-            .unwrap()
-            .clone()
+        assert_eq!(parse(lists), expected);
     }
 
     #[test]
-    fn parse_returns_environment() {
-        let lists = lisper::parse_str("(struct foo)").unwrap();
-        let expected = Ok(Environment {
-            enums: HashMap::new(),
-            functions: HashMap::new(),
-            outputs: vec![],
-            structs: vec![(
-                "foo".to_string(),
-                Struct {
-                    location: Location::default(),
-                    name: "foo".to_string(),
-                    fields: Default::default(),
-                    functions: Default::default(),
-                },
-            )]
-            .into_iter()
-            .collect(),
+    fn constant_expression_cycle_returns_err() {
+        let input = "
+        (const A i32 (+ B 1))
+        (const B i32 (+ A 1))";
+        let lists = lisper::parse_str(input).unwrap();
+        let expected = Err(Error {
+            message: "Constant 'A' depends on its own value".to_string(),
+            location: Location::default(),
         });
-        let result = parse(lists);
 
-        assert_eq!(result, expected);
+        assert_eq!(parse(lists), expected);
     }
 
     #[test]
-    fn parse_duplicate_struct_returns_err() {
-        let list = make("(struct foo)");
+    fn field_default_expression_returns_value() {
+        let input = "
+        (const MAX-HEALTH u8 100)
+        (struct Character (fields (u8 health (- MAX-HEALTH 10)) (u8 max-health MAX-HEALTH)))";
+        let lists = lisper::parse_str(input).unwrap();
 
-        let lists = vec![list.clone(), list];
+        let env = parse(lists).unwrap();
+        let fields = &env.structs["Character"].fields;
+
+        assert_eq!(
+            fields["health"].default.as_ref().unwrap().1,
+            Literal::Int(90)
+        );
+        assert_eq!(
+            fields["max-health"].default.as_ref().unwrap().1,
+            Literal::Int(100)
+        );
+    }
 
+    #[test]
+    fn field_default_expression_wrong_type_returns_err() {
+        let input = "
+        (const NAME string \"Hero\")
+        (struct Character (fields (i32 health NAME)))";
+        let lists = lisper::parse_str(input).unwrap();
         let expected = Err(Error {
-            message: "Duplicate struct 'foo'".to_string(),
+            message: "Expected i32, got string in default of 'health'".to_string(),
             location: Location::default(),
         });
-        let result = parse(lists);
 
-        assert_eq!(result, expected);
+        assert_eq!(parse(lists), expected);
     }
 
     #[test]
-    fn parse_list_empty_returns_empty() {
-        let list = make("()");
-        let expected = Ok(Definition::Empty);
-        assert_eq!(parse_list(list), expected);
+    fn field_default_of_newtype_returns_ok() {
+        let input = "
+        (newtype Gold i32)
+        (struct Character (fields (Gold gold 100)))";
+        let lists = lisper::parse_str(input).unwrap();
+
+        let result = parse(lists);
+
+        assert!(result.is_ok());
     }
 
     #[test]
-    fn parse_list_returns_output() {
-        let list = make("(output cpp ../output output.hpp)");
-        let expected = Ok(Definition::Output(Output {
+    fn field_default_of_alias_wrong_value_returns_err() {
+        let input = "
+        (alias Level u8)
+        (struct Character (fields (Level level 1000)))";
+        let lists = lisper::parse_str(input).unwrap();
+        let expected = Err(Error {
+            message: "Value 1000 does not fit in u8".to_string(),
             location: Location::default(),
-            folder: std::path::PathBuf::from("../output"),
-            language: crate::definition::output::TargetLanguage::Cpp,
-        }));
-        assert_eq!(parse_list(list), expected);
+        });
+
+        assert_eq!(parse(lists), expected);
     }
 
     #[test]
-    fn parse_list_struct_returns_struct() {
-        let list = make("(struct foo)");
-        let expected = Ok(Definition::Struct(Struct {
+    fn field_default_of_struct_returns_err() {
+        let input = "
+        (struct Point)
+        (struct Character (fields (Point position 0)))";
+        let lists = lisper::parse_str(input).unwrap();
+        let expected = Err(Error {
+            message: "Default values are not supported for type 'Point'".to_string(),
             location: Location::default(),
-            name: "foo".to_string(),
-            fields: Default::default(),
-            functions: Default::default(),
-        }));
-        assert_eq!(parse_list(list), expected);
+        });
+
+        assert_eq!(parse(lists), expected);
     }
 
     #[test]
-    fn parse_list_enum_returns_enum() {
-        let list = make("(enum foo)");
-        let expected = Ok(Definition::Enum(Enum {
-            location: Location::default(),
-            name: "foo".to_string(),
-            variants: Default::default(),
-        }));
-        assert_eq!(parse_list(list), expected);
+    fn field_constraint_on_newtype_returns_ok() {
+        let input = "
+        (newtype Level i32)
+        (struct Character (fields (Level level (range 1 100))))";
+        let lists = lisper::parse_str(input).unwrap();
+
+        let result = parse(lists);
+
+        assert!(result.is_ok());
     }
 
     #[test]
-    fn parse_function_returns_expected() {
-        let list = make("(fn life () i64)");
-        let expected = Ok(Definition::Function(Function {
+    fn field_constraint_on_struct_returns_err() {
+        let input = "
+        (struct Point)
+        (struct Character (fields (Point position (max-len 4))))";
+        let lists = lisper::parse_str(input).unwrap();
+        let expected = Err(Error {
+            message: "max-len constraint requires a string, got 'Point'".to_string(),
             location: Location::default(),
-            name: "life".to_string(),
-            parameters: vec![],
-            return_type: (Location::default(), Type::I64),
-        }));
-        assert_eq!(parse_list(list), expected);
+        });
+
+        assert_eq!(parse(lists), expected);
     }
 
     #[test]
-    fn parse_list_unknown_returns_err() {
-        let list = make("(foo)");
+    fn generic_struct_fields_use_parameters() {
+        let input = "
+        (struct (Pair A B) (fields (A first) (B[] second)))
+        (struct Unit (fields (Pair<i32,string> ids)))";
+        let lists = lisper::parse_str(input).unwrap();
+
+        let env = parse(lists).unwrap();
+        let fields = &env.structs["Pair"].fields;
+
+        assert_eq!(fields["first"].type_, Type::Parameter("A".into()));
+        assert_eq!(
+            fields["second"].type_,
+            Type::List(Box::new(Type::Parameter("B".into())))
+        );
+        assert_eq!(
+            env.structs["Unit"].fields["ids"].type_,
+            Type::Generic("Pair".into(), vec![Type::I32, Type::String])
+        );
+    }
+
+    #[test]
+    fn generic_struct_wrong_arity_returns_err() {
+        let input = "
+        (struct (Pair A B) (fields (A first) (B second)))
+        (struct Unit (fields (Pair<i32> ids)))";
+        let lists = lisper::parse_str(input).unwrap();
         let expected = Err(Error {
-            message: "Expected enum, fn or struct, got '(foo)'".to_string(),
+            message: "Type 'Pair' expects 2 type arguments, got 1".to_string(),
             location: Location::default(),
         });
-        assert_eq!(parse_list(list), expected);
+
+        assert_eq!(parse(lists), expected);
     }
 
     #[test]
-    fn struct_has_unknown_type_returns_err() {
-        let lists = lisper::parse_str("(struct foo (fields (jaja bar)))").unwrap();
+    fn generic_struct_without_arguments_returns_err() {
+        let input = "
+        (struct (Pair A B) (fields (A first) (B second)))
+        (fn find () Pair)";
+        let lists = lisper::parse_str(input).unwrap();
         let expected = Err(Error {
-            message: "Unknown type 'jaja'".to_string(),
+            message: "Type 'Pair' expects 2 type arguments, got 0".to_string(),
             location: Location::default(),
         });
-        let result = parse(lists);
 
-        assert_eq!(result, expected);
+        assert_eq!(parse(lists), expected);
     }
 
     #[test]
-    fn enum_has_unknown_type_returns_err() {
-        let lists = lisper::parse_str("(enum foo Point (Pointz Point))").unwrap();
+    fn type_arguments_on_non_generic_returns_err() {
+        let input = "
+        (struct Point (fields (i32 x)))
+        (struct Unit (fields (Point<i32> position)))";
+        let lists = lisper::parse_str(input).unwrap();
         let expected = Err(Error {
-            message: "Unknown type 'Point'".to_string(),
+            message: "Type 'Point' expects 0 type arguments, got 1".to_string(),
             location: Location::default(),
         });
-        let result = parse(lists);
 
-        assert_eq!(result, expected);
+        assert_eq!(parse(lists), expected);
     }
 
     #[test]
-    #[ignore]
-    fn function_has_unknown_type_returns_err() {
-        let lists = lisper::parse_str("(fn life () Point point)").unwrap();
+    fn unknown_type_argument_returns_err() {
+        let input = "
+        (struct (Pair A B) (fields (A first) (B second)))
+        (struct Unit (fields (Pair<i32,Point> ids)))";
+        let lists = lisper::parse_str(input).unwrap();
         let expected = Err(Error {
             message: "Unknown type 'Point'".to_string(),
             location: Location::default(),
         });
-        let result = parse(lists);
 
-        assert_eq!(result, expected);
+        assert_eq!(parse(lists), expected);
     }
 
     #[test]
-    fn duplicate_function_returns_err() {
-        let lists = lisper::parse_str("(fn life () i64)\n(fn life () i64)").unwrap();
+    fn type_parameter_shadows_type_returns_err() {
+        let input = "
+        (struct Point (fields (i32 x)))
+        (struct (Ranged Point) (fields (Point min) (Point max)))";
+        let lists = lisper::parse_str(input).unwrap();
         let expected = Err(Error {
-            message: "Duplicate function 'life'".to_string(),
+            message: "Type parameter 'Point' has the same name as a type".to_string(),
             location: Location::default(),
         });
-        let result = parse(lists);
 
-        assert_eq!(result, expected);
+        assert_eq!(parse(lists), expected);
     }
 
     #[test]
-    fn function_references_struct_returns_ok() {
+    fn generic_enum_parameter_returns_ok() {
         let input = "
-        (struct Point (fields (i64 x) (i64 y)))
-        (fn life ((Point a)) i64)";
+        (enum (Maybe T) Nothing (Just T))
+        (struct Unit (fields (Maybe<u32> target)))";
+        let lists = lisper::parse_str(input).unwrap();
+
+        assert!(parse(lists).is_ok());
+    }
 
+    #[test]
+    fn struct_contains_itself_through_generic_returns_err() {
+        let input = "
+        (struct (Boxed T) (fields (T value)))
+        (struct Unit (fields (Boxed<Unit> inner)))";
         let lists = lisper::parse_str(input).unwrap();
+        let expected = Err(Error {
+            message: "Recursive type 'Unit' has infinite size: Unit -> Boxed -> Unit. Use an optional ('Unit?') or list ('Unit[]') to break the cycle".to_string(),
+            location: Location::default(),
+        });
 
-        let result = parse(lists);
+        assert_eq!(parse(lists), expected);
+    }
 
-        assert_eq!(result.is_ok(), true);
+    #[test]
+    fn struct_contains_itself_through_generic_list_returns_ok() {
+        let input = "
+        (struct (Boxed T) (fields (T[] values)))
+        (struct Unit (fields (Boxed<Unit> inner)))";
+        let lists = lisper::parse_str(input).unwrap();
+
+        assert!(parse(lists).is_ok());
     }
 
     #[test]
-    fn function_references_enum_returns_ok() {
+    fn monomorphize_creates_instances() {
         let input = "
-        (enum Point)
-        (fn life ((Point a)) i64)";
+        (struct (Ranged T) (fields (T min) (T max)))
+        (struct (Pair A B) (fields (A first) (B second)))
+        (struct Unit (fields (Pair<Ranged<u8>,string[]> stats)))";
+        let lists = lisper::parse_str(input).unwrap();
+
+        let env = parse(lists).unwrap().monomorphize().unwrap();
+        let mut names = env.structs.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+
+        assert_eq!(
+            names,
+            vec![
+                "Pair_10Ranged_2u8_11list6string".to_string(),
+                "Ranged_2u8".to_string(),
+                "Unit".to_string()
+            ]
+        );
+        assert_eq!(
+            env.structs["Unit"].fields["stats"].type_,
+            Type::Identifier("Pair_10Ranged_2u8_11list6string".into())
+        );
+
+        let pair = &env.structs["Pair_10Ranged_2u8_11list6string"];
+        assert_eq!(pair.type_parameters, Vec::<String>::new());
+        assert_eq!(
+            pair.fields["first"].type_,
+            Type::Identifier("Ranged_2u8".into())
+        );
+        assert_eq!(
+            pair.fields["second"].type_,
+            Type::List(Box::new(Type::String))
+        );
+        assert_eq!(env.structs["Ranged_2u8"].fields["max"].type_, Type::U8);
+    }
 
+    #[test]
+    fn monomorphize_without_end_returns_err() {
+        let input = "
+        (struct (Nested T) (fields (Nested<Nested<T>>[] children)))
+        (struct Unit (fields (Nested<i32> tree)))";
         let lists = lisper::parse_str(input).unwrap();
+        let expected = Err(Error {
+            message: "Generic type 'Nested' is instantiated without end".to_string(),
+            location: Location::default(),
+        });
 
-        let result = parse(lists);
+        assert_eq!(parse(lists).unwrap().monomorphize(), expected);
+    }
 
-        assert_eq!(result.is_ok(), true);
+    #[test]
+    fn monomorphize_instance_names_are_unambiguous() {
+        let input = "
+        (struct (Pair A B) (fields (A first) (B second)))
+        (struct A_1B (fields (i32 value)))
+        (struct Unit (fields (Pair<A_1B,i32> left) (Pair<i32,A_1B> right)))";
+        let lists = lisper::parse_str(input).unwrap();
+
+        let env = parse(lists).unwrap().monomorphize().unwrap();
+        let mut names = env.structs.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+
+        assert_eq!(
+            names,
+            vec![
+                "A_1B".to_string(),
+                "Pair_3i32_4A_1B".to_string(),
+                "Pair_4A_1B_3i32".to_string(),
+                "Unit".to_string()
+            ]
+        );
     }
 
     #[test]
-    fn struct_references_struct_returns_ok() {
+    fn monomorphize_instance_with_same_name_as_type_returns_err() {
         let input = "
-        (struct Baz (fields (foo bar)))
-        (struct foo 
-            (fields 
-                (foo bar)))";
+        (struct (Ranged T) (fields (T min) (T max)))
+        (struct Ranged_2u8 (fields (i32 value)))
+        (struct Unit (fields (Ranged<u8> range)))";
+        let lists = lisper::parse_str(input).unwrap();
+        let expected = Err(Error {
+            message: "Instance 'Ranged_2u8' of generic type 'Ranged' has the same name as a type"
+                .to_string(),
+            location: Location::default(),
+        });
+
+        assert_eq!(parse(lists).unwrap().monomorphize(), expected);
+    }
 
+    #[test]
+    fn monomorphize_instances_with_same_name_returns_err() {
+        let input = "
+        (struct (X T U) (fields (T first) (U second)))
+        (struct (X_1A T) (fields (T value)))
+        (struct A (fields (i32 value)))
+        (struct B (fields (i32 value)))
+        (struct Unit (fields (X<A,B> first) (X_1A<B> second)))";
         let lists = lisper::parse_str(input).unwrap();
 
-        let result = parse(lists);
+        let result = parse(lists).unwrap().monomorphize();
 
-        assert_eq!(result.is_ok(), true);
+        assert!(result
+            .unwrap_err()
+            .message
+            .ends_with("have the same name 'X_1A_1B'"));
     }
 
     #[test]
-    fn struct_references_enum_returns_ok() {
+    fn module_qualifies_names() {
         let input = "
-        (enum value)
-        (struct foo 
-            (fields 
-                (value bar)))";
+        (module combat
+            (struct Move (fields (Kind kind)))
+            (enum Kind Melee Ranged)
+            (fn attack ((Move move)) bool))
+        (struct Move (fields (combat/Move move)))";
+        let lists = lisper::parse_str(input).unwrap();
+
+        let env = parse(lists).unwrap();
+        let mut names = env.structs.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+
+        assert_eq!(names, vec!["Move".to_string(), "combat/Move".to_string()]);
+        assert!(env.enums.contains_key("combat/Kind"));
+        assert_eq!(
+            env.structs["combat/Move"].fields["kind"].type_,
+            Type::Identifier("combat/Kind".into())
+        );
+        assert_eq!(
+            env.functions["combat/attack"].parameters[0].type_,
+            Type::Identifier("combat/Move".into())
+        );
+        assert_eq!(
+            env.structs["Move"].fields["move"].type_,
+            Type::Identifier("combat/Move".into())
+        );
+    }
 
+    #[test]
+    fn nested_module_resolves_outer_types() {
+        let input = "
+        (module combat
+            (enum Kind Melee Ranged)
+            (module moves
+                (struct Move (fields (Kind kind) (Stats stats)))))
+        (struct Stats (fields (i32 health)))";
         let lists = lisper::parse_str(input).unwrap();
 
-        let result = parse(lists);
+        let env = parse(lists).unwrap();
+        let fields = &env.structs["combat/moves/Move"].fields;
 
-        assert_eq!(result.is_ok(), true);
+        assert_eq!(fields["kind"].type_, Type::Identifier("combat/Kind".into()));
+        assert_eq!(fields["stats"].type_, Type::Identifier("Stats".into()));
     }
 
     #[test]
-    fn enum_references_enum_returns_ok() {
+    fn module_type_without_qualifier_returns_err() {
         let input = "
-        (enum Testy (value value))
-        (enum value)";
+        (module combat (struct Move (fields (i32 damage))))
+        (struct Unit (fields (Move move)))";
+        let lists = lisper::parse_str(input).unwrap();
+        let expected = Err(Error {
+            message: "Unknown type 'Move'".to_string(),
+            location: Location::default(),
+        });
 
+        assert_eq!(parse(lists), expected);
+    }
+
+    #[test]
+    fn module_can_be_declared_more_than_once() {
+        let input = "
+        (module combat (enum Kind Melee Ranged))
+        (module combat (struct Move (fields (Kind kind))))";
         let lists = lisper::parse_str(input).unwrap();
 
-        let result = parse(lists);
+        let env = parse(lists).unwrap();
 
-        assert_eq!(result.is_ok(), true);
+        assert_eq!(
+            env.structs["combat/Move"].fields["kind"].type_,
+            Type::Identifier("combat/Kind".into())
+        );
     }
 
     #[test]
-    fn enum_references_struct_returns_ok() {
+    fn duplicate_in_module_returns_err() {
         let input = "
-        (enum Testy (value value))
-        (struct value)";
-
+        (module combat (struct Move))
+        (module combat (struct Move))";
         let lists = lisper::parse_str(input).unwrap();
+        let expected = Err(Error {
+            message: "Duplicate struct 'combat/Move'".to_string(),
+            location: Location::default(),
+        });
 
-        let result = parse(lists);
+        assert_eq!(parse(lists), expected);
+    }
 
-        assert_eq!(result.is_ok(), true);
+    #[test]
+    fn qualified_definition_name_returns_err() {
+        let input = "(struct combat/Move)";
+        let lists = lisper::parse_str(input).unwrap();
+        let expected = Err(Error {
+            message: "Invalid name 'combat/Move', declare it in a module instead".to_string(),
+            location: Location::default(),
+        });
+
+        assert_eq!(parse(lists), expected);
     }
 
     #[test]
-    fn struct_has_same_name_as_enum_returns_err() {
-        let lists = lisper::parse_str("(struct foo)\n(enum foo)").unwrap();
+    fn output_in_module_returns_err() {
+        let input = "(module combat (output cpp ../output output.hpp))";
+        let lists = lisper::parse_str(input).unwrap();
         let expected = Err(Error {
-            message: "Struct 'foo' has the same name as a enum".to_string(),
+            message: "Outputs can not be declared in a module".to_string(),
             location: Location::default(),
         });
-        let result = parse(lists);
 
-        assert_eq!(result, expected);
+        assert_eq!(parse(lists), expected);
     }
 
     // #[test]
@@ -0,0 +1,1220 @@
+use crate::{
+    definition::{
+        expression::{Expression, ExpressionKind, Operator, Pattern},
+        function::{Contract, ContractKind, Function, ParameterMode},
+        literal::Literal,
+        type_::Type,
+    },
+    env::Environment,
+};
+use lisper::{Error, Location};
+use std::collections::{BTreeMap, HashMap};
+
+/// A value built from the definitions or produced by a function body.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Void,
+    Bool(bool),
+    /// An integer along with the type that decides its range.
+    Int(i128, Type),
+    /// A float, kept at the 32-bit precision of `f32`.
+    Float(f32),
+    String(String),
+    List(Vec<Value>),
+    Optional(Option<Box<Value>>),
+    Struct(String, BTreeMap<String, Value>),
+    Variant(String, String, BTreeMap<String, Value>),
+    Flags(String, Vec<String>),
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn write_fields(
+            f: &mut std::fmt::Formatter<'_>,
+            fields: &BTreeMap<String, Value>,
+        ) -> std::fmt::Result {
+            for (name, value) in fields {
+                write!(f, " ({} {})", name, value)?;
+            }
+            Ok(())
+        }
+
+        match self {
+            Value::Void => write!(f, "void"),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Int(value, _) => write!(f, "{}", value),
+            Value::Float(value) => write!(f, "{:?}", value),
+            Value::String(value) => write!(f, "{:?}", value),
+            Value::List(items) => write!(
+                f,
+                "({})",
+                items
+                    .iter()
+                    .map(|i| i.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            Value::Optional(None) => write!(f, "none"),
+            Value::Optional(Some(value)) => write!(f, "{}", value),
+            Value::Struct(name, fields) => {
+                write!(f, "({}", name)?;
+                write_fields(f, fields)?;
+                write!(f, ")")
+            }
+            Value::Variant(name, variant, values) => {
+                write!(f, "({} {}", name, variant)?;
+                write_fields(f, values)?;
+                write!(f, ")")
+            }
+            Value::Flags(name, set) => {
+                write!(f, "({}", name)?;
+                for flag in set {
+                    write!(f, " {}", flag)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+impl Value {
+    /// Converts the value to a literal, if it is a bool, number or string.
+    pub fn to_literal(&self) -> Option<Literal> {
+        match self {
+            Value::Bool(value) => Some(Literal::Bool(*value)),
+            Value::Int(value, _) => Some(Literal::Int(*value)),
+            // Going through the shortest representation keeps `0.1` from becoming `0.10000000149011612`
+            Value::Float(value) => value.to_string().parse().ok().map(Literal::Float),
+            Value::String(value) => Some(Literal::String(value.clone())),
+            _ => None,
+        }
+    }
+}
+
+/// Why evaluation stopped early.
+enum Unwind {
+    Return(Value),
+    Error(Error),
+}
+impl From<Error> for Unwind {
+    fn from(error: Error) -> Self {
+        Unwind::Error(error)
+    }
+}
+
+/// The bindings of a function being evaluated.
+struct Frame<'a> {
+    function: &'a Function,
+    /// The qualified name calls are resolved from.
+    caller: &'a str,
+    self_value: Option<Value>,
+    scopes: Vec<HashMap<String, Value>>,
+}
+
+/// Evaluates function bodies against values built from the definitions.
+/// Bodies are expected to have passed the type checker.
+pub struct Interpreter<'a> {
+    env: &'a Environment,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(env: &'a Environment) -> Self {
+        Self { env }
+    }
+
+    /// Returns the value a field of the type holds when it is not given one.
+    pub fn default_value(&self, ty: &Type, location: &Location) -> Result<Value, Error> {
+        let value = match ty {
+            Type::Bool => Value::Bool(false),
+            Type::Float => Value::Float(0.0),
+            Type::String => Value::String(String::new()),
+            Type::List(_) => Value::List(vec![]),
+            Type::Optional(_) => Value::Optional(None),
+            Type::Identifier(name) => {
+                if let Some(s) = self.env.structs.get(name) {
+                    let mut fields = BTreeMap::new();
+                    for field in s.fields.values() {
+                        let value = match &field.default {
+                            Some((location, literal)) => literal_value(
+                                literal,
+                                &self.env.underlying_type(&field.type_),
+                                location,
+                            )?,
+                            None => self.default_value(&field.type_, &field.location)?,
+                        };
+                        fields.insert(field.name.clone(), value);
+                    }
+                    Value::Struct(name.clone(), fields)
+                } else if let Some(e) = self.env.enums.get(name) {
+                    let variant = e.ordered_variants()[0];
+                    let mut values = BTreeMap::new();
+                    for value in variant.values.values() {
+                        values.insert(
+                            value.name.clone(),
+                            self.default_value(&value.type_, &value.location)?,
+                        );
+                    }
+                    Value::Variant(name.clone(), variant.name.clone(), values)
+                } else if let Some(n) = self.env.newtypes.get(name) {
+                    self.default_value(&n.type_.1, location)?
+                } else if self.env.flags.contains_key(name) {
+                    Value::Flags(name.clone(), vec![])
+                } else {
+                    return Err(Error {
+                        message: format!("Unknown type '{}'", name),
+                        location: location.clone(),
+                    });
+                }
+            }
+            ty if Literal::int_range(ty).is_some() => Value::Int(0, ty.clone()),
+            ty => {
+                return Err(Error {
+                    message: format!("Values of type '{}' can not be evaluated", ty),
+                    location: location.clone(),
+                })
+            }
+        };
+
+        Ok(value)
+    }
+
+    /// Evaluates an expression outside of any function, with the given bindings.
+    /// Names are resolved from the caller. Literals take the expected type when one is given.
+    /// `let` adds to the bindings so they can be used by later expressions.
+    pub fn evaluate(
+        &self,
+        caller: &str,
+        expression: &Expression,
+        expected: Option<&Type>,
+        bindings: &mut HashMap<String, Value>,
+    ) -> Result<Value, Error> {
+        let function = Function {
+            location: expression.location.clone(),
+            name: String::new(),
+            parameters: vec![],
+            return_type: (expression.location.clone(), Type::Void),
+            is_const: false,
+            is_static: false,
+            body: vec![],
+            requires: vec![],
+            ensures: vec![],
+            doc: None,
+            attributes: vec![],
+        };
+
+        let mut frame = Frame {
+            function: &function,
+            caller,
+            self_value: None,
+            scopes: vec![std::mem::take(bindings)],
+        };
+        let result = self.eval(&mut frame, expression, expected);
+        *bindings = frame.scopes.remove(0);
+
+        match result {
+            Ok(value) => Ok(value),
+            Err(Unwind::Return(_)) => Err(Error {
+                message: "Can not return outside of a function".into(),
+                location: expression.location.clone(),
+            }),
+            Err(Unwind::Error(error)) => Err(error),
+        }
+    }
+
+    /// Calls the named function. Arguments passed to `mut` and `out` parameters are updated in place.
+    pub fn call(
+        &self,
+        name: &str,
+        arguments: &mut [Value],
+        location: &Location,
+    ) -> Result<Value, Error> {
+        let f = match self.env.functions.get(name) {
+            Some(f) => f,
+            None => {
+                return Err(Error {
+                    message: format!("Unknown function '{}'", name),
+                    location: location.clone(),
+                })
+            }
+        };
+
+        self.call_function(f, &f.name, None, arguments, location)
+    }
+
+    /// Calls a function of the struct the value holds. A non-const function may modify the value.
+    pub fn call_method(
+        &self,
+        value: &mut Value,
+        name: &str,
+        arguments: &mut [Value],
+        location: &Location,
+    ) -> Result<Value, Error> {
+        let s = match value {
+            Value::Struct(struct_name, _) => self.env.structs.get(struct_name.as_str()),
+            _ => None,
+        };
+
+        let (s, f) = match s.and_then(|s| s.functions.get(name).map(|f| (s, f))) {
+            Some(found) => found,
+            None => {
+                return Err(Error {
+                    message: format!("Unknown function '{}' of '{}'", name, value),
+                    location: location.clone(),
+                })
+            }
+        };
+
+        if f.is_static {
+            return self.call_function(f, &s.name, None, arguments, location);
+        }
+
+        self.call_function(f, &s.name, Some(value), arguments, location)
+    }
+
+    /// Calls a static function of the named struct.
+    pub fn call_static(
+        &self,
+        struct_name: &str,
+        name: &str,
+        arguments: &mut [Value],
+        location: &Location,
+    ) -> Result<Value, Error> {
+        let f = self
+            .env
+            .structs
+            .get(struct_name)
+            .and_then(|s| s.functions.get(name))
+            .filter(|f| f.is_static);
+        match f {
+            Some(f) => self.call_function(f, struct_name, None, arguments, location),
+            None => Err(Error {
+                message: format!("Unknown static function '{}' of '{}'", name, struct_name),
+                location: location.clone(),
+            }),
+        }
+    }
+
+    fn call_function(
+        &self,
+        f: &'a Function,
+        caller: &'a str,
+        self_value: Option<&mut Value>,
+        arguments: &mut [Value],
+        location: &Location,
+    ) -> Result<Value, Error> {
+        if f.body.is_empty() {
+            return Err(Error {
+                message: format!("Function '{}' has no body to evaluate", f.name),
+                location: location.clone(),
+            });
+        }
+
+        if f.parameters.len() != arguments.len() {
+            return Err(Error {
+                message: format!(
+                    "Function '{}' takes {} arguments, got {}",
+                    f.name,
+                    f.parameters.len(),
+                    arguments.len()
+                ),
+                location: location.clone(),
+            });
+        }
+
+        let bindings = f
+            .parameters
+            .iter()
+            .zip(arguments.iter())
+            .map(|(p, a)| (p.name.clone(), a.clone()))
+            .collect();
+        let mut frame = Frame {
+            function: f,
+            caller,
+            self_value: self_value.as_ref().map(|v| (*v).clone()),
+            scopes: vec![bindings],
+        };
+        self.check_contracts(&mut frame, ContractKind::Requires, &f.requires)?;
+
+        let mut result = Value::Void;
+        for expression in f.body.iter() {
+            match self.eval(&mut frame, expression, Some(&f.return_type.1)) {
+                Ok(value) => result = value,
+                Err(Unwind::Return(value)) => {
+                    result = value;
+                    break;
+                }
+                Err(Unwind::Error(error)) => return Err(error),
+            }
+        }
+
+        if !f.ensures.is_empty() {
+            let mut scope = HashMap::new();
+            if f.return_type.1 != Type::Void {
+                scope.insert("result".to_string(), result.clone());
+            }
+            frame.scopes.push(scope);
+            self.check_contracts(&mut frame, ContractKind::Ensures, &f.ensures)?;
+            frame.scopes.pop();
+        }
+
+        // Write back the parameters the function may modify
+        for (parameter, argument) in f.parameters.iter().zip(arguments.iter_mut()) {
+            if let Some(ParameterMode::Mut | ParameterMode::Out) = parameter.mode {
+                *argument = frame.scopes[0][&parameter.name].clone();
+            }
+        }
+        if let (Some(self_value), Some(value)) = (self_value, frame.self_value) {
+            if !f.is_const {
+                *self_value = value;
+            }
+        }
+
+        match f.return_type.1 {
+            Type::Void => Ok(Value::Void),
+            _ => Ok(result),
+        }
+    }
+
+    /// Evaluates the conditions of a contract, failing on the first that does not hold.
+    fn check_contracts(
+        &self,
+        frame: &mut Frame<'a>,
+        kind: ContractKind,
+        contracts: &[Contract],
+    ) -> Result<(), Error> {
+        for contract in contracts.iter() {
+            match self.eval(frame, &contract.condition, Some(&Type::Bool)) {
+                Ok(Value::Bool(true)) => {}
+                Ok(_) => {
+                    return Err(Error {
+                        message: format!(
+                            "{} '{}' of function '{}' failed",
+                            kind.describe(),
+                            contract.source,
+                            frame.function.name
+                        ),
+                        location: contract.location.clone(),
+                    })
+                }
+                Err(Unwind::Return(_)) => {
+                    return Err(Error {
+                        message: "Can not return from a condition".into(),
+                        location: contract.location.clone(),
+                    })
+                }
+                Err(Unwind::Error(error)) => return Err(error),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn eval(
+        &self,
+        frame: &mut Frame<'a>,
+        expression: &Expression,
+        expected: Option<&Type>,
+    ) -> Result<Value, Unwind> {
+        let location = &expression.location;
+        let value = match &expression.kind {
+            ExpressionKind::Literal(literal) => {
+                let ty = match (literal, expected) {
+                    (Literal::Int(_), Some(ty)) if is_number(ty) => ty.clone(),
+                    (Literal::Int(_), _) => Type::I32,
+                    (Literal::Float(_), _) => Type::Float,
+                    (Literal::Bool(_), _) => Type::Bool,
+                    (Literal::String(_), _) => Type::String,
+                };
+                literal_value(literal, &ty, location)?
+            }
+            ExpressionKind::Variable(name) => self.lookup(frame, name, location)?,
+            ExpressionKind::Let(name, value) => {
+                let value = self.eval(frame, value, None)?;
+                frame.scopes.last_mut().unwrap().insert(name.clone(), value);
+                Value::Void
+            }
+            ExpressionKind::Set(target, value) => {
+                let expected = value_type(&self.eval(frame, target, None)?);
+                let value = self.eval(frame, value, expected.as_ref())?;
+                *self.place(frame, target)? = value;
+                Value::Void
+            }
+            ExpressionKind::Get(value, field) => {
+                let value = self.eval(frame, value, None)?;
+                get_field(value, field, location)?
+            }
+            ExpressionKind::Operation(operator, operands) => {
+                self.eval_operation(frame, *operator, operands, expected, location)?
+            }
+            ExpressionKind::If(condition, then, otherwise) => {
+                match (self.eval_bool(frame, condition)?, otherwise) {
+                    (true, _) => self.eval_scoped(frame, then, expected)?,
+                    (false, Some(otherwise)) => self.eval_scoped(frame, otherwise, expected)?,
+                    (false, None) => Value::Void,
+                }
+            }
+            ExpressionKind::Cond(clauses, otherwise) => {
+                let mut result = None;
+                for (condition, body) in clauses.iter() {
+                    if self.eval_bool(frame, condition)? {
+                        result = Some(self.eval_scoped(frame, body, expected)?);
+                        break;
+                    }
+                }
+
+                match (result, otherwise) {
+                    (Some(value), _) => value,
+                    (None, Some(otherwise)) => self.eval_scoped(frame, otherwise, expected)?,
+                    (None, None) => Value::Void,
+                }
+            }
+            ExpressionKind::For(item, items, body) => {
+                let items = match self.eval(frame, items, None)? {
+                    Value::List(items) => items,
+                    value => return Err(unexpected("list", &value, &items.location)),
+                };
+
+                for value in items {
+                    frame.scopes.push(HashMap::from([(item.clone(), value)]));
+                    let result = self.eval(frame, body, None);
+                    frame.scopes.pop();
+                    result?;
+                }
+                Value::Void
+            }
+            ExpressionKind::Do(body) => {
+                frame.scopes.push(HashMap::new());
+                let mut result = Ok(Value::Void);
+                for (index, e) in body.iter().enumerate() {
+                    let expected = expected.filter(|_| index == body.len() - 1);
+                    result = self.eval(frame, e, expected);
+                    if result.is_err() {
+                        break;
+                    }
+                }
+                frame.scopes.pop();
+                result?
+            }
+            ExpressionKind::Return(value) => {
+                let value = match value {
+                    Some(value) => self.eval(frame, value, Some(&frame.function.return_type.1))?,
+                    None => Value::Void,
+                };
+                return Err(Unwind::Return(value));
+            }
+            ExpressionKind::Call(name, arguments) => {
+                self.eval_call(frame, name, arguments, location)?
+            }
+            ExpressionKind::Construct {
+                type_,
+                variant,
+                fields,
+            } => {
+                let mut values = vec![];
+                for (name, value) in fields.iter() {
+                    let field_type = self.get_construct_field_type(type_, variant, name);
+                    let field_type = match field_type {
+                        Some(Type::Optional(inner)) => Some(*inner),
+                        ty => ty,
+                    };
+                    values.push((name.clone(), self.eval(frame, value, field_type.as_ref())?));
+                }
+                self.construct(type_, variant.as_deref(), values, location)?
+            }
+            ExpressionKind::Match(value, arms) => {
+                let (variant, mut values) = match self.eval(frame, value, None)? {
+                    Value::Variant(_, variant, values) => (variant, values),
+                    other => return Err(unexpected("enum", &other, &value.location)),
+                };
+
+                let arm = arms.iter().find(|arm| match &arm.pattern {
+                    Pattern::Variant(name, _) => *name == variant,
+                    Pattern::Wildcard => true,
+                });
+                let arm = match arm {
+                    Some(arm) => arm,
+                    None => {
+                        return Err(Unwind::Error(Error {
+                            message: format!("No arm matches variant '{}'", variant),
+                            location: location.clone(),
+                        }))
+                    }
+                };
+
+                let mut scope = HashMap::new();
+                if let Pattern::Variant(_, bindings) = &arm.pattern {
+                    for binding in bindings {
+                        if let Some(value) = values.remove(binding) {
+                            scope.insert(binding.clone(), value);
+                        }
+                    }
+                }
+
+                frame.scopes.push(scope);
+                let result = self.eval(frame, &arm.body, expected);
+                frame.scopes.pop();
+                result?
+            }
+        };
+
+        Ok(value)
+    }
+
+    /// Builds a struct or enum variant. Fields that are not given take their default value.
+    pub fn construct(
+        &self,
+        ty: &Type,
+        variant: Option<&str>,
+        fields: Vec<(String, Value)>,
+        location: &Location,
+    ) -> Result<Value, Error> {
+        let name = ty.to_string();
+        let (mut value, declared) = match (
+            self.env.structs.get(&name),
+            self.env.enums.get(&name),
+            variant,
+        ) {
+            (Some(s), _, None) => (self.default_value(ty, location)?, &s.fields),
+            (None, Some(e), Some(variant)) => match e.variants.get(variant) {
+                Some(v) => {
+                    let mut values = BTreeMap::new();
+                    for value in v.values.values() {
+                        values.insert(
+                            value.name.clone(),
+                            self.default_value(&value.type_, &value.location)?,
+                        );
+                    }
+                    (
+                        Value::Variant(name.clone(), variant.to_string(), values),
+                        &v.values,
+                    )
+                }
+                None => {
+                    return Err(Error {
+                        message: format!("Unknown variant '{}' of '{}'", variant, name),
+                        location: location.clone(),
+                    })
+                }
+            },
+            _ => {
+                return Err(Error {
+                    message: format!(
+                        "Expected struct or enum variant to construct, got '{}'",
+                        name
+                    ),
+                    location: location.clone(),
+                })
+            }
+        };
+
+        let slots = match &mut value {
+            Value::Struct(_, slots) | Value::Variant(_, _, slots) => slots,
+            _ => unreachable!(),
+        };
+        for (field, field_value) in fields {
+            let declared = match declared.get(&field) {
+                Some(declared) => declared,
+                None => {
+                    return Err(Error {
+                        message: format!("Unknown field '{}' of '{}'", field, name),
+                        location: location.clone(),
+                    })
+                }
+            };
+
+            let field_value = match (&declared.type_, field_value) {
+                (Type::Optional(_), Value::Optional(value)) => Value::Optional(value),
+                (Type::Optional(_), value) => Value::Optional(Some(Box::new(value))),
+                (_, value) => value,
+            };
+            slots.insert(field, field_value);
+        }
+
+        Ok(value)
+    }
+
+    fn get_construct_field_type(
+        &self,
+        ty: &Type,
+        variant: &Option<String>,
+        field: &str,
+    ) -> Option<Type> {
+        let name = ty.to_string();
+        let fields = match variant {
+            None => &self.env.structs.get(&name)?.fields,
+            Some(variant) => &self.env.enums.get(&name)?.variants.get(variant)?.values,
+        };
+        fields
+            .get(field)
+            .map(|f| self.env.underlying_type(&f.type_))
+    }
+
+    fn eval_scoped(
+        &self,
+        frame: &mut Frame<'a>,
+        expression: &Expression,
+        expected: Option<&Type>,
+    ) -> Result<Value, Unwind> {
+        frame.scopes.push(HashMap::new());
+        let result = self.eval(frame, expression, expected);
+        frame.scopes.pop();
+        result
+    }
+
+    fn eval_bool(&self, frame: &mut Frame<'a>, expression: &Expression) -> Result<bool, Unwind> {
+        match self.eval(frame, expression, Some(&Type::Bool))? {
+            Value::Bool(value) => Ok(value),
+            value => Err(unexpected("bool", &value, &expression.location)),
+        }
+    }
+
+    fn lookup(&self, frame: &Frame<'a>, name: &str, location: &Location) -> Result<Value, Error> {
+        if let Some(value) = frame.scopes.iter().rev().find_map(|s| s.get(name)) {
+            return Ok(value.clone());
+        }
+
+        if name == "self" {
+            if let Some(value) = &frame.self_value {
+                return Ok(value.clone());
+            }
+        }
+
+        if let Some(constant) = self.env.resolve_constant(frame.caller, name) {
+            if constant.expression.is_some() {
+                return Err(Error {
+                    message: format!(
+                        "Constant '{}' is read before its value is known",
+                        constant.name
+                    ),
+                    location: location.clone(),
+                });
+            }
+            let ty = self.env.underlying_type(&constant.type_.1);
+            return literal_value(&constant.value.1, &ty, &constant.value.0);
+        }
+
+        Err(Error {
+            message: format!("Unknown identifier '{}'", name),
+            location: location.clone(),
+        })
+    }
+
+    /// Returns the binding or field a `set` assigns to.
+    fn place<'f>(
+        &self,
+        frame: &'f mut Frame<'a>,
+        target: &Expression,
+    ) -> Result<&'f mut Value, Error> {
+        match &target.kind {
+            ExpressionKind::Variable(name) => {
+                if let Some(scope) = frame.scopes.iter_mut().rev().find(|s| s.contains_key(name)) {
+                    return Ok(scope.get_mut(name).unwrap());
+                }
+
+                match (name.as_str(), &mut frame.self_value) {
+                    ("self", Some(value)) => Ok(value),
+                    _ => Err(Error {
+                        message: format!("Can not set '{}'", name),
+                        location: target.location.clone(),
+                    }),
+                }
+            }
+            ExpressionKind::Get(value, field) => match self.place(frame, value)? {
+                Value::Struct(_, fields) => match fields.get_mut(field) {
+                    Some(value) => Ok(value),
+                    None => Err(Error {
+                        message: format!("Unknown field '{}'", field),
+                        location: target.location.clone(),
+                    }),
+                },
+                value => Err(unexpected_error("struct", value, &target.location)),
+            },
+            _ => Err(Error {
+                message: "Expected binding or field to set".into(),
+                location: target.location.clone(),
+            }),
+        }
+    }
+
+    fn eval_call(
+        &self,
+        frame: &mut Frame<'a>,
+        name: &str,
+        arguments: &[Expression],
+        location: &Location,
+    ) -> Result<Value, Unwind> {
+        let f = match self.env.resolve_function(frame.caller, name) {
+            Some(f) => f,
+            None => {
+                return Err(Unwind::Error(Error {
+                    message: format!("Unknown function '{}'", name),
+                    location: location.clone(),
+                }))
+            }
+        };
+
+        let mut values = vec![];
+        for (index, argument) in arguments.iter().enumerate() {
+            let expected = f
+                .parameters
+                .get(index)
+                .map(|p| self.env.underlying_type(&p.type_));
+            values.push(self.eval(frame, argument, expected.as_ref())?);
+        }
+
+        let result = self.call(&f.name, &mut values, location)?;
+
+        // Modified arguments are written back to the bindings or fields they came from
+        for ((parameter, argument), value) in f.parameters.iter().zip(arguments).zip(values) {
+            if let Some(ParameterMode::Mut | ParameterMode::Out) = parameter.mode {
+                *self.place(frame, argument)? = value;
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn eval_operation(
+        &self,
+        frame: &mut Frame<'a>,
+        operator: Operator,
+        operands: &[Expression],
+        expected: Option<&Type>,
+        location: &Location,
+    ) -> Result<Value, Unwind> {
+        match operator {
+            Operator::Not => return Ok(Value::Bool(!self.eval_bool(frame, &operands[0])?)),
+            Operator::And => {
+                for operand in operands {
+                    if !self.eval_bool(frame, operand)? {
+                        return Ok(Value::Bool(false));
+                    }
+                }
+                return Ok(Value::Bool(true));
+            }
+            Operator::Or => {
+                for operand in operands {
+                    if self.eval_bool(frame, operand)? {
+                        return Ok(Value::Bool(true));
+                    }
+                }
+                return Ok(Value::Bool(false));
+            }
+            _ => {}
+        }
+
+        // Literals take the type of the first operand that is not a literal, matching the type checker
+        let mut values = vec![None; operands.len()];
+        let mut operand_type = None;
+        for (index, operand) in operands.iter().enumerate() {
+            if !matches!(operand.kind, ExpressionKind::Literal(_)) {
+                let value = self.eval(frame, operand, None)?;
+                operand_type = operand_type.or_else(|| value_type(&value));
+                values[index] = Some(value);
+            }
+        }
+        let operand_type = operand_type.or_else(|| {
+            expected
+                .filter(|ty| is_number(ty) && !operator.is_comparison())
+                .cloned()
+        });
+        let mut evaluated = vec![];
+        for (operand, value) in operands.iter().zip(values) {
+            match value {
+                Some(value) => evaluated.push(value),
+                None => evaluated.push(self.eval(frame, operand, operand_type.as_ref())?),
+            }
+        }
+
+        let result = match (operator, evaluated.as_slice()) {
+            (Operator::Equal, [a, b]) => Value::Bool(a == b),
+            (Operator::NotEqual, [a, b]) => Value::Bool(a != b),
+            (Operator::Less, [a, b]) => Value::Bool(compare(a, b, location)?.is_lt()),
+            (Operator::LessEqual, [a, b]) => Value::Bool(compare(a, b, location)?.is_le()),
+            (Operator::Greater, [a, b]) => Value::Bool(compare(a, b, location)?.is_gt()),
+            (Operator::GreaterEqual, [a, b]) => Value::Bool(compare(a, b, location)?.is_ge()),
+            (Operator::Subtract, [value]) => match value {
+                Value::Int(value, ty) => checked_int(0i128.checked_sub(*value), ty, location)?,
+                Value::Float(value) => Value::Float(-value),
+                value => return Err(unexpected("number", value, location)),
+            },
+            (operator, values) => {
+                let mut result = values[0].clone();
+                for value in values[1..].iter() {
+                    result = arithmetic(operator, &result, value, location)?;
+                }
+                result
+            }
+        };
+
+        Ok(result)
+    }
+}
+
+/// Applies an arithmetic operator, failing on overflow of the integer type and division by zero.
+fn arithmetic(
+    operator: Operator,
+    a: &Value,
+    b: &Value,
+    location: &Location,
+) -> Result<Value, Error> {
+    match (a, b) {
+        (Value::Int(a, ty), Value::Int(b, _)) => {
+            if *b == 0 && matches!(operator, Operator::Divide | Operator::Remainder) {
+                return Err(Error {
+                    message: "Division by zero".into(),
+                    location: location.clone(),
+                });
+            }
+
+            let result = match operator {
+                Operator::Add => a.checked_add(*b),
+                Operator::Subtract => a.checked_sub(*b),
+                Operator::Multiply => a.checked_mul(*b),
+                Operator::Divide => a.checked_div(*b),
+                Operator::Remainder => a.checked_rem(*b),
+                _ => None,
+            };
+            checked_int(result, ty, location)
+        }
+        (Value::Float(a), Value::Float(b)) => {
+            let result = match operator {
+                Operator::Add => a + b,
+                Operator::Subtract => a - b,
+                Operator::Multiply => a * b,
+                Operator::Divide => a / b,
+                Operator::Remainder => a % b,
+                _ => f32::NAN,
+            };
+            Ok(Value::Float(result))
+        }
+        (value, _) => Err(unexpected_error("number", value, location)),
+    }
+}
+
+/// Ensures the result of an integer operation fits in its type.
+fn checked_int(result: Option<i128>, ty: &Type, location: &Location) -> Result<Value, Error> {
+    let (min, max) = Literal::int_range(ty).unwrap_or((i128::MIN, i128::MAX));
+    match result {
+        Some(value) if value >= min && value <= max => Ok(Value::Int(value, ty.clone())),
+        _ => Err(Error {
+            message: format!("Overflow of {} value", ty),
+            location: location.clone(),
+        }),
+    }
+}
+
+fn compare(a: &Value, b: &Value, location: &Location) -> Result<std::cmp::Ordering, Error> {
+    match (a, b) {
+        (Value::Int(a, _), Value::Int(b, _)) => Ok(a.cmp(b)),
+        (Value::Float(a), Value::Float(b)) => Ok(a.total_cmp(b)),
+        (value, _) => Err(unexpected_error("number", value, location)),
+    }
+}
+
+fn get_field(value: Value, field: &str, location: &Location) -> Result<Value, Error> {
+    match value {
+        Value::Struct(name, mut fields) => match fields.remove(field) {
+            Some(value) => Ok(value),
+            None => Err(Error {
+                message: format!("Unknown field '{}' of '{}'", field, name),
+                location: location.clone(),
+            }),
+        },
+        value => Err(unexpected_error("struct", &value, location)),
+    }
+}
+
+/// Converts a literal to a value of the given type.
+fn literal_value(literal: &Literal, ty: &Type, location: &Location) -> Result<Value, Error> {
+    literal.type_check(ty, location.clone())?;
+    let value = match (literal, ty) {
+        (Literal::Bool(value), _) => Value::Bool(*value),
+        (Literal::String(value), _) => Value::String(value.clone()),
+        (Literal::Float(value), _) => Value::Float(*value as f32),
+        (Literal::Int(value), Type::Float) => Value::Float(*value as f32),
+        (Literal::Int(value), ty) => Value::Int(*value, ty.clone()),
+    };
+    Ok(value)
+}
+
+/// Returns the numeric type of the value, if it is a number.
+fn value_type(value: &Value) -> Option<Type> {
+    match value {
+        Value::Int(_, ty) => Some(ty.clone()),
+        Value::Float(_) => Some(Type::Float),
+        _ => None,
+    }
+}
+
+fn is_number(ty: &Type) -> bool {
+    *ty == Type::Float || Literal::int_range(ty).is_some()
+}
+
+fn unexpected_error(expected: &str, value: &Value, location: &Location) -> Error {
+    Error {
+        message: format!("Expected {}, got '{}'", expected, value),
+        location: location.clone(),
+    }
+}
+
+fn unexpected(expected: &str, value: &Value, location: &Location) -> Unwind {
+    Unwind::Error(unexpected_error(expected, value, location))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_env(input: &str) -> Environment {
+        Environment::parse(lisper::parse_str(input).unwrap()).unwrap()
+    }
+
+    fn call(env: &Environment, name: &str, arguments: &mut [Value]) -> Result<Value, Error> {
+        Interpreter::new(env).call(name, arguments, &Location::default())
+    }
+
+    fn int(value: i128) -> Value {
+        Value::Int(value, Type::I32)
+    }
+
+    const CHARACTER: &str = "(struct Position (fields (i32 x) (i32 y)))
+        (struct Character (fields (i32 health 10) (u8 level) (Position position) (i32[] scores) (Position? target))
+            (fn hurt ((in i32 amount)) void (set (get self health) (- (get self health) amount)))
+            (fn alive? (self const) () bool (> (get self health) 0))
+            (static-fn named () Character (Character (level 2))))
+        (enum Shape (Circle (f32 radius)) (Square (f32 side)))
+        (const MAX-LEVEL u8 50)";
+
+    #[test]
+    fn default_struct_uses_field_defaults() {
+        let env = make_env(CHARACTER);
+        let value = Interpreter::new(&env)
+            .default_value(&Type::Identifier("Character".into()), &Location::default())
+            .unwrap();
+
+        assert_eq!(
+            value.to_string(),
+            "(Character (health 10) (level 0) (position (Position (x 0) (y 0))) (scores ()) (target none))"
+        );
+    }
+
+    #[test]
+    fn call_evaluates_arithmetic() {
+        let env = make_env("(fn area ((i32 w) (i32 h)) i32 (let a (* w h)) (+ a 1))");
+        let result = call(&env, "area", &mut [int(3), int(4)]);
+        assert_eq!(result, Ok(int(13)));
+    }
+
+    #[test]
+    fn call_reads_constant_of_its_module() {
+        let env = make_env(
+            "(const MAX-HIT i32 1)
+            (module combat
+                (const MAX-HIT i32 99)
+                (fn cap ((i32 damage)) i32 (if (> damage MAX-HIT) MAX-HIT damage)))",
+        );
+        assert_eq!(call(&env, "combat/cap", &mut [int(120)]), Ok(int(99)));
+        assert_eq!(call(&env, "combat/cap", &mut [int(20)]), Ok(int(20)));
+    }
+
+    #[test]
+    fn call_reading_pending_constant_returns_err() {
+        let mut env = make_env("(const MAX-HIT i32 99) (fn cap () i32 MAX-HIT)");
+        let constant = env.constants.get_mut("MAX-HIT").unwrap();
+        constant.expression = Some(Expression {
+            location: Location::default(),
+            kind: ExpressionKind::Literal(Literal::Int(99)),
+        });
+
+        let result = call(&env, "cap", &mut []);
+        assert_eq!(
+            result,
+            Err("Constant 'MAX-HIT' is read before its value is known".into())
+        );
+    }
+
+    #[test]
+    fn call_evaluates_cond() {
+        let env = make_env("(fn sign ((i32 n)) i32 (cond ((< n 0) -1) ((= n 0) 0) (else 1)))");
+        assert_eq!(call(&env, "sign", &mut [int(-5)]), Ok(int(-1)));
+        assert_eq!(call(&env, "sign", &mut [int(0)]), Ok(int(0)));
+        assert_eq!(call(&env, "sign", &mut [int(7)]), Ok(int(1)));
+    }
+
+    #[test]
+    fn call_early_return_stops_evaluation() {
+        let env = make_env("(fn clamp ((i32 n)) i32 (if (> n 10) (return 10)) n)");
+        assert_eq!(call(&env, "clamp", &mut [int(50)]), Ok(int(10)));
+        assert_eq!(call(&env, "clamp", &mut [int(5)]), Ok(int(5)));
+    }
+
+    #[test]
+    fn call_iterates_list() {
+        let env = make_env(
+            "(fn total ((in i32[] items)) i32 (let sum 0) (for i items (set sum (+ sum i))) sum)",
+        );
+        let items = Value::List(vec![int(1), int(2), int(3)]);
+        assert_eq!(call(&env, "total", &mut [items]), Ok(int(6)));
+    }
+
+    #[test]
+    fn call_overflow_returns_err() {
+        let env = make_env("(fn next ((u8 level)) u8 (+ level 1))");
+        let result = call(&env, "next", &mut [Value::Int(255, Type::U8)]);
+        assert_eq!(result, Err("Overflow of u8 value".into()));
+    }
+
+    #[test]
+    fn call_division_by_zero_returns_err() {
+        let env = make_env("(fn div ((i32 a) (i32 b)) i32 (/ a b))");
+        let result = call(&env, "div", &mut [int(1), int(0)]);
+        assert_eq!(result, Err("Division by zero".into()));
+    }
+
+    #[test]
+    fn call_failed_precondition_returns_err() {
+        let env = make_env("(fn div ((i32 a) (i32 b)) i32 (requires (!= b 0)) (/ a b))");
+        let result = call(&env, "div", &mut [int(1), int(0)]);
+        assert_eq!(
+            result,
+            Err("Precondition '(!= b 0)' of function 'div' failed".into())
+        );
+    }
+
+    #[test]
+    fn call_checks_postcondition_on_result() {
+        let env = make_env(
+            "(fn clamp ((i32 n)) i32 (ensures (<= result 10)) (if (> n 20) (return n)) (if (> n 10) 10 n))",
+        );
+        assert_eq!(call(&env, "clamp", &mut [int(15)]), Ok(int(10)));
+        assert_eq!(
+            call(&env, "clamp", &mut [int(30)]),
+            Err("Postcondition '(<= result 10)' of function 'clamp' failed".into())
+        );
+    }
+
+    #[test]
+    fn call_float_arithmetic() {
+        let env = make_env("(fn half ((f32 a)) f32 (/ a 2))");
+        let result = call(&env, "half", &mut [Value::Float(3.0)]);
+        assert_eq!(result, Ok(Value::Float(1.5)));
+    }
+
+    #[test]
+    fn call_writes_back_mut_and_out_parameters() {
+        let env = make_env(
+            "(fn split ((in i32 n) (mut i32 acc) (out i32 half)) void (set acc (+ acc n)) (set half (/ n 2)))
+            (fn run () i32 (let a 1) (let h 0) (split 10 a h) (+ a h))",
+        );
+        let mut arguments = [int(10), int(1), int(0)];
+        call(&env, "split", &mut arguments).unwrap();
+        assert_eq!(arguments, [int(10), int(11), int(5)]);
+
+        assert_eq!(call(&env, "run", &mut []), Ok(int(16)));
+    }
+
+    #[test]
+    fn call_reads_constants() {
+        let input = format!(
+            "{}\n(fn capped ((u8 level)) bool (< level MAX-LEVEL))",
+            CHARACTER
+        );
+        let env = make_env(&input);
+        let result = call(&env, "capped", &mut [Value::Int(60, Type::U8)]);
+        assert_eq!(result, Ok(Value::Bool(false)));
+    }
+
+    #[test]
+    fn call_constructs_values() {
+        let input = format!(
+            "{}\n(fn spawn ((i32 x)) Character (Character (position (Position (x x))) (target (Position (y 2)))))
+            (fn circle () Shape (Shape Circle (radius 2)))",
+            CHARACTER
+        );
+        let env = make_env(&input);
+
+        let result = call(&env, "spawn", &mut [int(4)]).unwrap();
+        assert_eq!(
+            result.to_string(),
+            "(Character (health 10) (level 0) (position (Position (x 4) (y 0))) (scores ()) (target (Position (x 0) (y 2))))"
+        );
+
+        let result = call(&env, "circle", &mut []).unwrap();
+        assert_eq!(result.to_string(), "(Shape Circle (radius 2.0))");
+    }
+
+    #[test]
+    fn call_method_modifies_self() {
+        let env = make_env(CHARACTER);
+        let interpreter = Interpreter::new(&env);
+        let location = Location::default();
+        let mut character = interpreter
+            .call_static("Character", "named", &mut [], &location)
+            .unwrap();
+
+        interpreter
+            .call_method(&mut character, "hurt", &mut [int(4)], &location)
+            .unwrap();
+        let alive = interpreter.call_method(&mut character, "alive?", &mut [], &location);
+
+        assert_eq!(alive, Ok(Value::Bool(true)));
+        assert_eq!(get_field(character, "health", &location), Ok(int(6)));
+    }
+
+    #[test]
+    fn call_function_without_body_returns_err() {
+        let env = make_env("(fn custom () i32)");
+        assert_eq!(
+            call(&env, "custom", &mut []),
+            Err("Function 'custom' has no body to evaluate".into())
+        );
+    }
+
+    #[test]
+    fn evaluate_keeps_let_bindings() {
+        let env = make_env(CHARACTER);
+        let interpreter = Interpreter::new(&env);
+        let mut bindings = HashMap::new();
+        let parse = |input: &str| {
+            let mut list = lisper::parse_str(&format!("({})", input)).unwrap()[0].clone();
+            Expression::pop(&mut list).unwrap()
+        };
+
+        interpreter
+            .evaluate(
+                "",
+                &parse("(let c (Character (health 3)))"),
+                None,
+                &mut bindings,
+            )
+            .unwrap();
+        let result = interpreter.evaluate("", &parse("(get c health)"), None, &mut bindings);
+
+        assert_eq!(result, Ok(int(3)));
+    }
+
+    #[test]
+    fn call_match_binds_variant_values() {
+        let env = make_env(
+            "(enum Action Wait (Attack (i32 damage)) (Move (i32 x) (i32 y)))
+            (fn cost ((in Action a)) i32 (match a (Wait 0) ((Attack damage) (* damage 2)) (_ 1)))",
+        );
+        let interpreter = Interpreter::new(&env);
+        let location = Location::default();
+        let attack = interpreter
+            .construct(
+                &Type::Identifier("Action".into()),
+                Some("Attack"),
+                vec![("damage".into(), int(5))],
+                &location,
+            )
+            .unwrap();
+        let wait = interpreter
+            .default_value(&Type::Identifier("Action".into()), &location)
+            .unwrap();
+
+        assert_eq!(call(&env, "cost", &mut [attack]), Ok(int(10)));
+        assert_eq!(call(&env, "cost", &mut [wait]), Ok(int(0)));
+    }
+}
@@ -0,0 +1,274 @@
+use crate::definition::{import::Import, FromLisp};
+use lisper::{Error, List, Location};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+/// The lists read from a definition file, without its imports.
+#[derive(Debug, Clone)]
+pub struct Source {
+    pub path: PathBuf,
+    pub lists: Vec<List>,
+}
+
+/// Loads definition files along with the files they import.
+/// Every file is loaded once, no matter how many files import it.
+#[derive(Default)]
+pub struct Loader {
+    loaded: HashSet<PathBuf>,
+    /// The files currently being loaded, used to detect import cycles.
+    stack: Vec<PathBuf>,
+    /// Loaded files, each following the files it imports.
+    sources: Vec<Source>,
+}
+
+impl Loader {
+    /// Loads every file with the extension in the directory, along with the files they import.
+    pub fn load_directory(extension: &str, path: PathBuf) -> Result<Vec<Source>, Error> {
+        let entries = std::fs::read_dir(&path).map_err(|_| Error {
+            message: format!("Unable to read directory '{}'", path.display()),
+            location: Location::default(),
+        })?;
+
+        let mut files = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|file| file.is_file() && file.extension().is_some_and(|e| e == extension))
+            .collect::<Vec<_>>();
+        files.sort();
+
+        let mut loader = Loader::default();
+        for file in files.iter() {
+            loader.load_file(file)?;
+        }
+
+        Ok(loader.sources)
+    }
+
+    /// Loads the file and every file it imports.
+    pub fn load_file(&mut self, path: &Path) -> Result<(), Error> {
+        let path = path.canonicalize().map_err(|_| Error {
+            message: format!("Unable to read '{}'", path.display()),
+            location: Location::default(),
+        })?;
+
+        if self.loaded.contains(&path) {
+            return Ok(());
+        }
+
+        let contents = std::fs::read_to_string(&path).map_err(|_| Error {
+            message: format!("Unable to read '{}'", path.display()),
+            location: Location::default(),
+        })?;
+        let lists = lisper::parse_str(&contents).map_err(|e| in_file(&path, e))?;
+
+        self.stack.push(path.clone());
+        let mut definitions = vec![];
+        for list in lists {
+            if !Import::can_try(&list) {
+                definitions.push(list);
+                continue;
+            }
+
+            let import = Import::from_lisp(list).map_err(|e| in_file(&path, e))?;
+            let imported = path.parent().unwrap().join(&import.path);
+            let imported = imported.canonicalize().map_err(|_| Error {
+                message: format!(
+                    "{}: Unable to import '{}'",
+                    path.display(),
+                    import.path.display()
+                ),
+                location: import.location.clone(),
+            })?;
+
+            if let Some(start) = self.stack.iter().position(|p| *p == imported) {
+                let mut cycle = self.stack[start..].to_vec();
+                cycle.push(imported);
+
+                return Err(Error {
+                    message: format!(
+                        "{}: Import cycle: {}",
+                        path.display(),
+                        cycle
+                            .iter()
+                            .map(|p| p.display().to_string())
+                            .collect::<Vec<_>>()
+                            .join(" -> ")
+                    ),
+                    location: import.location,
+                });
+            }
+
+            self.load_file(&imported)?;
+        }
+        self.stack.pop();
+
+        self.sources.push(Source {
+            path: path.clone(),
+            lists: definitions,
+        });
+        self.loaded.insert(path);
+        Ok(())
+    }
+}
+
+/// Prefixes the error with the file it occurred in.
+pub fn in_file(path: &Path, error: Error) -> Error {
+    Error {
+        message: format!("{}: {}", path.display(), error.message),
+        location: error.location,
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::env::Environment;
+
+    /// An empty folder for a test to write definition files to, removed when dropped.
+    pub struct TempFolder(PathBuf);
+
+    impl TempFolder {
+        /// Creates a folder unique to this process and name, so tests running in parallel don't collide.
+        pub fn new(name: &str) -> Self {
+            let folder =
+                std::env::temp_dir().join(format!("wc-gen-{}-{}", name, std::process::id()));
+            if folder.exists() {
+                std::fs::remove_dir_all(&folder).unwrap();
+            }
+            std::fs::create_dir_all(&folder).unwrap();
+            Self(folder.canonicalize().unwrap())
+        }
+    }
+
+    impl std::ops::Deref for TempFolder {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempFolder {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn make_folder(name: &str) -> TempFolder {
+        TempFolder::new(&format!("loader-{}", name))
+    }
+
+    fn write(folder: &Path, name: &str, contents: &str) {
+        let path = folder.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    fn load(path: &Path) -> Result<Vec<String>, Error> {
+        let mut loader = Loader::default();
+        loader.load_file(path)?;
+        Ok(loader
+            .sources
+            .iter()
+            .flat_map(|s| s.lists.iter().map(|l| l.to_string()))
+            .collect())
+    }
+
+    #[test]
+    fn load_file_follows_relative_imports() {
+        let folder = make_folder("relative");
+        write(
+            &folder,
+            "game/units.scm",
+            "(import \"../common/math.scm\")\n(struct Unit)",
+        );
+        write(&folder, "common/math.scm", "(struct Vec2)");
+
+        let lists = load(&folder.join("game/units.scm")).unwrap();
+
+        assert_eq!(lists.len(), 2);
+        assert!(lists[0].contains("Vec2"));
+        assert!(lists[1].contains("Unit"));
+    }
+
+    #[test]
+    fn load_file_loads_shared_import_once() {
+        let folder = make_folder("once");
+        write(
+            &folder,
+            "main.scm",
+            "(import \"a.scm\")\n(import \"b.scm\")",
+        );
+        write(&folder, "a.scm", "(import \"common.scm\")\n(struct A)");
+        write(&folder, "b.scm", "(import \"common.scm\")\n(struct B)");
+        write(&folder, "common.scm", "(struct Common)");
+
+        let lists = load(&folder.join("main.scm")).unwrap();
+
+        assert_eq!(lists.len(), 3);
+    }
+
+    #[test]
+    fn load_file_cycle_returns_err() {
+        let folder = make_folder("cycle");
+        write(&folder, "a.scm", "(import \"b.scm\")");
+        write(&folder, "b.scm", "(import \"a.scm\")");
+
+        let a = folder.join("a.scm");
+        let b = folder.join("b.scm");
+        let expected = Err(Error {
+            message: format!(
+                "{}: Import cycle: {} -> {} -> {}",
+                b.display(),
+                a.display(),
+                b.display(),
+                a.display()
+            ),
+            location: Location::default(),
+        });
+
+        assert_eq!(load(&a), expected);
+    }
+
+    #[test]
+    fn load_file_missing_import_names_importing_file() {
+        let folder = make_folder("missing");
+        write(&folder, "a.scm", "(import \"missing.scm\")");
+
+        let a = folder.join("a.scm");
+        let expected = Err(Error {
+            message: format!("{}: Unable to import 'missing.scm'", a.display()),
+            location: Location::default(),
+        });
+
+        assert_eq!(load(&a), expected);
+    }
+
+    #[test]
+    fn load_directory_loads_files_with_extension() {
+        let folder = make_folder("directory");
+        write(&folder, "a.scm", "(import \"shared/b.scm\")\n(struct A)");
+        write(&folder, "c.scm", "(import \"shared/b.scm\")\n(struct C)");
+        write(&folder, "shared/b.scm", "(struct B)");
+        write(&folder, "notes.txt", "(struct Ignored)");
+
+        let sources = Loader::load_directory("scm", folder.to_path_buf()).unwrap();
+
+        assert_eq!(sources.len(), 3);
+        assert_eq!(sources[0].path, folder.join("shared/b.scm"));
+    }
+
+    #[test]
+    fn parse_sources_error_names_imported_file() {
+        let folder = make_folder("source-error");
+        write(&folder, "a.scm", "(import \"b.scm\")\n(struct A)");
+        write(&folder, "b.scm", "(struct B (fields (i32)))");
+
+        let sources = Loader::load_directory("scm", folder.to_path_buf()).unwrap();
+        let error = Environment::parse_sources(sources).unwrap_err();
+
+        let prefix = format!("{}: ", folder.join("b.scm").display());
+        assert!(error.message.starts_with(&prefix), "{}", error.message);
+    }
+}
@@ -1,14 +1,22 @@
 mod compiler;
 mod definition;
 mod env;
+mod interpreter;
+mod loader;
+mod repl;
+mod type_checker;
 
 fn main() -> Result<(), String> {
     let extension = "scm";
     let path = "../wc-definition";
 
-    let result = lisper::load_directory(extension, path.into())?;
-    let result = env::Environment::parse(result)?;
-    compiler::compile(result);
+    if std::env::args().nth(1).as_deref() == Some("repl") {
+        return Ok(repl::run(extension, path.into())?);
+    }
+
+    let result = loader::Loader::load_directory(extension, path.into())?;
+    let result = env::Environment::parse_sources(result)?;
+    compiler::compile(result)?;
 
     let is_server = false;
     return Ok(());
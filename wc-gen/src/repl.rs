@@ -0,0 +1,588 @@
+use crate::{
+    definition::{
+        expression::{Expression, ExpressionKind},
+        field::Field,
+        function::{Function, ParameterMode},
+        type_::Type,
+    },
+    env::Environment,
+    interpreter::{Interpreter, Value},
+    loader::Loader,
+    type_checker,
+};
+use lisper::{Error, Location};
+use std::{
+    collections::HashMap,
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+const HELP: &str = "Enter an expression to evaluate it, such as (Character (health 10)).
+:type NAME    shows the definition of a type, function or constant
+:type EXPR    shows the type of an expression
+:call X FN... calls a function of the struct value bound to X, or a static function of struct X
+:reload       loads the definitions again
+:quit         exits";
+
+/// What the REPL should do after a line is entered.
+#[derive(Debug, PartialEq)]
+pub enum Output {
+    Print(String),
+    Quit,
+}
+
+/// Evaluates expressions against the definitions in a directory.
+pub struct Repl {
+    extension: String,
+    path: PathBuf,
+    env: Environment,
+    /// Values bound with `let`, along with their types.
+    bindings: HashMap<String, Value>,
+    binding_types: HashMap<String, Type>,
+}
+
+/// Runs the REPL on standard input until it is closed or `:quit` is entered.
+pub fn run(extension: &str, path: PathBuf) -> Result<(), Error> {
+    let mut repl = Repl::new(extension, path)?;
+    let stdin = std::io::stdin();
+    let mut input = String::new();
+
+    loop {
+        print!("{}", if input.is_empty() { "> " } else { ". " });
+        std::io::stdout().flush().ok();
+
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => return Ok(()),
+            Ok(_) => input.push_str(&line),
+        }
+
+        // Keep reading until every list is closed
+        if !is_complete(&input) {
+            continue;
+        }
+
+        match repl.execute(&input) {
+            Ok(Output::Print(text)) if text.is_empty() => {}
+            Ok(Output::Print(text)) => println!("{}", text),
+            Ok(Output::Quit) => return Ok(()),
+            Err(e) => println!("{}", describe_error(&e)),
+        }
+        input.clear();
+    }
+}
+
+impl Repl {
+    pub fn new(extension: &str, path: PathBuf) -> Result<Self, Error> {
+        let env = load(extension, &path)?;
+        Ok(Self {
+            extension: extension.to_string(),
+            path,
+            env,
+            bindings: HashMap::new(),
+            binding_types: HashMap::new(),
+        })
+    }
+
+    /// Runs a command or evaluates the expressions in the input.
+    pub fn execute(&mut self, input: &str) -> Result<Output, Error> {
+        let input = input.trim();
+        let (command, argument) = match input.split_once(char::is_whitespace) {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (input, ""),
+        };
+
+        match command {
+            "" => Ok(Output::Print(String::new())),
+            ":quit" | ":q" => Ok(Output::Quit),
+            ":help" => Ok(Output::Print(HELP.to_string())),
+            ":reload" => {
+                // The previous definitions are kept if the new ones fail to load
+                self.env = load(&self.extension, &self.path)?;
+                self.bindings.clear();
+                self.binding_types.clear();
+                Ok(Output::Print("Reloaded definitions".to_string()))
+            }
+            ":type" => self.describe(argument).map(Output::Print),
+            ":call" => self.call(argument).map(Output::Print),
+            _ if command.starts_with(':') => Err(Error {
+                message: format!("Unknown command '{}', enter :help for a list", command),
+                location: Location::default(),
+            }),
+            _ => self.evaluate(input).map(Output::Print),
+        }
+    }
+
+    /// Returns the definition of the named type, function or constant, or else the type of the expression.
+    fn describe(&self, argument: &str) -> Result<String, Error> {
+        let env = &self.env;
+        if let Some(s) = env.structs.get(argument) {
+            let mut fields = s.fields.values().collect::<Vec<_>>();
+            fields.sort_by(|a, b| a.name.cmp(&b.name));
+            let mut functions = s.functions.values().collect::<Vec<_>>();
+            functions.sort_by(|a, b| a.name.cmp(&b.name));
+
+            let mut text = format!("(struct {}\n\t(fields", s.name);
+            for field in fields {
+                text.push_str(&format!(" {}", describe_field(field)));
+            }
+            text.push(')');
+            for f in functions {
+                text.push_str(&format!("\n\t{}", describe_function(f)));
+            }
+            text.push(')');
+            return Ok(text);
+        }
+
+        if let Some(e) = env.enums.get(argument) {
+            let mut text = format!("(enum {}", e.name);
+            for variant in e.ordered_variants() {
+                let mut values = variant.values.values().collect::<Vec<_>>();
+                values.sort_by(|a, b| a.name.cmp(&b.name));
+                match values.is_empty() {
+                    true => text.push_str(&format!(" {}", variant.name)),
+                    false => text.push_str(&format!(
+                        " ({} {})",
+                        variant.name,
+                        values
+                            .iter()
+                            .map(|v| describe_field(v))
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    )),
+                }
+            }
+            text.push(')');
+            return Ok(text);
+        }
+
+        if let Some(n) = env.newtypes.get(argument) {
+            return Ok(format!("(newtype {} {})", n.name, n.type_.1));
+        }
+
+        if let Some(a) = env.aliases.get(argument) {
+            return Ok(format!("(alias {} {})", a.name, a.type_.1));
+        }
+
+        if let Some(f) = env.flags.get(argument) {
+            return Ok(format!("(flags {} {})", f.name, f.values.join(" ")));
+        }
+
+        if let Some(f) = env.functions.get(argument) {
+            return Ok(describe_function(f));
+        }
+
+        if let Some(c) = env.constants.get(argument) {
+            return Ok(format!("(const {} {} {})", c.name, c.type_.1, c.value.1));
+        }
+
+        let mut types = vec![];
+        let mut binding_types = self.binding_types.clone();
+        for expression in parse_expressions(argument)? {
+            types.push(type_checker::check_expression(
+                env,
+                "",
+                &expression,
+                None,
+                &mut binding_types,
+            )?);
+        }
+
+        match types.last() {
+            Some(ty) => Ok(ty.to_string()),
+            None => Err(Error {
+                message: "Expected type, function, constant or expression".into(),
+                location: Location::default(),
+            }),
+        }
+    }
+
+    /// Checks and evaluates each expression in the input, returning the values that are not void.
+    fn evaluate(&mut self, input: &str) -> Result<String, Error> {
+        let expressions = parse_expressions(input)?;
+
+        // Everything is checked before anything runs, so a mistake leaves the bindings as they were
+        let mut binding_types = self.binding_types.clone();
+        for expression in expressions.iter() {
+            type_checker::check_expression(&self.env, "", expression, None, &mut binding_types)?;
+        }
+
+        let interpreter = Interpreter::new(&self.env);
+        let mut output = vec![];
+        let mut result = Ok(());
+        for expression in expressions.iter() {
+            match interpreter.evaluate("", expression, None, &mut self.bindings) {
+                Ok(Value::Void) => {}
+                Ok(value) => output.push(value.to_string()),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+
+        // Bindings made before a failure are kept, so their types are kept with them
+        self.binding_types = binding_types;
+        self.binding_types
+            .retain(|name, _| self.bindings.contains_key(name));
+
+        result.map(|_| output.join("\n"))
+    }
+
+    /// Calls a function of the struct value bound to the target, or a static function of the struct it names.
+    /// Bindings passed to `mut` and `out` parameters, and the target itself, are updated in place.
+    fn call(&mut self, argument: &str) -> Result<String, Error> {
+        let mut parts = argument.splitn(3, char::is_whitespace);
+        let target = parts.next().unwrap_or_default();
+        let name = parts.next().unwrap_or_default();
+        let arguments = parse_expressions(parts.next().unwrap_or_default())?;
+
+        let struct_name = match self.bindings.get(target) {
+            Some(Value::Struct(struct_name, _)) => struct_name.clone(),
+            _ if self.env.structs.contains_key(target) => target.to_string(),
+            _ => {
+                return Err(Error {
+                    message: format!(
+                        "Expected a struct or a bound struct value, got '{}'",
+                        target
+                    ),
+                    location: Location::default(),
+                })
+            }
+        };
+        let f = match self.env.structs[&struct_name].functions.get(name) {
+            Some(f) => f,
+            None => {
+                return Err(Error {
+                    message: format!("Unknown function '{}' of '{}'", name, struct_name),
+                    location: Location::default(),
+                })
+            }
+        };
+
+        let mut binding_types = self.binding_types.clone();
+        for (parameter, argument) in f.parameters.iter().zip(arguments.iter()) {
+            type_checker::check_expression(
+                &self.env,
+                "",
+                argument,
+                Some(&parameter.type_),
+                &mut binding_types,
+            )?;
+            let settable = matches!(&argument.kind, ExpressionKind::Variable(name) if self.bindings.contains_key(name));
+            if let (Some(ParameterMode::Mut | ParameterMode::Out), false) =
+                (parameter.mode, settable)
+            {
+                return Err(Error {
+                    message: "Can not set a temporary value".into(),
+                    location: argument.location.clone(),
+                });
+            }
+        }
+
+        let interpreter = Interpreter::new(&self.env);
+        let mut values = vec![];
+        for (index, argument) in arguments.iter().enumerate() {
+            let expected = f
+                .parameters
+                .get(index)
+                .map(|p| self.env.underlying_type(&p.type_));
+            values.push(interpreter.evaluate(
+                "",
+                argument,
+                expected.as_ref(),
+                &mut self.bindings,
+            )?);
+        }
+
+        let location = Location::default();
+        let result = match self.bindings.get_mut(target) {
+            Some(value) => interpreter.call_method(value, name, &mut values, &location)?,
+            None => interpreter.call_static(&struct_name, name, &mut values, &location)?,
+        };
+
+        for ((parameter, argument), value) in f.parameters.iter().zip(arguments).zip(values) {
+            if let (Some(ParameterMode::Mut | ParameterMode::Out), ExpressionKind::Variable(name)) =
+                (parameter.mode, argument.kind)
+            {
+                self.bindings.insert(name, value);
+            }
+        }
+
+        match result {
+            Value::Void => Ok(String::new()),
+            value => Ok(value.to_string()),
+        }
+    }
+}
+
+fn load(extension: &str, path: &Path) -> Result<Environment, Error> {
+    let sources = Loader::load_directory(extension, path.to_path_buf())?;
+    Environment::parse_sources(sources)
+}
+
+fn parse_expressions(input: &str) -> Result<Vec<Expression>, Error> {
+    let mut list = lisper::parse_str(&format!("({})", input))?.remove(0);
+    let mut expressions = vec![];
+    while !list.is_empty() {
+        expressions.push(Expression::pop(&mut list)?);
+    }
+    Ok(expressions)
+}
+
+/// Returns whether every list opened in the input is closed. Parentheses in strings are skipped.
+fn is_complete(input: &str) -> bool {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in input.chars() {
+        match (in_string, escaped, c) {
+            (true, false, '\\') => escaped = true,
+            (true, false, '"') => in_string = false,
+            (true, _, _) => escaped = false,
+            (false, _, '"') => in_string = true,
+            (false, _, '(') => depth += 1,
+            (false, _, ')') => depth -= 1,
+            _ => {}
+        }
+    }
+
+    depth <= 0
+}
+
+/// Returns the message of the error along with where it happened, if it is known.
+fn describe_error(error: &Error) -> String {
+    match error.location == Location::default() {
+        true => format!("error: {}", error.message),
+        false => format!(
+            "error at line {}, column {}: {}",
+            error.location.line, error.location.column, error.message
+        ),
+    }
+}
+
+fn describe_field(field: &Field) -> String {
+    match &field.default {
+        Some((_, default)) => format!("({} {} {})", field.type_, field.name, default),
+        None => format!("({} {})", field.type_, field.name),
+    }
+}
+
+fn describe_function(f: &Function) -> String {
+    let keyword = if f.is_static { "static-fn" } else { "fn" };
+    let const_self = if f.is_const { " (self const)" } else { "" };
+    let parameters = f
+        .parameters
+        .iter()
+        .map(|p| match p.mode {
+            Some(ParameterMode::In) => format!("(in {} {})", p.type_, p.name),
+            Some(ParameterMode::Mut) => format!("(mut {} {})", p.type_, p.name),
+            Some(ParameterMode::Out) => format!("(out {} {})", p.type_, p.name),
+            None => format!("({} {})", p.type_, p.name),
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut contracts = String::new();
+    for (keyword, conditions) in [("requires", &f.requires), ("ensures", &f.ensures)] {
+        if !conditions.is_empty() {
+            let conditions = conditions
+                .iter()
+                .map(|c| c.source.as_str())
+                .collect::<Vec<_>>()
+                .join(" ");
+            contracts.push_str(&format!(" ({} {})", keyword, conditions));
+        }
+    }
+
+    format!(
+        "({} {}{} ({}) {}{})",
+        keyword, f.name, const_self, parameters, f.return_type.1, contracts
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::tests::TempFolder;
+
+    const DEFINITIONS: &str = "(struct Character (fields (i32 health 10) (string name))
+            (fn hurt ((in i32 amount)) i32 (- (get self health) amount)))
+        (enum Mood Happy (Sad (i32 tears)))
+        (fn heal ((in Character c) (in i32 amount)) Character
+            (Character (health (+ (get c health) amount)) (name (get c name))))";
+
+    /// Creates a folder with the definitions for a test to load. The folder is removed when it is dropped.
+    fn make_repl(name: &str, contents: &str) -> (Repl, TempFolder) {
+        let folder = TempFolder::new(&format!("repl-{}", name));
+        std::fs::write(folder.join("defs.scm"), contents).unwrap();
+        (Repl::new("scm", folder.to_path_buf()).unwrap(), folder)
+    }
+
+    fn print(text: &str) -> Result<Output, Error> {
+        Ok(Output::Print(text.to_string()))
+    }
+
+    #[test]
+    fn execute_construct_prints_value() {
+        let (mut repl, _folder) = make_repl("construct", DEFINITIONS);
+        assert_eq!(
+            repl.execute("(Character (health 3))"),
+            print("(Character (health 3) (name \"\"))")
+        );
+    }
+
+    #[test]
+    fn execute_call_uses_bindings() {
+        let (mut repl, _folder) = make_repl("call", DEFINITIONS);
+        assert_eq!(
+            repl.execute("(let c (Character (name \"Ada\")))"),
+            print("")
+        );
+        assert_eq!(
+            repl.execute("(heal c 5)"),
+            print("(Character (health 15) (name \"Ada\"))")
+        );
+    }
+
+    const FUNCTIONS: &str = "(struct Character (fields (i32 health 10) (string name))
+            (fn hurt ((in i32 amount)) i32 (- (get self health) amount))
+            (fn drain ((in i32 amount) (out i32 left)) void
+                (set (get self health) (- (get self health) amount))
+                (set left (get self health)))
+            (static-fn named ((in string name)) Character (Character (name name))))
+        (enum Mood Happy Sad)";
+
+    #[test]
+    fn execute_call_method_updates_binding() {
+        let (mut repl, _folder) = make_repl("call-method", FUNCTIONS);
+        repl.execute("(let c (Character (name \"Ada\")))").unwrap();
+        repl.execute("(let left 0)").unwrap();
+        assert_eq!(repl.execute(":call c hurt 3"), print("7"));
+        assert_eq!(repl.execute(":call c drain 4 left"), print(""));
+        assert_eq!(repl.execute("left"), print("6"));
+        assert_eq!(repl.execute("(get c health)"), print("6"));
+        assert_eq!(
+            repl.execute(":call c drain 1 2"),
+            Err("Can not set a temporary value".into())
+        );
+    }
+
+    #[test]
+    fn execute_call_static_returns_value() {
+        let (mut repl, _folder) = make_repl("call-static", FUNCTIONS);
+        assert_eq!(
+            repl.execute(":call Character named \"Bo\""),
+            print("(Character (health 10) (name \"Bo\"))")
+        );
+        assert_eq!(
+            repl.execute(":call Mood named"),
+            Err("Expected a struct or a bound struct value, got 'Mood'".into())
+        );
+        assert_eq!(
+            repl.execute(":call Character heal"),
+            Err("Unknown function 'heal' of 'Character'".into())
+        );
+    }
+
+    #[test]
+    fn execute_type_error_keeps_bindings() {
+        let (mut repl, _folder) = make_repl("type-error", DEFINITIONS);
+        repl.execute("(let a 1)").unwrap();
+        assert_eq!(
+            repl.execute("(let b true) (+ a b)"),
+            Err("Expected i32, got bool".into())
+        );
+        assert_eq!(repl.execute("b"), Err("Unknown identifier 'b'".into()));
+        assert_eq!(repl.execute("(+ a 1)"), print("2"));
+    }
+
+    #[test]
+    fn execute_type_of_definitions() {
+        let (mut repl, _folder) = make_repl("type", DEFINITIONS);
+        assert_eq!(
+            repl.execute(":type Character"),
+            print("(struct Character\n\t(fields (i32 health 10) (string name))\n\t(fn hurt ((in i32 amount)) i32))")
+        );
+        assert_eq!(
+            repl.execute(":type Mood"),
+            print("(enum Mood Happy (Sad (i32 tears)))")
+        );
+        assert_eq!(
+            repl.execute(":type heal"),
+            print("(fn heal ((in Character c) (in i32 amount)) Character)")
+        );
+    }
+
+    #[test]
+    fn execute_type_of_expression() {
+        let (mut repl, _folder) = make_repl("type-expr", DEFINITIONS);
+        assert_eq!(repl.execute(":type (< 1 2)"), print("bool"));
+        assert_eq!(repl.execute(":type (Mood Sad (tears 2))"), print("Mood"));
+    }
+
+    #[test]
+    fn execute_return_returns_err() {
+        let (mut repl, _folder) = make_repl("return", DEFINITIONS);
+        assert_eq!(
+            repl.execute("(return 1)"),
+            Err("Can not return outside of a function".into())
+        );
+    }
+
+    #[test]
+    fn execute_unknown_command_returns_err() {
+        let (mut repl, _folder) = make_repl("unknown", DEFINITIONS);
+        assert_eq!(
+            repl.execute(":foo"),
+            Err("Unknown command ':foo', enter :help for a list".into())
+        );
+        assert_eq!(repl.execute(":quit"), Ok(Output::Quit));
+    }
+
+    #[test]
+    fn execute_reload_reads_changes() {
+        let (mut repl, _folder) = make_repl("reload", DEFINITIONS);
+        repl.execute("(let a 1)").unwrap();
+
+        std::fs::write(repl.path.join("defs.scm"), "(const MAX i32 5)").unwrap();
+        assert_eq!(repl.execute(":reload"), print("Reloaded definitions"));
+        assert_eq!(repl.execute("MAX"), print("5"));
+        assert_eq!(repl.execute("a"), Err("Unknown identifier 'a'".into()));
+    }
+
+    #[test]
+    fn execute_reload_with_errors_keeps_definitions() {
+        let (mut repl, _folder) = make_repl("reload-error", DEFINITIONS);
+
+        std::fs::write(repl.path.join("defs.scm"), "(const MAX i32 true)").unwrap();
+        assert!(repl.execute(":reload").is_err());
+        assert_eq!(
+            repl.execute(":type Mood"),
+            print("(enum Mood Happy (Sad (i32 tears)))")
+        );
+    }
+
+    #[test]
+    fn is_complete_counts_lists() {
+        assert!(!is_complete("(Character (health 3)"));
+        assert!(is_complete("(Character (health 3))"));
+        assert!(!is_complete("(Character (name \")\")"));
+    }
+
+    #[test]
+    fn describe_error_includes_location() {
+        let error = Error {
+            message: "Unknown identifier 'b'".into(),
+            location: Location { line: 2, column: 5 },
+        };
+        assert_eq!(
+            describe_error(&error),
+            "error at line 2, column 5: Unknown identifier 'b'"
+        );
+        assert_eq!(
+            describe_error(&"Unknown command ':foo'".into()),
+            "error: Unknown command ':foo'"
+        );
+    }
+}
@@ -0,0 +1,1382 @@
+use crate::{
+    definition::{
+        expression::{Expression, ExpressionKind, MatchArm, Operator, Pattern},
+        field::Field,
+        function::{Contract, Function, ParameterMode},
+        literal::Literal,
+        struct_::Struct,
+        type_::Type,
+    },
+    env::Environment,
+};
+use lisper::{Error, Location};
+use std::collections::{HashMap, HashSet};
+
+/// A name that can be read within a function body.
+#[derive(Debug, Clone)]
+struct Binding {
+    type_: Type,
+    /// The reason the binding can not be assigned to, if any.
+    readonly: Option<String>,
+}
+
+/// Checks the types of the expressions in a function body.
+pub struct TypeChecker<'a> {
+    env: &'a Environment,
+    function: &'a Function,
+    self_struct: Option<&'a Struct>,
+    /// The qualified name calls are resolved from. Struct functions use the name of their struct.
+    caller: &'a str,
+    scopes: Vec<HashMap<String, Binding>>,
+}
+
+/// Checks the bodies of every function in the environment, in order of name so errors are stable.
+/// Every function is checked so a change to a definition reports each function it breaks.
+pub fn check_environment(env: &Environment) -> Result<(), Error> {
+    let mut functions = env
+        .functions
+        .values()
+        .map(|f| (f, None))
+        .chain(
+            env.structs
+                .values()
+                .flat_map(|s| s.functions.values().map(move |f| (f, Some(s)))),
+        )
+        .collect::<Vec<_>>();
+    functions.sort_by(|(a, a_struct), (b, b_struct)| {
+        let a_name = a_struct.map(|s| s.name.as_str());
+        let b_name = b_struct.map(|s| s.name.as_str());
+        (a_name, &a.name).cmp(&(b_name, &b.name))
+    });
+
+    let errors = functions
+        .into_iter()
+        .filter_map(|(f, s)| TypeChecker::new(env, f, s).check_function().err())
+        .collect::<Vec<_>>();
+
+    match errors.first() {
+        Some(first) => Err(Error {
+            message: errors
+                .iter()
+                .map(|e| e.message.clone())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            location: first.location.clone(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Checks an expression outside of any function, such as one entered in the REPL or a constant value.
+/// Names are resolved from the caller. `let` adds to the bindings so they can be used by later expressions.
+pub fn check_expression(
+    env: &Environment,
+    caller: &str,
+    expression: &Expression,
+    expected: Option<&Type>,
+    bindings: &mut HashMap<String, Type>,
+) -> Result<Type, Error> {
+    let mut pending = vec![expression];
+    while let Some(e) = pending.pop() {
+        if let ExpressionKind::Return(_) = e.kind {
+            return Err(Error {
+                message: "Can not return outside of a function".into(),
+                location: e.location.clone(),
+            });
+        }
+        pending.extend(e.children());
+    }
+
+    let function = Function {
+        location: expression.location.clone(),
+        name: String::new(),
+        parameters: vec![],
+        return_type: (expression.location.clone(), Type::Void),
+        is_const: false,
+        is_static: false,
+        body: vec![],
+        requires: vec![],
+        ensures: vec![],
+        doc: None,
+        attributes: vec![],
+    };
+    let mut checker = TypeChecker::new(env, &function, None);
+    checker.caller = caller;
+    checker.scopes[0] = bindings
+        .iter()
+        .map(|(name, ty)| {
+            let binding = Binding {
+                type_: ty.clone(),
+                readonly: None,
+            };
+            (name.clone(), binding)
+        })
+        .collect();
+
+    let ty = checker.check(expression, expected)?;
+    if let Some(expected) = expected {
+        expect_type(&ty, expected, &expression.location)?;
+    }
+    *bindings = checker.scopes[0]
+        .drain()
+        .map(|(name, binding)| (name, binding.type_))
+        .collect();
+    Ok(ty)
+}
+
+impl<'a> TypeChecker<'a> {
+    pub fn new(
+        env: &'a Environment,
+        function: &'a Function,
+        self_struct: Option<&'a Struct>,
+    ) -> Self {
+        let parameters = function
+            .parameters
+            .iter()
+            .map(|p| {
+                let readonly = match p.mode {
+                    Some(ParameterMode::In) => Some(format!("'in' parameter '{}'", p.name)),
+                    _ => None,
+                };
+                let binding = Binding {
+                    type_: p.type_.clone(),
+                    readonly,
+                };
+                (p.name.clone(), binding)
+            })
+            .collect();
+
+        Self {
+            env,
+            function,
+            self_struct: self_struct.filter(|_| !function.is_static),
+            caller: self_struct.map_or(&function.name, |s| &s.name),
+            scopes: vec![parameters],
+        }
+    }
+
+    /// Checks the body of the function. Every path of a function with a return type must return a value.
+    pub fn check_function(&mut self) -> Result<(), Error> {
+        self.check_contracts()?;
+
+        let body = &self.function.body;
+        let return_type = self.function.return_type.1.clone();
+        if body.is_empty() {
+            return Ok(());
+        }
+
+        let (last, statements) = body.split_last().unwrap();
+        for statement in statements {
+            self.check(statement, None)?;
+        }
+
+        match return_type {
+            Type::Void => self.check(last, None).map(|_| ()),
+            ty => self.check_tail(last, &ty),
+        }
+    }
+
+    /// Checks the `requires` and `ensures` conditions, which are bool expressions that only read values.
+    /// `ensures` conditions can read the value returned as `result`.
+    fn check_contracts(&mut self) -> Result<(), Error> {
+        let function = self.function;
+        for contract in function.requires.iter() {
+            self.check_condition(contract)?;
+        }
+
+        self.scopes.push(HashMap::new());
+        if function.return_type.1 != Type::Void {
+            let binding = Binding {
+                type_: function.return_type.1.clone(),
+                readonly: None,
+            };
+            self.scopes
+                .last_mut()
+                .unwrap()
+                .insert("result".into(), binding);
+        }
+        let result = function
+            .ensures
+            .iter()
+            .try_for_each(|contract| self.check_condition(contract));
+        self.scopes.pop();
+        result
+    }
+
+    fn check_condition(&mut self, contract: &Contract) -> Result<(), Error> {
+        let mut pending = vec![&contract.condition];
+        while let Some(e) = pending.pop() {
+            let keyword = match e.kind {
+                ExpressionKind::Let(_, _) => "let",
+                ExpressionKind::Set(_, _) => "set",
+                ExpressionKind::For(_, _, _) => "for",
+                ExpressionKind::Return(_) => "return",
+                _ => {
+                    pending.extend(e.children());
+                    continue;
+                }
+            };
+            return Err(Error {
+                message: format!(
+                    "Conditions can only read values, got '{}'{}",
+                    keyword,
+                    self.context()
+                ),
+                location: e.location.clone(),
+            });
+        }
+
+        self.check_expected(&contract.condition, &Type::Bool)
+    }
+
+    /// Checks an expression whose value is returned from the function.
+    fn check_tail(&mut self, expression: &Expression, return_type: &Type) -> Result<(), Error> {
+        match &expression.kind {
+            ExpressionKind::If(condition, then, Some(otherwise)) => {
+                self.check_expected(condition, &Type::Bool)?;
+                self.check_tail(then, return_type)?;
+                self.check_tail(otherwise, return_type)
+            }
+            ExpressionKind::Cond(clauses, Some(otherwise)) => {
+                for (condition, body) in clauses.iter() {
+                    self.check_expected(condition, &Type::Bool)?;
+                    self.check_tail(body, return_type)?;
+                }
+                self.check_tail(otherwise, return_type)
+            }
+            ExpressionKind::Do(body) => {
+                self.scopes.push(HashMap::new());
+                let (last, statements) = body.split_last().unwrap();
+                let result = statements
+                    .iter()
+                    .try_for_each(|e| self.check(e, None).map(|_| ()))
+                    .and_then(|_| self.check_tail(last, return_type));
+                self.scopes.pop();
+                result
+            }
+            ExpressionKind::Return(_) => self.check(expression, Some(return_type)).map(|_| ()),
+            ExpressionKind::Match(value, arms) => {
+                for (arm, scope) in
+                    arms.iter()
+                        .zip(self.check_arms(value, arms, &expression.location)?)
+                {
+                    self.scopes.push(scope);
+                    let result = self.check_tail(&arm.body, return_type);
+                    self.scopes.pop();
+                    result?;
+                }
+                Ok(())
+            }
+            ExpressionKind::If(_, _, None)
+            | ExpressionKind::Cond(_, None)
+            | ExpressionKind::Let(_, _)
+            | ExpressionKind::Set(_, _)
+            | ExpressionKind::For(_, _, _) => Err(Error {
+                message: format!(
+                    "Not every path of function '{}' returns a value",
+                    self.function.name
+                ),
+                location: expression.location.clone(),
+            }),
+            _ => self.check_expected(expression, return_type),
+        }
+    }
+
+    /// Checks that the expression results in the expected type.
+    fn check_expected(&mut self, expression: &Expression, expected: &Type) -> Result<(), Error> {
+        let actual = self.check(expression, Some(expected))?;
+        expect_type(&actual, expected, &expression.location)
+    }
+
+    /// Checks the expression and returns its type. The expected type, if any, decides the type of literals.
+    pub fn check(
+        &mut self,
+        expression: &Expression,
+        expected: Option<&Type>,
+    ) -> Result<Type, Error> {
+        let location = &expression.location;
+        match &expression.kind {
+            ExpressionKind::Literal(literal) => check_literal(literal, expected, location),
+            ExpressionKind::Variable(name) => Ok(self.lookup(name, location)?.type_),
+            ExpressionKind::Let(name, value) => {
+                let ty = self.check(value, None)?;
+                if ty == Type::Void {
+                    return Err(Error {
+                        message: format!("Can not bind '{}' to a void value", name),
+                        location: value.location.clone(),
+                    });
+                }
+
+                let binding = Binding {
+                    type_: ty,
+                    readonly: None,
+                };
+                self.scopes
+                    .last_mut()
+                    .unwrap()
+                    .insert(name.clone(), binding);
+                Ok(Type::Void)
+            }
+            ExpressionKind::Set(target, value) => {
+                self.check_settable(target)?;
+                let ty = self.check(target, None)?;
+                self.check_expected(value, &ty)?;
+                Ok(Type::Void)
+            }
+            ExpressionKind::Get(value, field) => {
+                let ty = self.check(value, None)?;
+                Ok(self.get_field(&ty, field, location)?.type_.clone())
+            }
+            ExpressionKind::Operation(operator, operands) => {
+                self.check_operation(*operator, operands, expected, location)
+            }
+            ExpressionKind::If(condition, then, otherwise) => {
+                self.check_expected(condition, &Type::Bool)?;
+                let otherwise = match otherwise {
+                    Some(otherwise) => otherwise,
+                    None => {
+                        self.check_scoped(then, None)?;
+                        return Ok(Type::Void);
+                    }
+                };
+
+                let ty = self.check_scoped(then, expected)?;
+                let other = self.check_scoped(otherwise, Some(&ty))?;
+                expect_type(&other, &ty, &otherwise.location)?;
+                Ok(ty)
+            }
+            ExpressionKind::Cond(clauses, otherwise) => {
+                let mut result: Option<Type> = None;
+                for (condition, body) in clauses.iter() {
+                    self.check_expected(condition, &Type::Bool)?;
+                    let ty = self.check_scoped(body, result.as_ref().or(expected))?;
+                    match &result {
+                        Some(result) if otherwise.is_some() => {
+                            expect_type(&ty, result, &body.location)?
+                        }
+                        _ => result = Some(ty),
+                    }
+                }
+
+                match otherwise {
+                    Some(otherwise) => {
+                        let result = result.unwrap();
+                        let ty = self.check_scoped(otherwise, Some(&result))?;
+                        expect_type(&ty, &result, &otherwise.location)?;
+                        Ok(result)
+                    }
+                    None => Ok(Type::Void),
+                }
+            }
+            ExpressionKind::For(item, items, body) => {
+                let item_type = match self.check(items, None)? {
+                    Type::List(inner) => *inner,
+                    ty => {
+                        return Err(Error {
+                            message: format!("Expected list to iterate, got '{}'", ty),
+                            location: items.location.clone(),
+                        })
+                    }
+                };
+
+                let binding = Binding {
+                    type_: item_type,
+                    readonly: None,
+                };
+                self.scopes.push(HashMap::from([(item.clone(), binding)]));
+                let result = self.check(body, None);
+                self.scopes.pop();
+                result.map(|_| Type::Void)
+            }
+            ExpressionKind::Do(body) => {
+                self.scopes.push(HashMap::new());
+                let mut result = Ok(Type::Void);
+                for (index, e) in body.iter().enumerate() {
+                    let expected = expected.filter(|_| index == body.len() - 1);
+                    result = self.check(e, expected);
+                    if result.is_err() {
+                        break;
+                    }
+                }
+                self.scopes.pop();
+                result
+            }
+            ExpressionKind::Return(value) => {
+                let return_type = self.function.return_type.1.clone();
+                match (value, &return_type) {
+                    (None, Type::Void) => {}
+                    (None, ty) => {
+                        return Err(Error {
+                            message: format!("Expected {} value to return", ty),
+                            location: location.clone(),
+                        })
+                    }
+                    (Some(value), Type::Void) => {
+                        return Err(Error {
+                            message: format!(
+                                "Function '{}' does not return a value",
+                                self.function.name
+                            ),
+                            location: value.location.clone(),
+                        })
+                    }
+                    (Some(value), ty) => self.check_expected(value, ty)?,
+                }
+
+                // A return never results in a value, so it fits wherever it is used
+                Ok(expected.cloned().unwrap_or(Type::Void))
+            }
+            ExpressionKind::Call(name, arguments) => self.check_call(name, arguments, location),
+            ExpressionKind::Construct {
+                type_,
+                variant,
+                fields,
+            } => self.check_construct(type_, variant, fields, location),
+            ExpressionKind::Match(value, arms) => {
+                let mut result: Option<Type> = None;
+                for (arm, scope) in arms.iter().zip(self.check_arms(value, arms, location)?) {
+                    self.scopes.push(scope);
+                    let ty = self.check(&arm.body, result.as_ref().or(expected));
+                    self.scopes.pop();
+                    let ty = ty?;
+                    match &result {
+                        Some(result) => expect_type(&ty, result, &arm.body.location)?,
+                        None => result = Some(ty),
+                    }
+                }
+                Ok(result.unwrap())
+            }
+        }
+    }
+
+    /// Checks that the arms of a match cover every variant of the enum once, and returns the bindings of each arm.
+    fn check_arms(
+        &mut self,
+        value: &Expression,
+        arms: &[MatchArm],
+        location: &Location,
+    ) -> Result<Vec<HashMap<String, Binding>>, Error> {
+        let ty = self.check(value, None)?;
+        let e = match &ty {
+            Type::Identifier(name) => self.env.enums.get(name),
+            _ => None,
+        };
+        let e = match e {
+            Some(e) => e,
+            None => {
+                return Err(Error {
+                    message: format!("Expected enum to match, got '{}'", ty),
+                    location: value.location.clone(),
+                })
+            }
+        };
+
+        let mut matched = HashSet::new();
+        let mut scopes = vec![];
+        for (index, arm) in arms.iter().enumerate() {
+            if index > 0 && arms[index - 1].pattern == Pattern::Wildcard {
+                return Err(Error {
+                    message: format!(
+                        "Unreachable arm{}, the '_' arm before it matches every variant",
+                        self.context()
+                    ),
+                    location: arm.location.clone(),
+                });
+            }
+
+            let (variant, bindings) = match &arm.pattern {
+                Pattern::Wildcard if matched.len() == e.variants.len() => {
+                    return Err(Error {
+                        message: format!(
+                            "Unreachable arm '_'{}, every variant of '{}' is already matched",
+                            self.context(),
+                            e.name
+                        ),
+                        location: arm.location.clone(),
+                    })
+                }
+                Pattern::Wildcard => {
+                    scopes.push(HashMap::new());
+                    continue;
+                }
+                Pattern::Variant(variant, bindings) => (variant, bindings),
+            };
+
+            let v = match e.variants.get(variant) {
+                Some(v) => v,
+                None => {
+                    return Err(Error {
+                        message: format!("Unknown variant '{}' of '{}'", variant, e.name),
+                        location: arm.location.clone(),
+                    })
+                }
+            };
+
+            if !matched.insert(variant.as_str()) {
+                return Err(Error {
+                    message: format!(
+                        "Unreachable arm '{}'{}, the variant is already matched",
+                        variant,
+                        self.context()
+                    ),
+                    location: arm.location.clone(),
+                });
+            }
+
+            let mut scope = HashMap::new();
+            for binding in bindings {
+                let value = match v.values.get(binding) {
+                    Some(value) => value,
+                    None => {
+                        return Err(Error {
+                            message: format!(
+                                "Variant '{}' of '{}' has no value '{}'",
+                                variant, e.name, binding
+                            ),
+                            location: arm.location.clone(),
+                        })
+                    }
+                };
+
+                let binding_value = Binding {
+                    type_: value.type_.clone(),
+                    readonly: None,
+                };
+                scope.insert(binding.clone(), binding_value);
+            }
+            scopes.push(scope);
+        }
+
+        let has_wildcard = arms.iter().any(|a| a.pattern == Pattern::Wildcard);
+        let missing = e
+            .ordered_variants()
+            .into_iter()
+            .filter(|v| !matched.contains(v.name.as_str()))
+            .map(|v| format!("'{}'", v.name))
+            .collect::<Vec<_>>();
+        if !has_wildcard && !missing.is_empty() {
+            let variants = if missing.len() == 1 {
+                "variant"
+            } else {
+                "variants"
+            };
+            return Err(Error {
+                message: format!(
+                    "Match on '{}'{} is missing {} {}",
+                    e.name,
+                    self.context(),
+                    variants,
+                    missing.join(", ")
+                ),
+                location: location.clone(),
+            });
+        }
+
+        Ok(scopes)
+    }
+
+    /// Describes where the expressions being checked are, for errors that need to point at one of many.
+    fn context(&self) -> String {
+        match self.function.name.is_empty() {
+            true => String::new(),
+            false => format!(" in function '{}'", self.function.name),
+        }
+    }
+
+    /// Checks the expression in a scope of its own, so its bindings do not leak.
+    fn check_scoped(
+        &mut self,
+        expression: &Expression,
+        expected: Option<&Type>,
+    ) -> Result<Type, Error> {
+        self.scopes.push(HashMap::new());
+        let result = self.check(expression, expected);
+        self.scopes.pop();
+        result
+    }
+
+    /// Looks up a binding, `self` or a constant by name.
+    fn lookup(&self, name: &str, location: &Location) -> Result<Binding, Error> {
+        if let Some(binding) = self.scopes.iter().rev().find_map(|s| s.get(name)) {
+            return Ok(binding.clone());
+        }
+
+        if name == "self" {
+            if let Some(s) = self.self_struct {
+                let readonly = match self.function.is_const {
+                    true => Some(format!("self in const function '{}'", self.function.name)),
+                    false => None,
+                };
+                return Ok(Binding {
+                    type_: Type::Identifier(s.name.clone()),
+                    readonly,
+                });
+            }
+        }
+
+        if let Some(constant) = self.env.resolve_constant(self.caller, name) {
+            return Ok(Binding {
+                type_: constant.type_.1.clone(),
+                readonly: Some(format!("constant '{}'", constant.name)),
+            });
+        }
+
+        Err(Error {
+            message: format!("Unknown identifier '{}'", name),
+            location: location.clone(),
+        })
+    }
+
+    /// Ensures the target of a `set` can be assigned to.
+    fn check_settable(&self, target: &Expression) -> Result<(), Error> {
+        let mut root = target;
+        while let ExpressionKind::Get(value, _) = &root.kind {
+            root = value;
+        }
+
+        let readonly = match &root.kind {
+            ExpressionKind::Variable(name) => self.lookup(name, &root.location)?.readonly,
+            _ => Some("a temporary value".to_string()),
+        };
+
+        match readonly {
+            Some(reason) => Err(Error {
+                message: format!("Can not set {}", reason),
+                location: target.location.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the field of the struct type.
+    fn get_field(&self, ty: &Type, field: &str, location: &Location) -> Result<&'a Field, Error> {
+        let s = match ty {
+            Type::Identifier(name) => self.env.structs.get(name),
+            _ => None,
+        };
+
+        let s = match s {
+            Some(s) => s,
+            None => {
+                return Err(Error {
+                    message: format!("Expected struct to get '{}' from, got '{}'", field, ty),
+                    location: location.clone(),
+                })
+            }
+        };
+
+        match s.fields.get(field) {
+            Some(field) => Ok(field),
+            None => Err(Error {
+                message: format!("Unknown field '{}' of '{}'", field, s.name),
+                location: location.clone(),
+            }),
+        }
+    }
+
+    fn check_operation(
+        &mut self,
+        operator: Operator,
+        operands: &[Expression],
+        expected: Option<&Type>,
+        location: &Location,
+    ) -> Result<Type, Error> {
+        if operator.is_logical() {
+            for operand in operands {
+                self.check_expected(operand, &Type::Bool)?;
+            }
+            return Ok(Type::Bool);
+        }
+
+        // Operands share a type, which is decided by the first operand that is not a literal
+        let mut operand_type = None;
+        for operand in operands.iter() {
+            if !matches!(operand.kind, ExpressionKind::Literal(_)) {
+                operand_type = Some(self.check(operand, None)?);
+                break;
+            }
+        }
+        let operand_type = match operand_type {
+            Some(ty) => ty,
+            None => {
+                let expected = expected.filter(|ty| is_number(ty));
+                self.check(&operands[0], expected.filter(|_| !operator.is_comparison()))?
+            }
+        };
+
+        for operand in operands.iter() {
+            self.check_expected(operand, &operand_type)?;
+        }
+
+        let valid = match operator {
+            Operator::Equal | Operator::NotEqual => self.is_comparable(&operand_type),
+            _ => is_number(&operand_type),
+        };
+        if !valid {
+            return Err(Error {
+                message: format!(
+                    "Operator '{}' can not be used on '{}'",
+                    operator.symbol(),
+                    operand_type
+                ),
+                location: location.clone(),
+            });
+        }
+
+        match operator.is_comparison() {
+            true => Ok(Type::Bool),
+            false => Ok(operand_type),
+        }
+    }
+
+    /// Returns whether values of the type can be compared for equality.
+    fn is_comparable(&self, ty: &Type) -> bool {
+        match ty {
+            Type::Identifier(name) => {
+                self.env.newtypes.contains_key(name)
+                    || self.env.flags.contains_key(name)
+                    || self.env.enums.get(name).is_some_and(|e| e.is_simple())
+            }
+            Type::List(_) | Type::Optional(_) | Type::Generic(_, _) | Type::Void => false,
+            _ => true,
+        }
+    }
+
+    fn check_call(
+        &mut self,
+        name: &str,
+        arguments: &[Expression],
+        location: &Location,
+    ) -> Result<Type, Error> {
+        let f = match self.env.resolve_function(self.caller, name) {
+            Some(f) => f,
+            None => {
+                return Err(Error {
+                    message: format!("Unknown function '{}'", name),
+                    location: location.clone(),
+                })
+            }
+        };
+
+        if f.parameters.len() != arguments.len() {
+            return Err(Error {
+                message: format!(
+                    "Function '{}' takes {} arguments, got {}",
+                    name,
+                    f.parameters.len(),
+                    arguments.len()
+                ),
+                location: location.clone(),
+            });
+        }
+
+        for (parameter, argument) in f.parameters.iter().zip(arguments) {
+            if let Some(ParameterMode::Mut | ParameterMode::Out) = parameter.mode {
+                self.check_settable(argument)?;
+            }
+            self.check_expected(argument, &parameter.type_)?;
+        }
+
+        Ok(f.return_type.1.clone())
+    }
+
+    fn check_construct(
+        &mut self,
+        ty: &Type,
+        variant: &Option<String>,
+        fields: &[(String, Expression)],
+        location: &Location,
+    ) -> Result<Type, Error> {
+        let name = ty.to_string();
+        let declared = match (
+            self.env.structs.get(&name),
+            self.env.enums.get(&name),
+            variant,
+        ) {
+            (Some(s), _, None) => &s.fields,
+            (Some(_), _, Some(variant)) => {
+                return Err(Error {
+                    message: format!("Struct '{}' has no variants, got '{}'", name, variant),
+                    location: location.clone(),
+                })
+            }
+            (None, Some(e), Some(variant)) => match e.variants.get(variant) {
+                Some(v) => &v.values,
+                None => {
+                    return Err(Error {
+                        message: format!("Unknown variant '{}' of '{}'", variant, name),
+                        location: location.clone(),
+                    })
+                }
+            },
+            (None, Some(_), None) => {
+                return Err(Error {
+                    message: format!("Expected variant of '{}'", name),
+                    location: location.clone(),
+                })
+            }
+            (None, None, _) => {
+                return Err(Error {
+                    message: format!("Expected struct or enum to construct, got '{}'", name),
+                    location: location.clone(),
+                })
+            }
+        };
+
+        for (field_name, value) in fields {
+            let field = match declared.get(field_name) {
+                Some(field) => field,
+                None => {
+                    return Err(Error {
+                        message: format!("Unknown field '{}' of '{}'", field_name, name),
+                        location: value.location.clone(),
+                    })
+                }
+            };
+
+            // Optional fields are set to a value
+            let field_type = match &field.type_ {
+                Type::Optional(inner) => inner.as_ref(),
+                ty => ty,
+            };
+            self.check_expected(value, field_type)?;
+        }
+
+        Ok(ty.clone())
+    }
+}
+
+/// Returns the type of the literal, using the expected type for numbers where it fits.
+fn check_literal(
+    literal: &Literal,
+    expected: Option<&Type>,
+    location: &Location,
+) -> Result<Type, Error> {
+    let ty = match (literal, expected) {
+        (Literal::Bool(_), _) => Type::Bool,
+        (Literal::String(_), _) => Type::String,
+        (Literal::Float(_), _) => Type::Float,
+        (Literal::Int(_), Some(ty)) if is_number(ty) => ty.clone(),
+        (Literal::Int(_), _) => Type::I32,
+    };
+
+    literal.type_check(&ty, location.clone())?;
+    Ok(ty)
+}
+
+fn expect_type(actual: &Type, expected: &Type, location: &Location) -> Result<(), Error> {
+    if actual != expected {
+        return Err(Error {
+            message: format!("Expected {}, got {}", expected, actual),
+            location: location.clone(),
+        });
+    }
+
+    Ok(())
+}
+
+fn is_number(ty: &Type) -> bool {
+    *ty == Type::Float || Literal::int_range(ty).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(input: &str) -> Result<(), Error> {
+        let lists = lisper::parse_str(input).unwrap();
+        Environment::parse(lists).map(|_| ())
+    }
+
+    const POINT: &str =
+        "(struct Point (fields (i32 x) (i32 y) (u8 level) (i32[] scores) (Point? next)))
+        (enum Shape (Circle (f32 radius)) (Square (f32 side)))
+        (enum Mood Happy Sad)
+        (const MAX-LEVEL u8 50)";
+
+    fn check_with_point(input: &str) -> Result<(), Error> {
+        check(&format!("{}\n{}", POINT, input))
+    }
+
+    #[test]
+    fn arithmetic_on_ints_returns_ok() {
+        let input = "(fn add ((i32 a) (i32 b)) i32 (+ a b 1))";
+        assert_eq!(check(input), Ok(()));
+    }
+
+    #[test]
+    fn literal_takes_width_of_operand() {
+        let input = "(fn next ((u8 level)) bool (< level 200))";
+        assert_eq!(check(input), Ok(()));
+    }
+
+    #[test]
+    fn literal_does_not_fit_width_returns_err() {
+        let input = "(fn next ((u8 level)) bool (< level 300))";
+        assert_eq!(check(input), Err("Value 300 does not fit in u8".into()));
+    }
+
+    #[test]
+    fn mixed_int_widths_returns_err() {
+        let input = "(fn add ((i32 a) (i64 b)) i64 (+ a b))";
+        assert_eq!(check(input), Err("Expected i32, got i64".into()));
+    }
+
+    #[test]
+    fn return_type_mismatch_returns_err() {
+        let input = "(fn name () string 42)";
+        assert_eq!(check(input), Err("Expected string, got i32".into()));
+    }
+
+    #[test]
+    fn float_arithmetic_returns_ok() {
+        let input = "(fn half ((f32 a)) f32 (/ a 2))";
+        assert_eq!(check(input), Ok(()));
+    }
+
+    #[test]
+    fn string_arithmetic_returns_err() {
+        let input = "(fn join ((string a) (string b)) string (+ a b))";
+        assert_eq!(
+            check(input),
+            Err("Operator '+' can not be used on 'string'".into())
+        );
+    }
+
+    #[test]
+    fn logical_operator_on_int_returns_err() {
+        let input = "(fn both ((bool a) (i32 b)) bool (and a b))";
+        assert_eq!(check(input), Err("Expected bool, got i32".into()));
+    }
+
+    #[test]
+    fn unknown_identifier_returns_err() {
+        let input = "(fn add ((i32 a)) i32 (+ a b))";
+        assert_eq!(check(input), Err("Unknown identifier 'b'".into()));
+    }
+
+    #[test]
+    fn unknown_function_returns_err() {
+        let input = "(fn add ((i32 a)) i32 (double a))";
+        assert_eq!(check(input), Err("Unknown function 'double'".into()));
+    }
+
+    #[test]
+    fn call_checks_arguments() {
+        let input = "(fn double ((i32 a)) i32 (* a 2))
+            (fn quad ((i32 a)) i32 (double (double a)))
+            (fn bad ((bool a)) i32 (double a))";
+        assert_eq!(check(input), Err("Expected i32, got bool".into()));
+    }
+
+    #[test]
+    fn call_wrong_argument_count_returns_err() {
+        let input = "(fn double ((i32 a)) i32 (* a 2))
+            (fn bad () i32 (double 1 2))";
+        assert_eq!(
+            check(input),
+            Err("Function 'double' takes 1 arguments, got 2".into())
+        );
+    }
+
+    #[test]
+    fn let_binding_is_visible_in_rest_of_body() {
+        let input = "(fn area ((i32 w) (i32 h)) i32 (let a (* w h)) a)";
+        assert_eq!(check(input), Ok(()));
+    }
+
+    #[test]
+    fn let_binding_is_not_visible_outside_branch() {
+        let input = "(fn f ((bool c)) i32 (if c (let a 1)) a)";
+        assert_eq!(check(input), Err("Unknown identifier 'a'".into()));
+    }
+
+    #[test]
+    fn field_access_returns_field_type() {
+        let input = "(fn sum ((in Point p)) i32 (+ (get p x) (get p y)))";
+        assert_eq!(check_with_point(input), Ok(()));
+    }
+
+    #[test]
+    fn unknown_field_returns_err() {
+        let input = "(fn sum ((in Point p)) i32 (get p z))";
+        assert_eq!(
+            check_with_point(input),
+            Err("Unknown field 'z' of 'Point'".into())
+        );
+    }
+
+    #[test]
+    fn get_on_int_returns_err() {
+        let input = "(fn sum ((i32 p)) i32 (get p x))";
+        assert_eq!(
+            check(input),
+            Err("Expected struct to get 'x' from, got 'i32'".into())
+        );
+    }
+
+    #[test]
+    fn for_over_list_binds_item() {
+        let input = "(fn total ((in Point p)) i32
+            (let sum 0)
+            (for score (get p scores) (set sum (+ sum score)))
+            sum)";
+        assert_eq!(check_with_point(input), Ok(()));
+    }
+
+    #[test]
+    fn for_over_non_list_returns_err() {
+        let input = "(fn total ((i32 p)) void (for x p x))";
+        assert_eq!(
+            check(input),
+            Err("Expected list to iterate, got 'i32'".into())
+        );
+    }
+
+    #[test]
+    fn set_in_parameter_returns_err() {
+        let input = "(fn reset ((in Point p)) void (set (get p x) 0))";
+        assert_eq!(
+            check_with_point(input),
+            Err("Can not set 'in' parameter 'p'".into())
+        );
+    }
+
+    #[test]
+    fn set_constant_returns_err() {
+        let input = "(fn reset () void (set MAX-LEVEL 0))";
+        assert_eq!(
+            check_with_point(input),
+            Err("Can not set constant 'MAX-LEVEL'".into())
+        );
+    }
+
+    #[test]
+    fn set_self_in_const_function_returns_err() {
+        let input = "(struct Counter (fields (i32 count))
+            (fn reset (self const) () void (set (get self count) 0)))";
+        assert_eq!(
+            check(input),
+            Err("Can not set self in const function 'reset'".into())
+        );
+    }
+
+    #[test]
+    fn self_in_struct_function_returns_ok() {
+        let input = "(struct Counter (fields (i32 count))
+            (fn bump () i32 (set (get self count) (+ (get self count) 1)) (get self count)))";
+        assert_eq!(check(input), Ok(()));
+    }
+
+    #[test]
+    fn self_in_static_function_returns_err() {
+        let input = "(struct Counter (fields (i32 count))
+            (static-fn zero () i32 (get self count)))";
+        assert_eq!(check(input), Err("Unknown identifier 'self'".into()));
+    }
+
+    #[test]
+    fn constant_has_declared_type() {
+        let input = "(fn capped ((u8 level)) bool (< level MAX-LEVEL))";
+        assert_eq!(check_with_point(input), Ok(()));
+    }
+
+    #[test]
+    fn construct_struct_checks_fields() {
+        let input = "(fn origin () Point (Point (x 0) (y 0) (level 1)))";
+        assert_eq!(check_with_point(input), Ok(()));
+
+        let input = "(fn origin () Point (Point (x true)))";
+        assert_eq!(
+            check_with_point(input),
+            Err("Expected i32, got bool".into())
+        );
+    }
+
+    #[test]
+    fn construct_optional_field_takes_value() {
+        let input = "(fn chain ((in Point p)) Point (Point (next p)))";
+        assert_eq!(check_with_point(input), Ok(()));
+    }
+
+    #[test]
+    fn construct_enum_variant_checks_values() {
+        let input = "(fn unit () Shape (Shape Circle (radius 1.0)))";
+        assert_eq!(check_with_point(input), Ok(()));
+
+        let input = "(fn unit () Shape (Shape Triangle))";
+        assert_eq!(
+            check_with_point(input),
+            Err("Unknown variant 'Triangle' of 'Shape'".into())
+        );
+
+        let input = "(fn unit () Shape (Shape Circle (side 1.0)))";
+        assert_eq!(
+            check_with_point(input),
+            Err("Unknown field 'side' of 'Shape'".into())
+        );
+    }
+
+    #[test]
+    fn compare_enum_variants_returns_ok() {
+        let input = "(fn happy? ((Mood m)) bool (= m (Mood Happy)))";
+        assert_eq!(check_with_point(input), Ok(()));
+    }
+
+    #[test]
+    fn compare_structs_returns_err() {
+        let input = "(fn same? ((in Point a) (in Point b)) bool (= a b))";
+        assert_eq!(
+            check_with_point(input),
+            Err("Operator '=' can not be used on 'Point'".into())
+        );
+    }
+
+    #[test]
+    fn if_branches_must_match() {
+        let input = "(fn pick ((bool c)) i32 (if c 1 \"one\"))";
+        assert_eq!(check(input), Err("Expected i32, got string".into()));
+    }
+
+    #[test]
+    fn if_condition_must_be_bool() {
+        let input = "(fn pick ((i32 c)) i32 (if c 1 2))";
+        assert_eq!(check(input), Err("Expected bool, got i32".into()));
+    }
+
+    #[test]
+    fn if_without_else_in_tail_returns_err() {
+        let input = "(fn pick ((bool c)) i32 (if c 1))";
+        assert_eq!(
+            check(input),
+            Err("Not every path of function 'pick' returns a value".into())
+        );
+    }
+
+    #[test]
+    fn cond_with_else_returns_ok() {
+        let input = "(fn sign ((i32 n)) i32 (cond ((< n 0) -1) ((= n 0) 0) (else 1)))";
+        assert_eq!(check(input), Ok(()));
+    }
+
+    #[test]
+    fn cond_without_else_in_tail_returns_err() {
+        let input = "(fn sign ((i32 n)) i32 (cond ((< n 0) -1)))";
+        assert_eq!(
+            check(input),
+            Err("Not every path of function 'sign' returns a value".into())
+        );
+    }
+
+    #[test]
+    fn early_return_returns_ok() {
+        let input = "(fn clamp ((i32 n)) i32 (if (> n 10) (return 10)) n)";
+        assert_eq!(check(input), Ok(()));
+    }
+
+    #[test]
+    fn return_in_branch_fits_value() {
+        let input = "(fn clamp ((i32 n)) i32 (if (> n 10) (return 10) n))";
+        assert_eq!(check(input), Ok(()));
+    }
+
+    #[test]
+    fn return_wrong_type_returns_err() {
+        let input = "(fn clamp ((i32 n)) i32 (if (> n 10) (return true)) n)";
+        assert_eq!(check(input), Err("Expected i32, got bool".into()));
+    }
+
+    #[test]
+    fn return_value_from_void_returns_err() {
+        let input = "(fn log ((i32 n)) void (return n))";
+        assert_eq!(
+            check(input),
+            Err("Function 'log' does not return a value".into())
+        );
+    }
+
+    #[test]
+    fn ends_with_let_returns_err() {
+        let input = "(fn area ((i32 w) (i32 h)) i32 (let a (* w h)))";
+        assert_eq!(
+            check(input),
+            Err("Not every path of function 'area' returns a value".into())
+        );
+    }
+
+    #[test]
+    fn call_in_module_resolves_sibling() {
+        let input = "(module combat
+            (fn double ((i32 a)) i32 (* a 2))
+            (fn quad ((i32 a)) i32 (double (double a))))";
+        assert_eq!(check(input), Ok(()));
+    }
+
+    #[test]
+    fn constant_in_module_resolves_sibling() {
+        let input = "(module combat
+            (const MAX-HIT i32 99)
+            (fn cap ((i32 damage)) i32 (if (> damage MAX-HIT) MAX-HIT damage)))";
+        assert_eq!(check(input), Ok(()));
+    }
+
+    #[test]
+    fn mut_argument_must_be_settable() {
+        let input = "(fn bump ((mut i32 a)) void (set a (+ a 1)))
+            (fn bad () void (bump 1))";
+        assert_eq!(check(input), Err("Can not set a temporary value".into()));
+    }
+
+    #[test]
+    fn out_argument_must_be_settable() {
+        let input = "(fn fill ((out i32 a)) void (set a 1))
+            (fn bad ((in i32 b)) void (fill (+ b 1)))";
+        assert_eq!(check(input), Err("Can not set a temporary value".into()));
+    }
+
+    const ACTION: &str =
+        "(enum Action Wait (Attack (i32 damage) (bool critical?)) (Move (i32 x) (i32 y)))";
+
+    fn check_with_action(input: &str) -> Result<(), Error> {
+        check(&format!("{}\n{}", ACTION, input))
+    }
+
+    #[test]
+    fn match_every_variant_returns_ok() {
+        let input = "(fn cost ((in Action a)) i32
+            (match a (Wait 0) ((Attack damage) (* damage 2)) ((Move x y) (+ x y))))";
+        assert_eq!(check_with_action(input), Ok(()));
+    }
+
+    #[test]
+    fn match_wildcard_returns_ok() {
+        let input = "(fn cost ((in Action a)) i32 (match a ((Attack damage) damage) (_ 0)))";
+        assert_eq!(check_with_action(input), Ok(()));
+    }
+
+    #[test]
+    fn match_missing_variant_returns_err() {
+        let input = "(fn cost ((in Action a)) i32 (match a (Wait 0) ((Attack damage) damage)))";
+        assert_eq!(
+            check_with_action(input),
+            Err("Match on 'Action' in function 'cost' is missing variant 'Move'".into())
+        );
+    }
+
+    #[test]
+    fn match_new_variant_reports_every_match() {
+        let input = "(fn cost ((in Action a)) i32 (match a (Wait 0) (Attack 1)))
+            (fn name ((in Action a)) string (match a (Wait \"wait\") (Attack \"attack\")))";
+        assert_eq!(
+            check_with_action(input),
+            Err(
+                "Match on 'Action' in function 'cost' is missing variant 'Move'
+Match on 'Action' in function 'name' is missing variant 'Move'"
+                    .into()
+            )
+        );
+    }
+
+    #[test]
+    fn match_duplicate_arm_returns_err() {
+        let input = "(fn cost ((in Action a)) i32 (match a (Wait 0) (Wait 1) (_ 2)))";
+        assert_eq!(
+            check_with_action(input),
+            Err("Unreachable arm 'Wait' in function 'cost', the variant is already matched".into())
+        );
+    }
+
+    #[test]
+    fn match_arm_after_wildcard_returns_err() {
+        let input = "(fn cost ((in Action a)) i32 (match a (_ 0) (Wait 1)))";
+        assert_eq!(
+            check_with_action(input),
+            Err(
+                "Unreachable arm in function 'cost', the '_' arm before it matches every variant"
+                    .into()
+            )
+        );
+    }
+
+    #[test]
+    fn match_wildcard_after_every_variant_returns_err() {
+        let input = "(fn cost ((in Action a)) i32 (match a (Wait 0) (Attack 1) (Move 2) (_ 3)))";
+        assert_eq!(
+            check_with_action(input),
+            Err("Unreachable arm '_' in function 'cost', every variant of 'Action' is already matched".into())
+        );
+    }
+
+    #[test]
+    fn match_unknown_binding_returns_err() {
+        let input = "(fn cost ((in Action a)) i32 (match a ((Move x z) x) (_ 0)))";
+        assert_eq!(
+            check_with_action(input),
+            Err("Variant 'Move' of 'Action' has no value 'z'".into())
+        );
+    }
+
+    #[test]
+    fn match_binding_has_value_type() {
+        let input = "(fn cost ((in Action a)) i32 (match a ((Attack critical?) critical?) (_ 0)))";
+        assert_eq!(
+            check_with_action(input),
+            Err("Expected i32, got bool".into())
+        );
+    }
+
+    #[test]
+    fn match_unknown_variant_returns_err() {
+        let input = "(fn cost ((in Action a)) i32 (match a (Jump 0) (_ 1)))";
+        assert_eq!(
+            check_with_action(input),
+            Err("Unknown variant 'Jump' of 'Action'".into())
+        );
+    }
+
+    #[test]
+    fn match_on_struct_returns_err() {
+        let input = "(fn cost ((in Point p)) i32 (match p (_ 0)))";
+        assert_eq!(
+            check_with_point(input),
+            Err("Expected enum to match, got 'Point'".into())
+        );
+    }
+
+    #[test]
+    fn contracts_over_parameters_and_result_returns_ok() {
+        let input = "(fn level-up ((in Point p)) u8
+            (requires (< (get p level) MAX-LEVEL))
+            (ensures (> result (get p level)))
+            (+ (get p level) 1))";
+        assert_eq!(check_with_point(input), Ok(()));
+    }
+
+    #[test]
+    fn contracts_over_fields_returns_ok() {
+        let input = "(struct Counter (fields (i32 count))
+            (fn next (self const) () i32 (requires (>= (get self count) 0)) (ensures (> result 0))))";
+        assert_eq!(check(input), Ok(()));
+    }
+
+    #[test]
+    fn contract_not_bool_returns_err() {
+        let input = "(fn next ((i32 n)) i32 (requires (+ n 1)) n)";
+        assert_eq!(check(input), Err("Expected bool, got i32".into()));
+    }
+
+    #[test]
+    fn ensures_result_of_void_function_returns_err() {
+        let input = "(fn reset ((mut i32 n)) void (ensures (= result 0)) (set n 0))";
+        assert_eq!(check(input), Err("Unknown identifier 'result'".into()));
+    }
+
+    #[test]
+    fn contract_set_returns_err() {
+        let input = "(fn reset ((mut i32 n)) void (requires (do (set n 0) true)))";
+        assert_eq!(
+            check(input),
+            Err("Conditions can only read values, got 'set' in function 'reset'".into())
+        );
+    }
+}